@@ -0,0 +1,120 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use zip::result::ZipError;
+
+// separates the archive from the member in `archive.zip::program.svo`
+pub const SEPARATOR: &str = "::";
+
+#[derive(Debug)]
+pub enum ArchiveError {
+    Io { path: PathBuf, error: io::Error },
+    Zip { path: PathBuf, error: ZipError },
+    Missing { path: PathBuf, entry: String },
+    UnknownFormat { path: PathBuf },
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArchiveError::Io { path, error } => {
+                write!(f, "failed to read {}: {}", path.display(), error)
+            }
+            ArchiveError::Zip { path, error } => write!(f, "{}: {}", path.display(), error),
+            ArchiveError::Missing { path, entry } => {
+                write!(f, "{} has no file {}", path.display(), entry)
+            }
+            ArchiveError::UnknownFormat { path } => write!(
+                f,
+                "{} is not a .zip, .tar, .tar.gz or .tgz archive",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+// `archive.zip::program.svo` split in two, `None` for a plain path
+pub fn split_path(path: &str) -> Option<(&Path, &str)> {
+    let (archive, entry) = path.split_once(SEPARATOR)?;
+    is_archive(Path::new(archive)).then_some((Path::new(archive), entry))
+}
+
+fn is_archive(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    [".zip", ".tar", ".tar.gz", ".tgz"]
+        .iter()
+        .any(|extension| name.ends_with(extension))
+}
+
+// reads one member into memory, nothing is extracted to disk
+pub fn read_entry(archive: &Path, entry: &str) -> Result<String, ArchiveError> {
+    let io_error = |error| ArchiveError::Io {
+        path: archive.to_path_buf(),
+        error,
+    };
+    let file = File::open(archive).map_err(io_error)?;
+    let name = archive.to_string_lossy();
+
+    let mut contents = String::new();
+    if name.ends_with(".zip") {
+        let zip_error = |error| ArchiveError::Zip {
+            path: archive.to_path_buf(),
+            error,
+        };
+        let mut zip = zip::ZipArchive::new(file).map_err(zip_error)?;
+        let mut member = match zip.by_name(entry) {
+            Ok(member) => member,
+            Err(ZipError::FileNotFound) => return Err(missing(archive, entry)),
+            Err(error) => return Err(zip_error(error)),
+        };
+        member.read_to_string(&mut contents).map_err(io_error)?;
+    } else if name.ends_with(".tar") {
+        read_tar_entry(tar::Archive::new(file), archive, entry, &mut contents)?;
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        let tar = tar::Archive::new(GzDecoder::new(file));
+        read_tar_entry(tar, archive, entry, &mut contents)?;
+    } else {
+        return Err(ArchiveError::UnknownFormat {
+            path: archive.to_path_buf(),
+        });
+    }
+
+    Ok(contents)
+}
+
+// tar has no index, the members are scanned in order until one matches
+fn read_tar_entry<R: Read>(
+    mut tar: tar::Archive<R>,
+    archive: &Path,
+    entry: &str,
+    contents: &mut String,
+) -> Result<(), ArchiveError> {
+    let io_error = |error| ArchiveError::Io {
+        path: archive.to_path_buf(),
+        error,
+    };
+
+    for member in tar.entries().map_err(io_error)? {
+        let mut member = member.map_err(io_error)?;
+        let path = member.path().map_err(io_error)?;
+        // tar run on `.` stores members as `./program.svo`
+        if path.strip_prefix(".").unwrap_or(&path) == Path::new(entry) {
+            member.read_to_string(contents).map_err(io_error)?;
+            return Ok(());
+        }
+    }
+
+    Err(missing(archive, entry))
+}
+
+fn missing(archive: &Path, entry: &str) -> ArchiveError {
+    ArchiveError::Missing {
+        path: archive.to_path_buf(),
+        entry: entry.to_string(),
+    }
+}
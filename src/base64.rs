@@ -0,0 +1,55 @@
+// standard alphabet with `=` padding
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | (byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(group >> (18 - 6 * i)) as usize & 0x3f] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+pub fn decode(text: &str) -> Result<Vec<u8>, String> {
+    if !text.len().is_multiple_of(4) {
+        return Err(format!(
+            "base64 string has length {}, expected a multiple of 4",
+            text.len()
+        ));
+    }
+
+    let data = text.trim_end_matches('=');
+    if text.len() - data.len() > 2 {
+        return Err("base64 string has more than two `=` of padding".to_string());
+    }
+
+    let mut out = Vec::with_capacity(data.len() * 3 / 4);
+    let mut group = 0u32;
+    for (i, c) in data.char_indices() {
+        let value = ALPHABET
+            .iter()
+            .position(|&letter| letter as char == c)
+            .ok_or_else(|| format!("invalid base64 character `{}` at offset {}", c, i))?;
+        group = group << 6 | value as u32;
+        if i % 4 == 3 {
+            out.extend_from_slice(&group.to_be_bytes()[1..]);
+            group = 0;
+        }
+    }
+
+    // a final group of two or three characters holds one or two bytes
+    match data.len() % 4 {
+        2 => out.push((group >> 4) as u8),
+        3 => out.extend_from_slice(&((group >> 2) as u16).to_be_bytes()),
+        _ => (),
+    }
+    Ok(out)
+}
@@ -0,0 +1,141 @@
+use std::time::Duration;
+
+use crate::json::Value;
+
+// a run slower than its previous time by more than this factor counts as a regression
+pub const REGRESSION_THRESHOLD: f64 = 1.1;
+
+// a program built into the binary for the `benchmark` subcommand
+pub struct Benchmark {
+    pub name: &'static str,
+    pub source: &'static str,
+    // sha256 of everything the program prints, a run that prints anything else failed
+    pub output_sha256: &'static str,
+    // median time on the reference machine, a release build on an x86_64 CPU with AVX2
+    // running the bytecode VM on `--opt-level 3` output
+    pub reference: Duration,
+}
+
+pub const SUITE: [Benchmark; 3] = [
+    Benchmark {
+        name: "mandelbrot",
+        source: include_str!("../examples/mandelbrot.svo"),
+        output_sha256: "83a0aac65090b3b5e85c22337afac39d8ac17bfd88675f044b33bd55ca0c351b",
+        reference: Duration::from_millis(6420),
+    },
+    Benchmark {
+        name: "fibonacci",
+        source: include_str!("../examples/fibonacci.svo"),
+        output_sha256: "fac89bae5eac39640ce768446a28ff0770328c8c93ff079036e6ecad6ecbc20f",
+        reference: Duration::from_micros(58_400),
+    },
+    Benchmark {
+        name: "scan",
+        source: include_str!("../examples/scan.svo"),
+        output_sha256: "dc51b8c96c2d745df3bd5590d990230a482fd247123599548e0632fdbf97fc22",
+        reference: Duration::from_micros(237_500),
+    },
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkResult {
+    pub name: String,
+    pub min: Duration,
+    pub median: Duration,
+}
+
+// one run of the suite, what `--json` prints and `--compare` reads back
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkReport {
+    pub score: u64,
+    pub results: Vec<BenchmarkResult>,
+}
+
+impl BenchmarkReport {
+    // the score is 1000 on the reference machine and scales with speed, so a machine
+    // twice as fast on every benchmark scores 2000. the speedups are averaged
+    // geometrically so no single benchmark dominates
+    pub fn new(results: Vec<BenchmarkResult>) -> BenchmarkReport {
+        let speedups: Vec<f64> = results
+            .iter()
+            .filter_map(|result| {
+                let benchmark = SUITE.iter().find(|b| b.name == result.name)?;
+                Some(benchmark.reference.as_secs_f64() / result.median.as_secs_f64())
+            })
+            .collect();
+        let mean =
+            speedups.iter().map(|speedup| speedup.ln()).sum::<f64>() / speedups.len().max(1) as f64;
+        BenchmarkReport {
+            score: (1000.0 * mean.exp()).round() as u64,
+            results,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let results = self
+            .results
+            .iter()
+            .map(|result| {
+                Value::object([
+                    ("name", result.name.as_str().into()),
+                    ("min_ns", (result.min.as_nanos() as u64).into()),
+                    ("median_ns", (result.median.as_nanos() as u64).into()),
+                ])
+            })
+            .collect::<Vec<Value>>();
+        Value::object([("score", self.score.into()), ("results", results.into())])
+    }
+
+    pub fn from_json(json: &Value) -> Result<BenchmarkReport, String> {
+        let score = json
+            .get("score")
+            .and_then(Value::as_u64)
+            .ok_or("missing `score`")?;
+        let results = json
+            .get("results")
+            .and_then(Value::as_array)
+            .ok_or("missing `results`")?
+            .iter()
+            .map(|result| {
+                let nanos = |key: &str| {
+                    result
+                        .get(key)
+                        .and_then(Value::as_u64)
+                        .map(Duration::from_nanos)
+                        .ok_or_else(|| format!("result without `{}`", key))
+                };
+                Ok(BenchmarkResult {
+                    name: result
+                        .get("name")
+                        .and_then(Value::as_str)
+                        .ok_or("result without `name`")?
+                        .to_string(),
+                    min: nanos("min_ns")?,
+                    median: nanos("median_ns")?,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(BenchmarkReport { score, results })
+    }
+
+    // each benchmark in both reports with its median now and before, benchmarks missing
+    // from `previous` are left out
+    pub fn compare<'a>(
+        &'a self,
+        previous: &'a BenchmarkReport,
+    ) -> Vec<(&'a BenchmarkResult, &'a BenchmarkResult)> {
+        self.results
+            .iter()
+            .filter_map(|result| {
+                let before = previous.results.iter().find(|b| b.name == result.name)?;
+                Some((result, before))
+            })
+            .collect()
+    }
+}
+
+impl BenchmarkResult {
+    pub fn regressed_from(&self, previous: &BenchmarkResult) -> bool {
+        self.median.as_secs_f64() > previous.median.as_secs_f64() * REGRESSION_THRESHOLD
+    }
+}
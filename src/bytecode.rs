@@ -0,0 +1,210 @@
+use tracing::info;
+
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::parser::Instruction;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteCode {
+    Add(u8),
+    Move(isize),
+    Set(u8),
+    BulkZero {
+        start_offset: isize,
+        len: usize,
+    },
+    FindZero(isize),
+    MultiplyAdd {
+        offset: isize,
+        factor: u8,
+    },
+    CopyRange {
+        src_offset: isize,
+        dst_offset: isize,
+        len: usize,
+    },
+    Write,
+    Read,
+    // both jumps land just past their partner
+    JumpIfZero(usize),
+    JumpIfNonZero(usize),
+    // a `MacroCall` nobody expanded, fails when reached
+    UnexpandedMacro,
+}
+
+// a program flattened so loops become jumps, one bytecode per instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ByteCodeProgram {
+    pub code: Vec<ByteCode>,
+}
+
+impl ByteCodeProgram {
+    pub fn compile(instructions: &[Instruction]) -> Self {
+        let mut code = Vec::new();
+        emit(instructions, &mut code);
+        ByteCodeProgram { code }
+    }
+}
+
+fn emit(instructions: &[Instruction], code: &mut Vec<ByteCode>) {
+    for instr in instructions {
+        let op = match instr {
+            Instruction::IncrementPointer => ByteCode::Move(1),
+            Instruction::DecrementPointer => ByteCode::Move(-1),
+            Instruction::Increment => ByteCode::Add(1),
+            Instruction::Decrement => ByteCode::Add(u8::MAX),
+            Instruction::Write => ByteCode::Write,
+            Instruction::Read => ByteCode::Read,
+            Instruction::Add(n) => ByteCode::Add(*n),
+            Instruction::Move(offset) => ByteCode::Move(*offset),
+            Instruction::SetCell(value) => ByteCode::Set(*value),
+            Instruction::BulkZero { start_offset, len } => ByteCode::BulkZero {
+                start_offset: *start_offset,
+                len: *len,
+            },
+            Instruction::FindZero { direction } => ByteCode::FindZero(*direction),
+            Instruction::MultiplyAdd { offset, factor } => ByteCode::MultiplyAdd {
+                offset: *offset,
+                factor: *factor,
+            },
+            Instruction::CopyRange {
+                src_offset,
+                dst_offset,
+                len,
+            } => ByteCode::CopyRange {
+                src_offset: *src_offset,
+                dst_offset: *dst_offset,
+                len: *len,
+            },
+            Instruction::Loop(body) => {
+                let begin = code.len();
+                code.push(ByteCode::JumpIfZero(0));
+                emit(body, code);
+                code.push(ByteCode::JumpIfNonZero(begin + 1));
+                code[begin] = ByteCode::JumpIfZero(code.len());
+                continue;
+            }
+            Instruction::MacroCall { .. } => ByteCode::UnexpandedMacro,
+            Instruction::DebugPrint { .. } => continue,
+        };
+        code.push(op);
+    }
+}
+
+// runs bytecode in a flat dispatch loop on top of an interpreter's tape, i/o and limits,
+// tracing, coverage, profiling and loop limits need the tree-walking `Interpreter::run`
+pub struct ByteCodeVM {
+    pub program: ByteCodeProgram,
+    pub interpreter: Interpreter,
+}
+
+impl ByteCodeVM {
+    pub fn new(program: ByteCodeProgram, interpreter: Interpreter) -> Self {
+        ByteCodeVM {
+            program,
+            interpreter,
+        }
+    }
+
+    pub fn run(&mut self) -> Result<(), RuntimeError> {
+        info!(bytecodes = self.program.code.len(), "program started");
+        let watchdog = self.interpreter.start_watchdog();
+        let result = self.dispatch();
+        drop(watchdog);
+        self.interpreter.finish_output()?;
+        info!(
+            steps = self.interpreter.steps(),
+            ok = result.is_ok(),
+            "program finished"
+        );
+        result
+    }
+
+    fn dispatch(&mut self) -> Result<(), RuntimeError> {
+        let code = &self.program.code;
+        let interpreter = &mut self.interpreter;
+        let limit = interpreter.config().max_steps;
+        let mut steps = interpreter.steps();
+        let mut pc = 0;
+
+        // steps are counted the same way as `run`, so limits trip at the same point
+        let result = loop {
+            let Some(op) = code.get(pc) else {
+                break Ok(());
+            };
+
+            let tape = interpreter.tape_mut();
+            pc = match *op {
+                ByteCode::Add(n) => {
+                    tape.set(tape.get().wrapping_add(n));
+                    pc + 1
+                }
+                ByteCode::Move(offset) => {
+                    if let Err(err) = tape.move_by(offset) {
+                        break Err(err);
+                    }
+                    pc + 1
+                }
+                ByteCode::Set(value) => {
+                    tape.set(value);
+                    pc + 1
+                }
+                ByteCode::BulkZero { start_offset, len } => {
+                    if let Err(err) = tape.zero_range(start_offset, len) {
+                        break Err(err);
+                    }
+                    pc + 1
+                }
+                ByteCode::FindZero(direction) => {
+                    if let Err(err) = tape.find_zero(direction) {
+                        break Err(err);
+                    }
+                    pc + 1
+                }
+                ByteCode::MultiplyAdd { offset, factor } => {
+                    if let Err(err) = tape.multiply_add(offset, factor) {
+                        break Err(err);
+                    }
+                    pc + 1
+                }
+                ByteCode::CopyRange {
+                    src_offset,
+                    dst_offset,
+                    len,
+                } => {
+                    if let Err(err) = tape.copy_range(src_offset, dst_offset, len) {
+                        break Err(err);
+                    }
+                    pc + 1
+                }
+                ByteCode::Write | ByteCode::Read => {
+                    let instr = if *op == ByteCode::Write {
+                        Instruction::Write
+                    } else {
+                        Instruction::Read
+                    };
+                    if let Err(err) = interpreter.execute(&instr) {
+                        break Err(err);
+                    }
+                    pc + 1
+                }
+                ByteCode::JumpIfZero(target) if tape.get() == 0 => target,
+                ByteCode::JumpIfNonZero(target) if tape.get() != 0 => target,
+                ByteCode::JumpIfZero(_) | ByteCode::JumpIfNonZero(_) => pc + 1,
+                ByteCode::UnexpandedMacro => break Err(RuntimeError::UnexpandedMacro),
+            };
+
+            steps += 1;
+            if let Some(limit) = limit {
+                if steps > limit {
+                    break Err(RuntimeError::StepLimitExceeded { limit });
+                }
+            }
+            if let Err(err) = interpreter.check_cancelled(steps) {
+                break Err(err);
+            }
+        };
+
+        interpreter.set_steps(steps);
+        result
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+use crate::bytecode::ByteCodeProgram;
+use crate::lexer::lex;
+use crate::optimize::optimize;
+use crate::parser::{parse, ParseError};
+
+#[derive(Debug)]
+pub enum CacheError {
+    Io { path: PathBuf, error: io::Error },
+    Parse { path: PathBuf, error: ParseError },
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CacheError::Io { path, error } => {
+                write!(f, "failed to read {}: {}", path.display(), error)
+            }
+            CacheError::Parse { path, error } => write!(f, "{}: {}", path.display(), error),
+        }
+    }
+}
+
+impl std::error::Error for CacheError {}
+
+// parsed, optimized and compiled bytecode for a source file, tagged with the
+// file's modification time so `load` can tell when it went stale
+#[derive(Debug, Clone)]
+pub struct CachedProgram {
+    pub path: PathBuf,
+    pub modified: SystemTime,
+    pub bytecode: Arc<ByteCodeProgram>,
+}
+
+// per-process, nothing is written to disk
+fn cache() -> &'static Mutex<HashMap<PathBuf, CachedProgram>> {
+    static CACHE: OnceLock<Mutex<HashMap<PathBuf, CachedProgram>>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+fn modified(path: &Path) -> Result<SystemTime, CacheError> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .map_err(|error| CacheError::Io {
+            path: path.to_path_buf(),
+            error,
+        })
+}
+
+impl CachedProgram {
+    // always reads and compiles the file, replacing whatever was cached for it
+    pub fn from_file(path: &Path) -> Result<Self, CacheError> {
+        let modified = modified(path)?;
+        let source = fs::read_to_string(path).map_err(|error| CacheError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        let instructions = parse(&lex(&source)).map_err(|error| CacheError::Parse {
+            path: path.to_path_buf(),
+            error,
+        })?;
+
+        let program = CachedProgram {
+            path: path.to_path_buf(),
+            modified,
+            bytecode: Arc::new(ByteCodeProgram::compile(&optimize(instructions))),
+        };
+        cache()
+            .lock()
+            .expect("program cache lock poisoned")
+            .insert(program.path.clone(), program.clone());
+        Ok(program)
+    }
+
+    // returns the cached bytecode while the file's modification time is unchanged,
+    // otherwise recompiles it through `from_file`
+    pub fn load(path: &Path) -> Result<Self, CacheError> {
+        let modified = modified(path)?;
+        let cached = cache()
+            .lock()
+            .expect("program cache lock poisoned")
+            .get(path)
+            .filter(|cached| cached.modified == modified)
+            .cloned();
+
+        match cached {
+            Some(cached) => Ok(cached),
+            None => CachedProgram::from_file(path),
+        }
+    }
+}
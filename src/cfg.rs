@@ -0,0 +1,146 @@
+use std::fmt::Write;
+
+use crate::parser::Instruction;
+use crate::program::Program;
+
+fn label(instr: &Instruction) -> String {
+    match instr {
+        Instruction::Add(n) => format!("ADD {}", n),
+        Instruction::Move(offset) => format!("MOVE {}", offset),
+        Instruction::SetCell(value) => format!("SET {}", value),
+        Instruction::BulkZero { start_offset, len } => format!("ZERO {} {}", start_offset, len),
+        Instruction::FindZero { direction } => format!("FIND {}", direction),
+        Instruction::MultiplyAdd { offset, factor } => format!("MULADD {} {}", offset, factor),
+        Instruction::CopyRange {
+            src_offset,
+            dst_offset,
+            len,
+        } => format!("COPY {} {} {}", src_offset, dst_offset, len),
+        Instruction::MacroCall { name, args } => format!("MACRO {} {:?}", name, args),
+        other => other.mnemonic().to_string(),
+    }
+}
+
+struct Graph {
+    out: String,
+    nodes: usize,
+}
+
+impl Graph {
+    fn node(&mut self, attributes: &str) -> String {
+        let name = format!("n{}", self.nodes);
+        self.nodes += 1;
+        let _ = writeln!(self.out, "    {} [{}];", name, attributes);
+        name
+    }
+
+    fn edge(&mut self, from: &str, to: &str, attributes: &str) {
+        if attributes.is_empty() {
+            let _ = writeln!(self.out, "    {} -> {};", from, to);
+        } else {
+            let _ = writeln!(self.out, "    {} -> {} [{}];", from, to, attributes);
+        }
+    }
+
+    fn connect(&mut self, preds: &[(String, &'static str)], to: &str) {
+        for (from, attributes) in preds {
+            self.edge(from, to, attributes);
+        }
+    }
+
+    // emits the blocks of `instructions` and returns the edges still waiting for a target
+    fn block(
+        &mut self,
+        instructions: &[Instruction],
+        mut position: usize,
+        mut preds: Vec<(String, &'static str)>,
+    ) -> Vec<(String, &'static str)> {
+        let mut straight: Vec<String> = Vec::new();
+
+        for (i, instr) in instructions.iter().enumerate() {
+            if let Instruction::Loop(body) = instr {
+                preds = self.flush(&mut straight, preds);
+
+                let header = self.node(&format!("shape=diamond, label=\"loop #{}\"", position));
+                self.connect(&preds, &header);
+
+                let body_preds = vec![(header.clone(), "label=\"!= 0\"")];
+                for (from, attributes) in self.block(body, position + 1, body_preds) {
+                    let attributes = if attributes.is_empty() {
+                        "style=dashed".to_string()
+                    } else {
+                        format!("{}, style=dashed", attributes)
+                    };
+                    let _ = writeln!(self.out, "    {} -> {} [{}];", from, header, attributes);
+                }
+
+                preds = vec![(header, "label=\"== 0\"")];
+            } else {
+                let text = label(instr);
+                // collapse runs like eight increments into `INC x8`
+                let repeat = instructions[..i]
+                    .iter()
+                    .rev()
+                    .take_while(|prev| !matches!(prev, Instruction::Loop(_)) && label(prev) == text)
+                    .count();
+                if repeat > 0 {
+                    let last = straight
+                        .last_mut()
+                        .expect("previous instruction is in the block");
+                    *last = format!("{} x{}", text, repeat + 1);
+                } else {
+                    straight.push(text);
+                }
+            }
+
+            position += instr.opcode_len();
+        }
+
+        self.flush(&mut straight, preds)
+    }
+
+    fn flush(
+        &mut self,
+        straight: &mut Vec<String>,
+        preds: Vec<(String, &'static str)>,
+    ) -> Vec<(String, &'static str)> {
+        if straight.is_empty() {
+            return preds;
+        }
+
+        let text: String = straight
+            .drain(..)
+            .map(|line| format!("{}\\l", line))
+            .collect();
+        let node = self.node(&format!("label=\"{}\"", text));
+        self.connect(&preds, &node);
+        vec![(node, "")]
+    }
+}
+
+impl Program {
+    // control flow graph in Graphviz DOT, loop back-edges are dashed
+    pub fn to_dot(&self) -> String {
+        let mut graph = Graph {
+            out: String::new(),
+            nodes: 0,
+        };
+
+        graph.out.push_str("digraph cfg {\n");
+        graph
+            .out
+            .push_str("    node [shape=box, fontname=\"monospace\"];\n");
+        graph
+            .out
+            .push_str("    entry [shape=Mdiamond, label=\"entry\"];\n");
+        graph
+            .out
+            .push_str("    exit [shape=Msquare, label=\"exit\"];\n");
+
+        let preds = graph.block(&self.instructions, 0, vec![("entry".to_string(), "")]);
+        graph.connect(&preds, "exit");
+
+        graph.out.push_str("}\n");
+        graph.out
+    }
+}
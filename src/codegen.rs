@@ -0,0 +1,305 @@
+use std::env;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::interpreter::{InterpreterConfig, IoMode, RuntimeError, WriteMode};
+use crate::parser::Instruction;
+
+// translates a program into a standalone C file, libc stdio serves as the runtime. fails
+// when the config asks for something the C wouldn't do the way the interpreter does
+pub fn to_c(instructions: &[Instruction], config: &InterpreterConfig) -> Result<String, String> {
+    check_supported(config)?;
+    Ok(generate(instructions, config, true))
+}
+
+// like `to_c` but the program never touches stdin, every read fails the way it does once
+// input runs out
+pub fn to_c_without_stdin(
+    instructions: &[Instruction],
+    config: &InterpreterConfig,
+) -> Result<String, String> {
+    check_supported(config)?;
+    Ok(generate(instructions, config, false))
+}
+
+// the generated C writes raw bytes and runs unbounded, so a config that changes either
+// would give a binary that behaves differently from `run`
+fn check_supported(config: &InterpreterConfig) -> Result<(), String> {
+    let unsupported = [
+        ("write_mode", config.write_mode != WriteMode::Byte),
+        ("max_steps", config.max_steps.is_some()),
+        ("max_output_bytes", config.max_output_bytes.is_some()),
+        ("timeout", config.timeout.is_some()),
+        ("max_loop_iterations", config.max_loop_iterations.is_some()),
+        (
+            "instruction_limit_per_loop",
+            config.instruction_limit_per_loop.is_some(),
+        ),
+    ];
+    match unsupported.iter().find(|(_, set)| *set) {
+        Some((key, _)) => Err(format!(
+            "`{}` isn't supported by generated C, leave it unset",
+            key
+        )),
+        None => Ok(()),
+    }
+}
+
+fn generate(instructions: &[Instruction], config: &InterpreterConfig, stdin: bool) -> String {
+    let messages = Messages {
+        underflow: RuntimeError::PointerUnderflow.to_string(),
+        overflow: RuntimeError::PointerOverflow {
+            tape_size: config.tape_size,
+        }
+        .to_string(),
+        // matches what `read_exact` reports when stdin runs dry
+        eof: RuntimeError::Io(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "failed to fill whole buffer",
+        ))
+        .to_string(),
+        io_mode: config.io_mode,
+        stdin,
+    };
+
+    let mut out = String::new();
+    out.push_str("#include <stdio.h>\n#include <stdlib.h>\n#include <string.h>\n\n");
+    let _ = writeln!(out, "#define TAPE_SIZE {}", config.tape_size);
+    let _ = writeln!(out, "#define START_POINTER {}\n", config.start_pointer);
+    let mut initial = vec![0; config.tape_size];
+    config.tape_init.fill(&mut initial);
+    if initial.iter().all(|&cell| cell == 0) {
+        out.push_str("static unsigned char tape[TAPE_SIZE];\n\n");
+    } else {
+        out.push_str("static unsigned char tape[TAPE_SIZE] = {");
+        for (i, cell) in initial.iter().enumerate() {
+            if i % 16 == 0 {
+                out.push_str("\n   ");
+            }
+            let _ = write!(out, " {},", cell);
+        }
+        out.push_str("\n};\n\n");
+    }
+    out.push_str("static void svo_fail(const char *message) {\n");
+    out.push_str("    fflush(stdout);\n");
+    out.push_str("    fprintf(stderr, \"error: runtime error: %s\\n\", message);\n");
+    out.push_str("    exit(1);\n}\n\n");
+    out.push_str("int main(void) {\n");
+    out.push_str("    unsigned char *p = tape + START_POINTER;\n");
+
+    emit_block(&mut out, instructions, 1, &messages);
+
+    out.push_str("    return 0;\n}\n");
+    out
+}
+
+struct Messages {
+    underflow: String,
+    overflow: String,
+    eof: String,
+    io_mode: IoMode,
+    stdin: bool,
+}
+
+fn emit_block(out: &mut String, instructions: &[Instruction], depth: usize, messages: &Messages) {
+    let indent = "    ".repeat(depth);
+
+    for instr in instructions {
+        match instr {
+            Instruction::IncrementPointer => {
+                let _ = writeln!(
+                    out,
+                    "{}if (++p == tape + TAPE_SIZE) svo_fail({:?});",
+                    indent, messages.overflow
+                );
+            }
+            Instruction::DecrementPointer => {
+                let _ = writeln!(
+                    out,
+                    "{}if (p == tape) svo_fail({:?});",
+                    indent, messages.underflow
+                );
+                let _ = writeln!(out, "{}--p;", indent);
+            }
+            Instruction::Increment => {
+                let _ = writeln!(out, "{}++*p;", indent);
+            }
+            Instruction::Decrement => {
+                let _ = writeln!(out, "{}--*p;", indent);
+            }
+            Instruction::Write | Instruction::Read if messages.io_mode == IoMode::Sandboxed => (),
+            Instruction::Write | Instruction::Read if messages.io_mode == IoMode::Forbidden => {
+                let _ = writeln!(
+                    out,
+                    "{}svo_fail({:?});",
+                    indent,
+                    RuntimeError::IoForbidden.to_string()
+                );
+            }
+            Instruction::Write => {
+                let _ = writeln!(out, "{}putchar(*p);", indent);
+            }
+            Instruction::Read if !messages.stdin => {
+                let _ = writeln!(out, "{}svo_fail({:?});", indent, messages.eof);
+            }
+            Instruction::Read => {
+                let _ = writeln!(out, "{}{{", indent);
+                let _ = writeln!(out, "{}    int c = getchar();", indent);
+                let _ = writeln!(
+                    out,
+                    "{}    if (c == EOF) svo_fail({:?});",
+                    indent, messages.eof
+                );
+                let _ = writeln!(out, "{}    *p = (unsigned char)c;", indent);
+                let _ = writeln!(out, "{}}}", indent);
+            }
+            Instruction::Loop(body) => {
+                let _ = writeln!(out, "{}while (*p) {{", indent);
+                emit_block(out, body, depth + 1, messages);
+                let _ = writeln!(out, "{}}}", indent);
+            }
+            Instruction::Add(n) => {
+                let _ = writeln!(out, "{}*p += {};", indent, n);
+            }
+            Instruction::Move(offset) if *offset >= 0 => {
+                let _ = writeln!(
+                    out,
+                    "{}if (TAPE_SIZE - (p - tape) <= {}) svo_fail({:?});",
+                    indent, offset, messages.overflow
+                );
+                let _ = writeln!(out, "{}p += {};", indent, offset);
+            }
+            Instruction::Move(offset) => {
+                let _ = writeln!(
+                    out,
+                    "{}if (p - tape < {}) svo_fail({:?});",
+                    indent,
+                    offset.unsigned_abs(),
+                    messages.underflow
+                );
+                let _ = writeln!(out, "{}p -= {};", indent, offset.unsigned_abs());
+            }
+            Instruction::SetCell(value) => {
+                let _ = writeln!(out, "{}*p = {};", indent, value);
+            }
+            Instruction::BulkZero { start_offset, len } => {
+                emit_range_check(out, &indent, *start_offset, *len, messages);
+                let _ = writeln!(out, "{}memset(p + ({}), 0, {});", indent, start_offset, len);
+            }
+            Instruction::CopyRange {
+                src_offset,
+                dst_offset,
+                len,
+            } => {
+                emit_range_check(out, &indent, *src_offset, *len, messages);
+                emit_range_check(out, &indent, *dst_offset, *len, messages);
+                let _ = writeln!(
+                    out,
+                    "{}memmove(p + ({}), p + ({}), {});",
+                    indent, dst_offset, src_offset, len
+                );
+            }
+            Instruction::FindZero { direction } => {
+                let _ = writeln!(out, "{}while (*p) {{", indent);
+                emit_block(out, &[Instruction::Move(*direction)], depth + 1, messages);
+                let _ = writeln!(out, "{}}}", indent);
+            }
+            Instruction::MultiplyAdd { offset, factor } => {
+                let _ = writeln!(out, "{}if (*p) {{", indent);
+                if *offset >= 0 {
+                    let _ = writeln!(
+                        out,
+                        "{}    if (TAPE_SIZE - (p - tape) <= {}) svo_fail({:?});",
+                        indent, offset, messages.overflow
+                    );
+                } else {
+                    let _ = writeln!(
+                        out,
+                        "{}    if (p - tape < {}) svo_fail({:?});",
+                        indent,
+                        offset.unsigned_abs(),
+                        messages.underflow
+                    );
+                }
+                let _ = writeln!(out, "{}    p[{}] += *p * {};", indent, offset, factor);
+                let _ = writeln!(out, "{}    *p = 0;", indent);
+                let _ = writeln!(out, "{}}}", indent);
+            }
+            Instruction::MacroCall { .. } => {
+                let _ = writeln!(
+                    out,
+                    "{}svo_fail({:?});",
+                    indent,
+                    RuntimeError::UnexpandedMacro.to_string()
+                );
+            }
+            // `--debug-print` only exists in the interpreter
+            Instruction::DebugPrint { .. } => (),
+        }
+    }
+}
+
+// fails the way a move would when `len` cells from `start_offset` away leave the tape
+fn emit_range_check(
+    out: &mut String,
+    indent: &str,
+    start_offset: isize,
+    len: usize,
+    messages: &Messages,
+) {
+    if start_offset < 0 {
+        let _ = writeln!(
+            out,
+            "{}if (p - tape < {}) svo_fail({:?});",
+            indent,
+            start_offset.unsigned_abs(),
+            messages.underflow
+        );
+    }
+    let end = start_offset + len as isize;
+    if end > 0 {
+        let _ = writeln!(
+            out,
+            "{}if (TAPE_SIZE - (p - tape) < {}) svo_fail({:?});",
+            indent, end, messages.overflow
+        );
+    }
+}
+
+// `$CC`, or `cc` when it isn't set
+pub fn compiler() -> String {
+    env::var("CC").unwrap_or_else(|_| "cc".to_string())
+}
+
+// hands generated C to the system compiler (`$CC`, `cc` by default),
+// `extra_args` selects what gets produced, e.g. `-c` for an object file
+pub fn compile_c(source: &str, output: &Path, extra_args: &[&str]) -> Result<(), String> {
+    let compiler = compiler();
+
+    let mut child = Command::new(&compiler)
+        .args(extra_args)
+        .args(["-x", "c", "-", "-o"])
+        .arg(output)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("failed to start {}: {}", compiler, err))?;
+
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(source.as_bytes())
+        .map_err(|err| format!("failed to pass source to {}: {}", compiler, err))?;
+
+    let status = child
+        .wait()
+        .map_err(|err| format!("failed to wait for {}: {}", compiler, err))?;
+
+    if !status.success() {
+        return Err(format!("{} exited with {}", compiler, status));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use std::fmt;
+
+use crate::lexer::Token;
+use crate::parser::Instruction;
+
+// a byte range of the source whose opcodes were all either reached or not
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoverageRange {
+    pub start: usize,
+    pub end: usize,
+    pub covered: bool,
+}
+
+impl fmt::Display for CoverageRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = if self.covered {
+            "covered"
+        } else {
+            "not-covered"
+        };
+        write!(f, "{} {}..{}", status, self.start, self.end)
+    }
+}
+
+// whether each opcode position ran, a loop's closing bracket shares its opening bracket's status
+pub fn covered_positions(instructions: &[Instruction], hits: &[u64]) -> Vec<bool> {
+    position_hits(instructions, hits)
+        .into_iter()
+        .map(|n| n > 0)
+        .collect()
+}
+
+// how many times each opcode position ran, a loop's closing bracket counts as often as its
+// opening bracket
+pub fn position_hits(instructions: &[Instruction], hits: &[u64]) -> Vec<u64> {
+    let len = instructions.iter().map(Instruction::opcode_len).sum();
+    let mut counts = vec![0; len];
+    mark(instructions, hits, 0, &mut counts);
+    counts
+}
+
+fn mark(instructions: &[Instruction], hits: &[u64], mut position: usize, counts: &mut [u64]) {
+    for instr in instructions {
+        let len = instr.opcode_len();
+        // debug prints cover no opcode
+        if len == 0 {
+            continue;
+        }
+        let hit = hits.get(position).copied().unwrap_or(0);
+        counts[position] = hit;
+
+        if let Instruction::Loop(body) = instr {
+            counts[position + len - 1] = hit;
+            mark(body, hits, position + 1, counts);
+        }
+
+        position += len;
+    }
+}
+
+// merges neighbouring opcodes with the same status, `tokens` are the lexed source tokens
+pub fn ranges(instructions: &[Instruction], hits: &[u64], tokens: &[Token]) -> Vec<CoverageRange> {
+    let covered = covered_positions(instructions, hits);
+    let mut ranges: Vec<CoverageRange> = Vec::new();
+
+    for (token, covered) in tokens
+        .iter()
+        .filter(|token| token.op.is_some())
+        .zip(covered)
+    {
+        match ranges.last_mut() {
+            Some(range) if range.covered == covered => range.end = token.end,
+            _ => ranges.push(CoverageRange {
+                start: token.start,
+                end: token.end,
+                covered,
+            }),
+        }
+    }
+
+    ranges
+}
+
+// the hit count of every line of `source` with an opcode on it, numbered from 1, a line
+// counts as often as its most executed opcode. `tokens` are `source`'s tokens and
+// `counts` come from `position_hits`
+pub fn line_hits(source: &str, tokens: &[Token], counts: &[u64]) -> Vec<(usize, u64)> {
+    let mut lines: Vec<(usize, u64)> = Vec::new();
+    let mut line = 1;
+    let mut scanned = 0;
+
+    for (token, &count) in tokens.iter().filter(|token| token.op.is_some()).zip(counts) {
+        line += source[scanned..token.start].matches('\n').count();
+        scanned = token.start;
+        match lines.last_mut() {
+            Some((last, hits)) if *last == line => *hits = (*hits).max(count),
+            _ => lines.push((line, count)),
+        }
+    }
+
+    lines
+}
+
+// an LCOV tracefile with one record for `source_name`, what `genhtml` and coverage
+// services read
+pub fn to_lcov(source_name: &str, lines: &[(usize, u64)]) -> String {
+    let mut out = format!("TN:\nSF:{}\n", source_name);
+    for (line, hits) in lines {
+        out.push_str(&format!("DA:{},{}\n", line, hits));
+    }
+    let hit = lines.iter().filter(|(_, hits)| *hits > 0).count();
+    out.push_str(&format!("LF:{}\nLH:{}\nend_of_record\n", lines.len(), hit));
+    out
+}
@@ -0,0 +1,142 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::parser::Instruction;
+
+// def-use chains between instructions, identified by opcode position like everywhere else
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DataFlowGraph {
+    // every instruction that writes a cell, mapped to the instructions that use what it wrote
+    pub uses: BTreeMap<usize, Vec<usize>>,
+    // writes still pending when the program ends, they're part of the final tape
+    pub live_out: BTreeSet<usize>,
+}
+
+impl DataFlowGraph {
+    // writes a later write replaces before anything looks at the cell
+    pub fn dead_stores(&self) -> Vec<usize> {
+        self.uses
+            .iter()
+            .filter(|(position, uses)| uses.is_empty() && !self.live_out.contains(position))
+            .map(|(position, _)| *position)
+            .collect()
+    }
+
+    fn define(&mut self, position: usize) {
+        self.uses.entry(position).or_default();
+    }
+
+    fn use_value(&mut self, def: usize, position: usize) {
+        let uses = self.uses.entry(def).or_default();
+        if uses.last() != Some(&position) {
+            uses.push(position);
+        }
+    }
+}
+
+// the pointer is only tracked through straight-line code, a loop or zero search may touch
+// any cell and leave the pointer anywhere, so it counts as using every pending write and
+// the offsets start over after it
+pub struct DataFlowAnalyzer;
+
+impl DataFlowAnalyzer {
+    pub fn analyze(instructions: &[Instruction]) -> DataFlowGraph {
+        let mut graph = DataFlowGraph::default();
+        graph.live_out = analyze_block(instructions, 0, &mut graph)
+            .into_values()
+            .collect();
+        graph
+    }
+}
+
+// returns the writes still waiting for a use when the block ends
+fn analyze_block(
+    instructions: &[Instruction],
+    mut position: usize,
+    graph: &mut DataFlowGraph,
+) -> HashMap<isize, usize> {
+    // cell offset from where the pointer was at the start or after the last loop, and the
+    // last write to each cell since then
+    let mut offset = 0;
+    let mut pending: HashMap<isize, usize> = HashMap::new();
+
+    for instr in instructions {
+        match instr {
+            Instruction::IncrementPointer => offset += 1,
+            Instruction::DecrementPointer => offset -= 1,
+            Instruction::Move(by) => offset += by,
+            // read-modify-write, uses the old value and replaces it. a read at the end of
+            // input leaves the cell alone, so it may pass the old value on too
+            Instruction::Increment
+            | Instruction::Decrement
+            | Instruction::Add(_)
+            | Instruction::Read => {
+                if let Some(def) = pending.insert(offset, position) {
+                    graph.use_value(def, position);
+                }
+                graph.define(position);
+            }
+            Instruction::SetCell(_) => {
+                pending.insert(offset, position);
+                graph.define(position);
+            }
+            Instruction::BulkZero { start_offset, len } => {
+                for cell in 0..*len as isize {
+                    pending.insert(offset + start_offset + cell, position);
+                }
+                graph.define(position);
+            }
+            Instruction::Write => {
+                if let Some(&def) = pending.get(&offset) {
+                    graph.use_value(def, position);
+                }
+            }
+            // uses the current cell to add to the target, then clears the current one
+            Instruction::MultiplyAdd { offset: to, .. } => {
+                for cell in [offset, offset + to] {
+                    if let Some(def) = pending.insert(cell, position) {
+                        graph.use_value(def, position);
+                    }
+                }
+                graph.define(position);
+            }
+            // reads every source cell, then replaces every destination cell
+            Instruction::CopyRange {
+                src_offset,
+                dst_offset,
+                len,
+            } => {
+                for cell in 0..*len as isize {
+                    if let Some(&def) = pending.get(&(offset + src_offset + cell)) {
+                        graph.use_value(def, position);
+                    }
+                }
+                for cell in 0..*len as isize {
+                    pending.insert(offset + dst_offset + cell, position);
+                }
+                graph.define(position);
+            }
+            // a macro call is as opaque as a loop until it's expanded
+            Instruction::Loop(_) | Instruction::FindZero { .. } | Instruction::MacroCall { .. } => {
+                for (_, def) in pending.drain() {
+                    graph.use_value(def, position);
+                }
+                if let Instruction::Loop(body) = instr {
+                    // whatever the body leaves pending may feed the next test or iteration
+                    for (_, def) in analyze_block(body, position + 1, graph) {
+                        graph.use_value(def, position);
+                    }
+                }
+                offset = 0;
+            }
+            // the report shows the tape, so it looks at every pending write
+            Instruction::DebugPrint { .. } => {
+                for &def in pending.values() {
+                    graph.use_value(def, position);
+                }
+            }
+        }
+        position += instr.opcode_len();
+    }
+
+    pending
+}
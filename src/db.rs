@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::interpreter::{run_with_input, InterpreterConfig, RuntimeError};
+use crate::parser::ParseError;
+use crate::program::Program;
+
+#[derive(Debug)]
+pub enum DbError {
+    Io { path: PathBuf, error: io::Error },
+    Parse { path: PathBuf, error: ParseError },
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DbError::Io { path, error } => {
+                write!(f, "failed to read {}: {}", path.display(), error)
+            }
+            DbError::Parse { path, error } => write!(f, "{}: {}", path.display(), error),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+// named programs held in memory, names are file stems and iterate in sorted order
+#[derive(Debug, Clone, Default)]
+pub struct ProgramDatabase {
+    programs: BTreeMap<String, Program>,
+}
+
+impl ProgramDatabase {
+    pub fn new() -> Self {
+        ProgramDatabase::default()
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, program: Program) {
+        self.programs.insert(name.into(), program);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Program> {
+        self.programs.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.programs.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.programs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.programs.is_empty()
+    }
+
+    // parses every `.svo` file directly inside `path`, nothing is added unless all of them parse,
+    // a program already in the database is replaced by a file with the same stem
+    pub fn load_directory(&mut self, path: &Path) -> Result<(), DbError> {
+        let io_error = |path: &Path| {
+            let path = path.to_path_buf();
+            move |error| DbError::Io { path, error }
+        };
+
+        let mut loaded = Vec::new();
+        for entry in fs::read_dir(path).map_err(io_error(path))? {
+            let file = entry.map_err(io_error(path))?.path();
+            if file.extension().is_none_or(|extension| extension != "svo") || !file.is_file() {
+                continue;
+            }
+            let Some(name) = file.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            let source = fs::read_to_string(&file).map_err(io_error(&file))?;
+            let program = Program::from_source(&source).map_err(|error| DbError::Parse {
+                path: file.clone(),
+                error,
+            })?;
+            loaded.push((name.to_string(), program));
+        }
+
+        self.programs.extend(loaded);
+        Ok(())
+    }
+
+    // runs every program on a fresh interpreter with empty input, in name order
+    pub fn run_all(
+        &self,
+        config: &InterpreterConfig,
+    ) -> Vec<(String, Result<Vec<u8>, RuntimeError>)> {
+        self.programs
+            .iter()
+            .map(|(name, program)| {
+                (
+                    name.clone(),
+                    run_with_input(&program.instructions, config, &[]),
+                )
+            })
+            .collect()
+    }
+}
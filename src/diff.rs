@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+
+use crate::parser::Instruction;
+use crate::program::Program;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffEntry {
+    Added(Instruction),
+    Removed(Instruction),
+    Changed { from: Instruction, to: Instruction },
+}
+
+// a diff entry with the opcode position of its instruction on each side that has it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocatedDiffEntry {
+    pub entry: DiffEntry,
+    pub from: Option<usize>,
+    pub to: Option<usize>,
+}
+
+// the opcode position of every instruction in `block`, which starts at `start`
+fn positions(block: &[Instruction], start: usize) -> Vec<usize> {
+    block
+        .iter()
+        .scan(start, |position, instr| {
+            let at = *position;
+            *position += instr.opcode_len();
+            Some(at)
+        })
+        .collect()
+}
+
+// number of nodes in an instruction's subtree, what it costs to add or remove it
+fn size(instr: &Instruction) -> usize {
+    match instr {
+        Instruction::Loop(body) => 1 + body.iter().map(size).sum::<usize>(),
+        _ => 1,
+    }
+}
+
+// top-down tree edit distance: siblings are aligned like a sequence diff and two loops
+// may only be matched with each other, at the cost of turning one body into the other
+struct Differ {
+    loop_costs: HashMap<(*const Instruction, *const Instruction), usize>,
+}
+
+impl Differ {
+    fn substitution(&mut self, a: &Instruction, b: &Instruction) -> Option<usize> {
+        match (a, b) {
+            (Instruction::Loop(x), Instruction::Loop(y)) => {
+                let key: (*const Instruction, *const Instruction) = (a, b);
+                if let Some(&cost) = self.loop_costs.get(&key) {
+                    return Some(cost);
+                }
+                let cost = self.table(x, y)[x.len()][y.len()];
+                self.loop_costs.insert(key, cost);
+                Some(cost)
+            }
+            (Instruction::Loop(_), _) | (_, Instruction::Loop(_)) => None,
+            _ => Some(usize::from(a != b)),
+        }
+    }
+
+    // cost[i][j] turns the first i of `a` into the first j of `b`
+    fn table(&mut self, a: &[Instruction], b: &[Instruction]) -> Vec<Vec<usize>> {
+        let mut cost = vec![vec![0; b.len() + 1]; a.len() + 1];
+        for i in 1..=a.len() {
+            cost[i][0] = cost[i - 1][0] + size(&a[i - 1]);
+        }
+        for j in 1..=b.len() {
+            cost[0][j] = cost[0][j - 1] + size(&b[j - 1]);
+        }
+
+        for i in 1..=a.len() {
+            for j in 1..=b.len() {
+                let remove = cost[i - 1][j] + size(&a[i - 1]);
+                let add = cost[i][j - 1] + size(&b[j - 1]);
+                let keep = self
+                    .substitution(&a[i - 1], &b[j - 1])
+                    .map_or(usize::MAX, |c| cost[i - 1][j - 1] + c);
+                cost[i][j] = remove.min(add).min(keep);
+            }
+        }
+
+        cost
+    }
+
+    // `a_start` and `b_start` are where the two blocks start
+    fn entries(
+        &mut self,
+        (a, a_start): (&[Instruction], usize),
+        (b, b_start): (&[Instruction], usize),
+        out: &mut Vec<LocatedDiffEntry>,
+    ) {
+        // identical ends are common between a program and a tweaked copy, skip them cheaply
+        let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+        let a_start = a_start
+            + a[..prefix]
+                .iter()
+                .map(Instruction::opcode_len)
+                .sum::<usize>();
+        let b_start = b_start
+            + b[..prefix]
+                .iter()
+                .map(Instruction::opcode_len)
+                .sum::<usize>();
+        let (a, b) = (&a[prefix..], &b[prefix..]);
+        let suffix = a
+            .iter()
+            .rev()
+            .zip(b.iter().rev())
+            .take_while(|(x, y)| x == y)
+            .count();
+        let (a, b) = (&a[..a.len() - suffix], &b[..b.len() - suffix]);
+        let (a_positions, b_positions) = (positions(a, a_start), positions(b, b_start));
+
+        let cost = self.table(a, b);
+        let mut script = Vec::new();
+        let (mut i, mut j) = (a.len(), b.len());
+
+        while i > 0 || j > 0 {
+            if i > 0 && j > 0 {
+                if let Some(c) = self.substitution(&a[i - 1], &b[j - 1]) {
+                    if cost[i][j] == cost[i - 1][j - 1] + c {
+                        script.push((Some(i - 1), Some(j - 1)));
+                        i -= 1;
+                        j -= 1;
+                        continue;
+                    }
+                }
+            }
+            if i > 0 && cost[i][j] == cost[i - 1][j] + size(&a[i - 1]) {
+                script.push((Some(i - 1), None));
+                i -= 1;
+            } else {
+                script.push((None, Some(j - 1)));
+                j -= 1;
+            }
+        }
+
+        for (i, j) in script.into_iter().rev() {
+            let from = i.map(|i| a_positions[i]);
+            let to = j.map(|j| b_positions[j]);
+            let entry = match (i.map(|i| &a[i]), j.map(|j| &b[j])) {
+                (Some(Instruction::Loop(x)), Some(Instruction::Loop(y))) => {
+                    let (from, to) = (from.expect("a side"), to.expect("b side"));
+                    self.entries((x, from + 1), (y, to + 1), out);
+                    continue;
+                }
+                (Some(from), Some(to)) if from != to => DiffEntry::Changed {
+                    from: from.clone(),
+                    to: to.clone(),
+                },
+                (Some(_), Some(_)) => continue,
+                (Some(from), None) => DiffEntry::Removed(from.clone()),
+                (None, Some(to)) => DiffEntry::Added(to.clone()),
+                (None, None) => unreachable!("every step touches one side"),
+            };
+            out.push(LocatedDiffEntry { entry, from, to });
+        }
+    }
+}
+
+impl Program {
+    // the cheapest set of additions, removals and changes that turns `a` into `b`,
+    // changes inside a loop that exists in both are listed without the loop itself
+    pub fn diff(a: &Program, b: &Program) -> Vec<DiffEntry> {
+        Program::located_diff(a, b)
+            .into_iter()
+            .map(|located| located.entry)
+            .collect()
+    }
+
+    // `diff` with where each entry's instructions are, an added instruction has no `from`
+    // and a removed one no `to`
+    pub fn located_diff(a: &Program, b: &Program) -> Vec<LocatedDiffEntry> {
+        let mut differ = Differ {
+            loop_costs: HashMap::new(),
+        };
+        let mut out = Vec::new();
+        differ.entries((&a.instructions, 0), (&b.instructions, 0), &mut out);
+        out
+    }
+}
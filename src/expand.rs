@@ -0,0 +1,298 @@
+use std::fmt;
+
+use crate::lexer::OpCode;
+use crate::parser::Instruction;
+use crate::translate::svo_word;
+
+// the built-in macros a `MacroCall` can name, each expands to plain instructions and
+// leaves the pointer where it found it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Macro {
+    // `print_str(bytes...)` writes the bytes through the current cell, which ends up zero
+    PrintStr(Vec<u8>),
+    // `clear_range(n)` clears `n` cells from the pointer on
+    ClearRange(u8),
+    // `memcpy(src_offset, dst_offset, len)` copies `len` cells starting `src_offset` cells
+    // from the pointer to the ones starting `dst_offset` cells from it, the current cell is
+    // scratch space so it has to be zero and can't be in either range. overlapping ranges
+    // copy like `memmove`
+    Memcpy {
+        src_offset: u8,
+        dst_offset: u8,
+        len: u8,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MacroError {
+    UnknownMacro(String),
+    WrongArgCount {
+        name: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    // memcpy with a range on the scratch cell
+    ScratchCellInRange,
+}
+
+impl fmt::Display for MacroError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MacroError::UnknownMacro(name) => write!(f, "unknown macro `{}`", name),
+            MacroError::WrongArgCount {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "`{}` takes {} arguments, found {}",
+                name, expected, found
+            ),
+            MacroError::ScratchCellInRange => write!(
+                f,
+                "`memcpy` uses the current cell as scratch space, offsets have to be at least 1"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MacroError {}
+
+impl Macro {
+    pub fn from_call(name: &str, args: &[u8]) -> Result<Macro, MacroError> {
+        let expect = |name, expected| {
+            if args.len() == expected {
+                Ok(())
+            } else {
+                Err(MacroError::WrongArgCount {
+                    name,
+                    expected,
+                    found: args.len(),
+                })
+            }
+        };
+
+        match name {
+            "print_str" => Ok(Macro::PrintStr(args.to_vec())),
+            "clear_range" => {
+                expect("clear_range", 1)?;
+                Ok(Macro::ClearRange(args[0]))
+            }
+            "memcpy" => {
+                expect("memcpy", 3)?;
+                if args[2] > 0 && (args[0] == 0 || args[1] == 0) {
+                    return Err(MacroError::ScratchCellInRange);
+                }
+                Ok(Macro::Memcpy {
+                    src_offset: args[0],
+                    dst_offset: args[1],
+                    len: args[2],
+                })
+            }
+            _ => Err(MacroError::UnknownMacro(name.to_string())),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Macro::PrintStr(_) => "print_str",
+            Macro::ClearRange(_) => "clear_range",
+            Macro::Memcpy { .. } => "memcpy",
+        }
+    }
+
+    pub fn args(&self) -> Vec<u8> {
+        match self {
+            Macro::PrintStr(bytes) => bytes.clone(),
+            Macro::ClearRange(len) => vec![*len],
+            Macro::Memcpy {
+                src_offset,
+                dst_offset,
+                len,
+            } => vec![*src_offset, *dst_offset, *len],
+        }
+    }
+
+    pub fn expand(&self) -> Vec<Instruction> {
+        match self {
+            Macro::PrintStr(bytes) => {
+                let mut instructions = Vec::with_capacity(bytes.len() * 2 + 1);
+                for byte in bytes {
+                    instructions.push(Instruction::SetCell(*byte));
+                    instructions.push(Instruction::Write);
+                }
+                instructions.push(Instruction::SetCell(0));
+                instructions
+            }
+            Macro::ClearRange(len) => vec![Instruction::BulkZero {
+                start_offset: 0,
+                len: *len as usize,
+            }],
+            Macro::Memcpy {
+                src_offset,
+                dst_offset,
+                len,
+            } => {
+                if src_offset == dst_offset {
+                    return Vec::new();
+                }
+                vec![Instruction::CopyRange {
+                    src_offset: *src_offset as isize,
+                    dst_offset: *dst_offset as isize,
+                    len: *len as usize,
+                }]
+            }
+        }
+    }
+}
+
+impl From<Macro> for Instruction {
+    fn from(m: Macro) -> Instruction {
+        Instruction::MacroCall {
+            name: m.name().to_string(),
+            args: m.args(),
+        }
+    }
+}
+
+// a range copy as plain loops, the current cell is the scratch space so it has to be zero
+// and outside both ranges
+fn copy_cells(src: isize, dst: isize, len: usize) -> Vec<Instruction> {
+    // copying away from the source first never reads a cell already written
+    let cells: Vec<isize> = if dst > src {
+        (0..len as isize).rev().collect()
+    } else {
+        (0..len as isize).collect()
+    };
+    cells
+        .into_iter()
+        .flat_map(|i| copy_cell(src + i, dst + i))
+        .collect()
+}
+
+// `[->+>+<<]` style: the source is moved into the target and the scratch cell under the
+// pointer, then the scratch cell is moved back into the source
+fn copy_cell(src: isize, dst: isize) -> Vec<Instruction> {
+    vec![
+        Instruction::Move(dst),
+        Instruction::SetCell(0),
+        Instruction::Move(src - dst),
+        Instruction::Loop(vec![
+            Instruction::Add(u8::MAX),
+            Instruction::Move(-src),
+            Instruction::Add(1),
+            Instruction::Move(dst),
+            Instruction::Add(1),
+            Instruction::Move(src - dst),
+        ]),
+        Instruction::Move(-src),
+        Instruction::Loop(vec![
+            Instruction::Add(u8::MAX),
+            Instruction::Move(src),
+            Instruction::Add(1),
+            Instruction::Move(-src),
+        ]),
+    ]
+}
+
+// a program as plain `sv` words that parse back into the same behaviour, macro calls are
+// expanded and the folded instructions the optimizer makes are spelled out opcode by opcode.
+// debug prints become `#debug_print` comments on their own line
+pub fn to_source(instructions: &[Instruction]) -> Result<String, MacroError> {
+    let mut out = String::new();
+    write_source(instructions, &mut out)?;
+    Ok(out)
+}
+
+fn write_source(instructions: &[Instruction], out: &mut String) -> Result<(), MacroError> {
+    for instr in instructions {
+        match instr {
+            Instruction::Increment => word(out, OpCode::Increment, 1),
+            Instruction::Decrement => word(out, OpCode::Decrement, 1),
+            Instruction::IncrementPointer => word(out, OpCode::IncrementPointer, 1),
+            Instruction::DecrementPointer => word(out, OpCode::DecrementPointer, 1),
+            Instruction::Write => word(out, OpCode::Write, 1),
+            Instruction::Read => word(out, OpCode::Read, 1),
+            Instruction::Loop(body) => {
+                word(out, OpCode::LoopBegin, 1);
+                write_source(body, out)?;
+                word(out, OpCode::LoopEnd, 1);
+            }
+            Instruction::Add(n) => add(out, *n),
+            Instruction::Move(by) => move_by(out, *by),
+            Instruction::SetCell(value) => {
+                clear(out);
+                add(out, *value);
+            }
+            Instruction::BulkZero { start_offset, len } => {
+                move_by(out, *start_offset);
+                for cell in 0..*len {
+                    if cell > 0 {
+                        move_by(out, 1);
+                    }
+                    clear(out);
+                }
+                move_by(out, -(*start_offset + len.saturating_sub(1) as isize));
+            }
+            Instruction::MultiplyAdd { offset, factor } => {
+                word(out, OpCode::LoopBegin, 1);
+                word(out, OpCode::Decrement, 1);
+                move_by(out, *offset);
+                add(out, *factor);
+                move_by(out, -*offset);
+                word(out, OpCode::LoopEnd, 1);
+            }
+            Instruction::FindZero { direction } => {
+                word(out, OpCode::LoopBegin, 1);
+                move_by(out, *direction);
+                word(out, OpCode::LoopEnd, 1);
+            }
+            // only `memcpy` makes one, so the current cell is free as scratch space
+            Instruction::CopyRange {
+                src_offset,
+                dst_offset,
+                len,
+            } => write_source(&copy_cells(*src_offset, *dst_offset, *len), out)?,
+            Instruction::MacroCall { name, args } => {
+                write_source(&Macro::from_call(name, args)?.expand(), out)?
+            }
+            Instruction::DebugPrint { label } => {
+                out.push_str(&format!("\n#debug_print \"{}\"\n", label));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn word(out: &mut String, op: OpCode, times: usize) {
+    for _ in 0..times {
+        if !out.is_empty() && !out.ends_with('\n') {
+            out.push(' ');
+        }
+        out.push_str(svo_word(op));
+    }
+}
+
+// the shorter way round the 256 values of a cell
+fn add(out: &mut String, n: u8) {
+    if n <= 128 {
+        word(out, OpCode::Increment, n as usize);
+    } else {
+        word(out, OpCode::Decrement, 256 - n as usize);
+    }
+}
+
+fn move_by(out: &mut String, by: isize) {
+    let op = if by > 0 {
+        OpCode::IncrementPointer
+    } else {
+        OpCode::DecrementPointer
+    };
+    word(out, op, by.unsigned_abs());
+}
+
+fn clear(out: &mut String) {
+    word(out, OpCode::LoopBegin, 1);
+    word(out, OpCode::Decrement, 1);
+    word(out, OpCode::LoopEnd, 1);
+}
@@ -0,0 +1,265 @@
+use crate::lexer::{lex, tokenize, OpCode};
+use crate::parser::{parse, ParseError};
+
+// lines of straight-line code wrap before this many columns
+const WIDTH: usize = 80;
+const INDENT: &str = "    ";
+// unchanged lines kept around each change in a diff
+const CONTEXT: usize = 3;
+
+// the canonical layout: straight-line code as space separated words wrapped at `WIDTH`,
+// every bracket on its own line with loop bodies indented. comments stay on their own
+// line or after the code they followed, one blank line is kept wherever the source had
+// any, and text that isn't a keyword is kept as a word so nothing is lost
+pub fn format(source: &str) -> Result<String, ParseError> {
+    parse(&lex(source))?;
+
+    let mut formatter = Formatter::default();
+    let mut kept = 0;
+    for token in tokenize(source) {
+        formatter.gap(&source[kept..token.start]);
+        let word = &source[token.start..token.end];
+        match token.op {
+            Some(OpCode::LoopBegin) => {
+                formatter.flush();
+                formatter.word(word);
+                formatter.flush();
+                formatter.depth += 1;
+            }
+            Some(OpCode::LoopEnd) => {
+                formatter.flush();
+                formatter.depth -= 1;
+                formatter.word(word);
+                formatter.flush();
+            }
+            _ => formatter.word(word),
+        }
+        formatter.newlines = 0;
+        kept = token.end;
+    }
+    formatter.gap(&source[kept..]);
+
+    Ok(formatter.finish())
+}
+
+#[derive(Default)]
+struct Formatter {
+    lines: Vec<String>,
+    line: String,
+    depth: usize,
+    // newlines since the last word or comment
+    newlines: usize,
+}
+
+impl Formatter {
+    // whitespace, comments and stray text between two keywords
+    fn gap(&mut self, gap: &str) {
+        let mut rest = gap;
+        while let Some(c) = rest.chars().next() {
+            if c == '\n' {
+                self.newlines += 1;
+                rest = &rest[1..];
+            } else if c.is_whitespace() {
+                rest = &rest[c.len_utf8()..];
+            } else if c == '#' {
+                let end = rest.find('\n').unwrap_or(rest.len());
+                self.comment(rest[..end].trim_end());
+                self.newlines = 0;
+                rest = &rest[end..];
+            } else {
+                let end = rest
+                    .find(|c: char| c.is_whitespace() || c == '#')
+                    .unwrap_or(rest.len());
+                self.word(&rest[..end]);
+                self.newlines = 0;
+                rest = &rest[end..];
+            }
+        }
+    }
+
+    fn word(&mut self, word: &str) {
+        self.blank_line();
+        if !self.line.is_empty() && self.line.len() + 1 + word.len() > WIDTH {
+            self.flush();
+        }
+        if self.line.is_empty() {
+            self.line = INDENT.repeat(self.depth);
+        } else {
+            self.line.push(' ');
+        }
+        self.line.push_str(word);
+    }
+
+    fn comment(&mut self, comment: &str) {
+        let own_line = self.newlines > 0 || (self.lines.is_empty() && self.line.is_empty());
+        self.blank_line();
+        if own_line {
+            self.flush();
+            self.lines.push(INDENT.repeat(self.depth) + comment);
+            return;
+        }
+        // a bracket already ended its line, the comment goes after it
+        match self.lines.last_mut() {
+            Some(last) if self.line.is_empty() => {
+                last.push(' ');
+                last.push_str(comment);
+            }
+            _ => {
+                self.line.push(' ');
+                self.line.push_str(comment);
+                self.flush();
+            }
+        }
+    }
+
+    // one blank line for any number of them, none at the start
+    fn blank_line(&mut self) {
+        if self.newlines < 2 {
+            return;
+        }
+        self.flush();
+        if self.lines.last().is_some_and(|last| !last.is_empty()) {
+            self.lines.push(String::new());
+        }
+    }
+
+    fn flush(&mut self) {
+        if !self.line.is_empty() {
+            self.lines.push(std::mem::take(&mut self.line));
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.flush();
+        while self.lines.last().is_some_and(String::is_empty) {
+            self.lines.pop();
+        }
+        self.lines
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Keep,
+    Remove,
+    Add,
+}
+
+// `old` turned into `new` as a unified diff, empty when they're the same
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let old: Vec<&str> = old.split_inclusive('\n').collect();
+    let new: Vec<&str> = new.split_inclusive('\n').collect();
+    let edits = edits(&old, &new);
+    if edits.iter().all(|edit| *edit == Edit::Keep) {
+        return String::new();
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", old_name, new_name);
+    let changes: Vec<usize> = (0..edits.len())
+        .filter(|&i| edits[i] != Edit::Keep)
+        .collect();
+
+    // where each edit starts in the old and new lines
+    let mut starts = Vec::with_capacity(edits.len() + 1);
+    let (mut i, mut j) = (0, 0);
+    for edit in &edits {
+        starts.push((i, j));
+        match edit {
+            Edit::Keep => (i, j) = (i + 1, j + 1),
+            Edit::Remove => i += 1,
+            Edit::Add => j += 1,
+        }
+    }
+    starts.push((i, j));
+
+    let mut next = 0;
+    while next < changes.len() {
+        // changes closer than twice the context share a hunk
+        let first = changes[next];
+        let mut last = first;
+        next += 1;
+        while next < changes.len() && changes[next] - last <= 2 * CONTEXT {
+            last = changes[next];
+            next += 1;
+        }
+        let begin = first.saturating_sub(CONTEXT);
+        let end = (last + 1 + CONTEXT).min(edits.len());
+
+        let (old_start, new_start) = starts[begin];
+        let (old_end, new_end) = starts[end];
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, old_end - old_start),
+            hunk_range(new_start, new_end - new_start)
+        ));
+        for (edit, &(i, j)) in edits[begin..end].iter().zip(&starts[begin..end]) {
+            let (marker, line) = match edit {
+                Edit::Keep => (' ', old[i]),
+                Edit::Remove => ('-', old[i]),
+                Edit::Add => ('+', new[j]),
+            };
+            out.push(marker);
+            out.push_str(line);
+            if !line.ends_with('\n') {
+                out.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+
+    out
+}
+
+// `start,len` with lines counted from 1, an empty range names the line before it
+fn hunk_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+// a shortest edit script from the longest common subsequence, after the common prefix
+// and suffix are taken off so a small change to a long file stays cheap
+fn edits(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    // common[i][j] is the longest common subsequence of a[i..] and b[j..]
+    let mut common = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            common[i][j] = if a[i] == b[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut edits = vec![Edit::Keep; prefix];
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            edits.push(Edit::Keep);
+            (i, j) = (i + 1, j + 1);
+        } else if j == b.len() || (i < a.len() && common[i + 1][j] >= common[i][j + 1]) {
+            edits.push(Edit::Remove);
+            i += 1;
+        } else {
+            edits.push(Edit::Add);
+            j += 1;
+        }
+    }
+    edits.extend(vec![Edit::Keep; suffix]);
+    edits
+}
@@ -0,0 +1,109 @@
+use crate::expand::{Macro, MacroError};
+use crate::lexer::OpCode;
+use crate::parser::{parse, Instruction, ParseError};
+
+// builds a program one opcode at a time, loops are checked by `build` the same way
+// the parser checks source
+#[derive(Debug, Clone, Default)]
+pub struct Generator {
+    opcodes: Vec<OpCode>,
+}
+
+impl Generator {
+    pub fn new() -> Self {
+        Generator::default()
+    }
+
+    fn repeat(&mut self, op: OpCode, n: usize) -> &mut Self {
+        self.opcodes.extend(std::iter::repeat_n(op, n));
+        self
+    }
+
+    pub fn increment(&mut self, n: u8) -> &mut Self {
+        self.repeat(OpCode::Increment, n as usize)
+    }
+
+    pub fn decrement(&mut self, n: u8) -> &mut Self {
+        self.repeat(OpCode::Decrement, n as usize)
+    }
+
+    pub fn move_right(&mut self, n: usize) -> &mut Self {
+        self.repeat(OpCode::IncrementPointer, n)
+    }
+
+    pub fn move_left(&mut self, n: usize) -> &mut Self {
+        self.repeat(OpCode::DecrementPointer, n)
+    }
+
+    pub fn write(&mut self) -> &mut Self {
+        self.repeat(OpCode::Write, 1)
+    }
+
+    pub fn read(&mut self) -> &mut Self {
+        self.repeat(OpCode::Read, 1)
+    }
+
+    pub fn loop_begin(&mut self) -> &mut Self {
+        self.repeat(OpCode::LoopBegin, 1)
+    }
+
+    pub fn loop_end(&mut self) -> &mut Self {
+        self.repeat(OpCode::LoopEnd, 1)
+    }
+
+    pub fn opcodes(&self) -> &[OpCode] {
+        &self.opcodes
+    }
+
+    pub fn build(&self) -> Result<Vec<Instruction>, ParseError> {
+        parse(&self.opcodes)
+    }
+
+    // a call to one of the built-in macros, checked here so `MacroExpander` can always
+    // expand it
+    pub fn macro_call(name: &str, args: &[u8]) -> Result<Instruction, MacroError> {
+        Macro::from_call(name, args).map(Instruction::from)
+    }
+
+    // stores the bytes of `s` in consecutive zeroed cells from the pointer on, which ends
+    // up one past the last byte
+    pub fn load_string(s: &str) -> Vec<Instruction> {
+        let mut instructions = Vec::new();
+        for byte in s.bytes() {
+            instructions.extend(ProgramBuilder::set_cell(byte));
+            instructions.push(Instruction::IncrementPointer);
+        }
+        instructions
+    }
+}
+
+// ready-made instruction sequences, each assumes the current cell starts at zero
+pub struct ProgramBuilder;
+
+impl ProgramBuilder {
+    // the shorter way round: counts up to `value`, or down through the wrap past zero
+    pub fn set_cell(value: u8) -> Vec<Instruction> {
+        if value <= 128 {
+            vec![Instruction::Increment; value as usize]
+        } else {
+            vec![Instruction::Decrement; 256 - value as usize]
+        }
+    }
+
+    // writes `n` in decimal, stepping the cell from one digit to the next, then clears it
+    pub fn print_number(n: u8) -> Vec<Instruction> {
+        let digits = n.to_string();
+        let mut instructions = Vec::new();
+        let mut current = 0u8;
+
+        for digit in digits.bytes() {
+            let delta = digit.wrapping_sub(current);
+            instructions.extend(ProgramBuilder::set_cell(delta));
+            instructions.push(Instruction::Write);
+            current = digit;
+        }
+
+        instructions.push(Instruction::Loop(vec![Instruction::Decrement]));
+        instructions
+    }
+}
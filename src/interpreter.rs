@@ -0,0 +1,1903 @@
+use std::cell::{Ref, RefCell};
+use std::fmt;
+use std::io::{self, BufWriter, Read, Write};
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use tracing::{info, trace};
+
+use crate::loops::LoopMeta;
+use crate::parser::Instruction;
+use crate::profiler::Profiler;
+use crate::rng::Rng;
+use crate::shared::{SharedTape, SharedTapeLock};
+use crate::simd;
+use crate::toml;
+use crate::trace::{TraceEvent, TraceSink};
+
+// how `Write` and `Read` behave, the sandboxed modes never touch stdin or stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IoMode {
+    #[default]
+    Standard,
+    // I/O instructions do nothing
+    Sandboxed,
+    // I/O instructions fail with `RuntimeError::IoForbidden`
+    Forbidden,
+}
+
+impl IoMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            IoMode::Standard => "standard",
+            IoMode::Sandboxed => "sandboxed",
+            IoMode::Forbidden => "forbidden",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<IoMode> {
+        [IoMode::Standard, IoMode::Sandboxed, IoMode::Forbidden]
+            .into_iter()
+            .find(|mode| mode.name() == name)
+    }
+}
+
+// how `Write` turns the current cell into output bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WriteMode {
+    // the cell as a raw byte
+    #[default]
+    Byte,
+    // the cell as a code point, UTF-8 encoded, U+FFFD when it isn't a valid one
+    UnicodeCodepoint,
+    // consecutive writes are collected into UTF-8 sequences, malformed ones become U+FFFD
+    Utf8Bytes,
+}
+
+impl WriteMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            WriteMode::Byte => "byte",
+            WriteMode::UnicodeCodepoint => "codepoint",
+            WriteMode::Utf8Bytes => "utf8",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<WriteMode> {
+        [
+            WriteMode::Byte,
+            WriteMode::UnicodeCodepoint,
+            WriteMode::Utf8Bytes,
+        ]
+        .into_iter()
+        .find(|mode| mode.name() == name)
+    }
+}
+
+// what the cells hold before the program starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TapeInit {
+    #[default]
+    Zeros,
+    // every cell 0xff
+    Ones,
+    // 0, 1, 2, ... 255, 0, 1, ...
+    Sequential,
+    // splitmix64 bytes from the seed
+    Random(u64),
+}
+
+impl TapeInit {
+    pub fn from_name(name: &str) -> Option<TapeInit> {
+        match name {
+            "zeros" => Some(TapeInit::Zeros),
+            "ones" => Some(TapeInit::Ones),
+            "sequential" => Some(TapeInit::Sequential),
+            _ => name
+                .strip_prefix("random:")
+                .and_then(|seed| seed.parse().ok())
+                .map(TapeInit::Random),
+        }
+    }
+
+    pub fn fill(self, cells: &mut [u8]) {
+        match self {
+            TapeInit::Zeros => cells.fill(0),
+            TapeInit::Ones => cells.fill(u8::MAX),
+            TapeInit::Sequential => {
+                for (i, cell) in cells.iter_mut().enumerate() {
+                    *cell = i as u8;
+                }
+            }
+            TapeInit::Random(seed) => Rng::new(seed).fill(cells),
+        }
+    }
+}
+
+impl fmt::Display for TapeInit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TapeInit::Zeros => write!(f, "zeros"),
+            TapeInit::Ones => write!(f, "ones"),
+            TapeInit::Sequential => write!(f, "sequential"),
+            TapeInit::Random(seed) => write!(f, "random:{}", seed),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InterpreterConfig {
+    pub tape_size: usize,
+    pub start_pointer: usize,
+    pub max_steps: Option<u64>,
+    // applies to each loop separately, counting its iterations over the whole run
+    pub max_loop_iterations: Option<u64>,
+    // applies to each pass through a loop body, loops nested inside count towards it
+    pub instruction_limit_per_loop: Option<u64>,
+    pub max_output_bytes: Option<u64>,
+    pub io_mode: IoMode,
+    pub tape_init: TapeInit,
+    // bytes of program output held back before they reach the writer, 0 writes through
+    pub output_buffer_size: usize,
+    pub write_mode: WriteMode,
+    // wall-clock limit per `run`, checked every `cancel_check_interval` steps
+    pub timeout: Option<Duration>,
+    pub cancel_check_interval: u64,
+    // experimental, spreads loops with independent iterations over threads
+    pub parallel_loops: bool,
+    // `#debug_print` comments report to stderr, otherwise they do nothing
+    pub debug_print: bool,
+}
+
+impl Default for InterpreterConfig {
+    fn default() -> Self {
+        InterpreterConfig {
+            tape_size: 1024,
+            start_pointer: 512,
+            max_steps: None,
+            max_loop_iterations: None,
+            instruction_limit_per_loop: None,
+            max_output_bytes: None,
+            io_mode: IoMode::Standard,
+            tape_init: TapeInit::Zeros,
+            output_buffer_size: 8192,
+            write_mode: WriteMode::Byte,
+            timeout: None,
+            cancel_check_interval: 1000,
+            parallel_loops: false,
+            debug_print: false,
+        }
+    }
+}
+
+impl InterpreterConfig {
+    // reads the keys present in `source` on top of the defaults
+    pub fn from_toml(source: &str) -> Result<InterpreterConfig, String> {
+        let table = toml::parse(source).map_err(|err| err.to_string())?;
+        let mut config = InterpreterConfig::default();
+
+        for (key, value) in &table {
+            let integer = || {
+                value
+                    .as_integer()
+                    .filter(|n| *n >= 0)
+                    .ok_or_else(|| format!("`{}` must be a non-negative integer", key))
+            };
+
+            match key.as_str() {
+                "tape_size" => config.tape_size = integer()? as usize,
+                "start_pointer" => config.start_pointer = integer()? as usize,
+                "max_steps" => config.max_steps = Some(integer()? as u64),
+                "max_loop_iterations" => config.max_loop_iterations = Some(integer()? as u64),
+                "instruction_limit_per_loop" => {
+                    config.instruction_limit_per_loop = Some(integer()? as u64)
+                }
+                "max_output_bytes" => config.max_output_bytes = Some(integer()? as u64),
+                "output_buffer_size" => config.output_buffer_size = integer()? as usize,
+                "cancel_check_interval" => config.cancel_check_interval = integer()? as u64,
+                "parallel_loops" => {
+                    config.parallel_loops = value
+                        .as_bool()
+                        .ok_or_else(|| format!("`{}` must be true or false", key))?
+                }
+                "debug_print" => {
+                    config.debug_print = value
+                        .as_bool()
+                        .ok_or_else(|| format!("`{}` must be true or false", key))?
+                }
+                "timeout" => {
+                    let seconds = value
+                        .as_float()
+                        .filter(|seconds| *seconds > 0.0 && seconds.is_finite())
+                        .ok_or_else(|| format!("`{}` must be a positive number of seconds", key))?;
+                    config.timeout = Some(Duration::from_secs_f64(seconds));
+                }
+                "io_mode" => {
+                    config.io_mode =
+                        value.as_str().and_then(IoMode::from_name).ok_or_else(|| {
+                            format!(
+                                "`{}` must be \"standard\", \"sandboxed\" or \"forbidden\"",
+                                key
+                            )
+                        })?
+                }
+                "write_mode" => {
+                    config.write_mode =
+                        value
+                            .as_str()
+                            .and_then(WriteMode::from_name)
+                            .ok_or_else(|| {
+                                format!("`{}` must be \"byte\", \"codepoint\" or \"utf8\"", key)
+                            })?
+                }
+                "tape_init" => {
+                    config.tape_init =
+                        value
+                            .as_str()
+                            .and_then(TapeInit::from_name)
+                            .ok_or_else(|| {
+                                format!(
+                            "`{}` must be \"zeros\", \"ones\", \"sequential\" or \"random:<seed>\"",
+                            key
+                        )
+                            })?
+                }
+                _ => return Err(format!("unknown key `{}`", key)),
+            }
+        }
+
+        Ok(config)
+    }
+
+    pub fn to_toml(&self) -> String {
+        let mut out = format!(
+            "tape_size = {}\nstart_pointer = {}\n",
+            self.tape_size, self.start_pointer
+        );
+        if let Some(max_steps) = self.max_steps {
+            out.push_str(&format!("max_steps = {}\n", max_steps));
+        }
+        if let Some(max_loop_iterations) = self.max_loop_iterations {
+            out.push_str(&format!("max_loop_iterations = {}\n", max_loop_iterations));
+        }
+        if let Some(limit) = self.instruction_limit_per_loop {
+            out.push_str(&format!("instruction_limit_per_loop = {}\n", limit));
+        }
+        if let Some(max_output_bytes) = self.max_output_bytes {
+            out.push_str(&format!("max_output_bytes = {}\n", max_output_bytes));
+        }
+        out.push_str(&format!("io_mode = \"{}\"\n", self.io_mode.name()));
+        out.push_str(&format!("tape_init = \"{}\"\n", self.tape_init));
+        out.push_str(&format!(
+            "output_buffer_size = {}\n",
+            self.output_buffer_size
+        ));
+        out.push_str(&format!("write_mode = \"{}\"\n", self.write_mode.name()));
+        if let Some(timeout) = self.timeout {
+            out.push_str(&format!(
+                "timeout = {}\n",
+                toml::Value::Float(timeout.as_secs_f64())
+            ));
+        }
+        out.push_str(&format!(
+            "cancel_check_interval = {}\n",
+            self.cancel_check_interval
+        ));
+        if self.parallel_loops {
+            out.push_str("parallel_loops = true\n");
+        }
+        if self.debug_print {
+            out.push_str("debug_print = true\n");
+        }
+        out
+    }
+}
+
+#[derive(Debug)]
+pub enum RuntimeError {
+    PointerUnderflow,
+    PointerOverflow {
+        tape_size: usize,
+    },
+    Io(io::Error),
+    StepLimitExceeded {
+        limit: u64,
+    },
+    LoopIterationLimitExceeded {
+        loop_pos: usize,
+        count: u64,
+    },
+    LoopBodyTooLong {
+        pos: usize,
+        steps: u64,
+    },
+    OutputLimitExceeded {
+        limit: u64,
+    },
+    IoForbidden,
+    // a `MacroCall` reached a backend, `MacroExpander` has to run first
+    UnexpandedMacro,
+    SharedTapeConflict {
+        path: PathBuf,
+        expected: u64,
+        found: u64,
+    },
+    Timeout {
+        limit: Duration,
+    },
+    TraceDivergence {
+        step: u64,
+        expected: String,
+        actual: String,
+    },
+    // `run_reverse` reached an instruction whose old cell values can't be recovered
+    Irreversible {
+        pos: usize,
+        op: &'static str,
+    },
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeError::PointerUnderflow => write!(f, "data pointer moved left of cell 0"),
+            RuntimeError::PointerOverflow { tape_size } => {
+                write!(
+                    f,
+                    "data pointer moved past the end of the tape ({} cells)",
+                    tape_size
+                )
+            }
+            RuntimeError::Io(err) => write!(f, "i/o error: {}", err),
+            RuntimeError::StepLimitExceeded { limit } => {
+                write!(f, "program exceeded the limit of {} steps", limit)
+            }
+            RuntimeError::LoopIterationLimitExceeded { loop_pos, count } => write!(
+                f,
+                "loop at #{} exceeded the limit of {} iterations",
+                loop_pos, count
+            ),
+            RuntimeError::LoopBodyTooLong { pos, steps } => write!(
+                f,
+                "loop at #{} exceeded the limit of {} instructions in one pass",
+                pos, steps
+            ),
+            RuntimeError::OutputLimitExceeded { limit } => {
+                write!(f, "program exceeded the limit of {} output bytes", limit)
+            }
+            RuntimeError::IoForbidden => write!(f, "i/o is not allowed in this interpreter"),
+            RuntimeError::UnexpandedMacro => {
+                write!(
+                    f,
+                    "macro call was never expanded, run the macro expander first"
+                )
+            }
+            RuntimeError::SharedTapeConflict {
+                path,
+                expected,
+                found,
+            } => write!(
+                f,
+                "shared tape {} is {} bytes, this tape needs {}",
+                path.display(),
+                found,
+                expected
+            ),
+            RuntimeError::Timeout { limit } => write!(
+                f,
+                "program exceeded the time limit of {} s",
+                limit.as_secs_f64()
+            ),
+            RuntimeError::TraceDivergence {
+                step,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "execution diverged from trace at step {}\n  expected: {}\n  actual:   {}",
+                step, expected, actual
+            ),
+            RuntimeError::Irreversible { pos, op } => {
+                write!(f, "{} at #{} can't be run in reverse", op, pos)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    // `cell` is the first cell that isn't part of a valid character
+    InvalidUtf8 { cell: usize },
+    NotTerminated { start: usize },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::InvalidUtf8 { cell } => {
+                write!(f, "cell {} is not part of a valid utf-8 character", cell)
+            }
+            DecodeError::NotTerminated { start } => {
+                write!(
+                    f,
+                    "no zero cell between cell {} and the end of the tape",
+                    start
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<io::Error> for RuntimeError {
+    fn from(err: io::Error) -> Self {
+        RuntimeError::Io(err)
+    }
+}
+
+// below this many iterations a thread costs more than it saves
+const MIN_ITERATIONS_PER_THREAD: usize = 1 << 14;
+
+#[derive(Debug)]
+enum Cells {
+    Owned(Vec<u8>),
+    Shared(SharedTape),
+}
+
+// a clone of a shared tape is a private copy of its cells, two tapes mapping the same file
+// would each hand out a `&mut [u8]` over it
+impl Clone for Cells {
+    fn clone(&self) -> Self {
+        Cells::Owned(self.to_vec())
+    }
+}
+
+impl Deref for Cells {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Cells::Owned(cells) => cells,
+            Cells::Shared(cells) => cells,
+        }
+    }
+}
+
+impl DerefMut for Cells {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match self {
+            Cells::Owned(cells) => cells,
+            Cells::Shared(cells) => cells,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Tape {
+    cells: Cells,
+    pointer: usize,
+}
+
+impl Tape {
+    pub fn new(size: usize, pointer: usize) -> Self {
+        Tape {
+            cells: Cells::Owned(vec![0; size]),
+            pointer,
+        }
+    }
+
+    // cells mapped from `path`, which other processes can map at the same time
+    pub fn shared(path: &Path, size: usize, pointer: usize) -> Result<Self, RuntimeError> {
+        Ok(Tape {
+            cells: Cells::Shared(SharedTape::open(path, size)?),
+            pointer,
+        })
+    }
+
+    // holds the shared tape's spinlock, `None` for a tape private to this process
+    pub fn lock(&self) -> Option<SharedTapeLock> {
+        match &self.cells {
+            Cells::Owned(_) => None,
+            Cells::Shared(cells) => Some(cells.lock()),
+        }
+    }
+
+    pub fn pointer(&self) -> usize {
+        self.pointer
+    }
+
+    pub fn cells(&self) -> &[u8] {
+        &self.cells
+    }
+
+    pub fn cells_mut(&mut self) -> &mut [u8] {
+        &mut self.cells
+    }
+
+    // the slice names for `cells` and `cells_mut`
+    pub fn as_slice(&self) -> &[u8] {
+        &self.cells
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.cells
+    }
+
+    // the cells without the pointer, a shared tape's are copied out of the mapping
+    pub fn into_inner(self) -> Vec<u8> {
+        match self.cells {
+            Cells::Owned(cells) => cells,
+            Cells::Shared(cells) => cells.to_vec(),
+        }
+    }
+
+    // FNV-1a over the cells and then the pointer, not cryptographic but two different tapes
+    // are very unlikely to collide
+    pub fn checksum(&self) -> u64 {
+        const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const PRIME: u64 = 0x0100_0000_01b3;
+        self.cells
+            .iter()
+            .chain(&(self.pointer as u64).to_le_bytes())
+            .fold(OFFSET_BASIS, |hash, &byte| {
+                (hash ^ byte as u64).wrapping_mul(PRIME)
+            })
+    }
+
+    // the utf-8 string from `start` up to the next zero cell, which isn't included
+    pub fn decode_cstring(&self, start: usize) -> Result<String, DecodeError> {
+        let bytes = self.cells.get(start..).unwrap_or_default();
+        let len = bytes
+            .iter()
+            .position(|&cell| cell == 0)
+            .ok_or(DecodeError::NotTerminated { start })?;
+        String::from_utf8(bytes[..len].to_vec()).map_err(|err| DecodeError::InvalidUtf8 {
+            cell: start + err.utf8_error().valid_up_to(),
+        })
+    }
+
+    // cells `addr` and `addr + 1` as a little-endian number, panics if either is off the tape
+    pub fn decode_u16_le(&self, addr: usize) -> u16 {
+        u16::from_le_bytes([self.cells[addr], self.cells[addr + 1]])
+    }
+
+    pub fn get(&self) -> u8 {
+        self.cells[self.pointer]
+    }
+
+    pub fn set(&mut self, value: u8) {
+        self.cells[self.pointer] = value;
+    }
+
+    pub fn move_right(&mut self) -> Result<(), RuntimeError> {
+        self.move_by(1)
+    }
+
+    pub fn move_left(&mut self) -> Result<(), RuntimeError> {
+        self.move_by(-1)
+    }
+
+    // non-zero cells with their index, zero stretches are skipped a word at a time
+    pub fn iter_nonzero(&self) -> impl Iterator<Item = (usize, u8)> + '_ {
+        NonZeroCells {
+            cells: &self.cells,
+            index: 0,
+        }
+    }
+
+    // clears `len` cells from `offset` cells away from the pointer, failing like a move
+    // would if the range leaves the tape
+    pub fn zero_range(&mut self, offset: isize, len: usize) -> Result<(), RuntimeError> {
+        let start = self
+            .pointer
+            .checked_add_signed(offset)
+            .ok_or(RuntimeError::PointerUnderflow)?;
+        if start + len > self.cells.len() {
+            return Err(RuntimeError::PointerOverflow {
+                tape_size: self.cells.len(),
+            });
+        }
+        simd::bulk_zero(&mut self.cells, start, len);
+        Ok(())
+    }
+
+    // copies `len` cells from `src_offset` cells away from the pointer to `dst_offset` cells
+    // away, failing like a move would if either range leaves the tape
+    pub fn copy_range(
+        &mut self,
+        src_offset: isize,
+        dst_offset: isize,
+        len: usize,
+    ) -> Result<(), RuntimeError> {
+        let start = |offset| {
+            let start = self
+                .pointer
+                .checked_add_signed(offset)
+                .ok_or(RuntimeError::PointerUnderflow)?;
+            if start + len > self.cells.len() {
+                return Err(RuntimeError::PointerOverflow {
+                    tape_size: self.cells.len(),
+                });
+            }
+            Ok(start)
+        };
+        let (src, dst) = (start(src_offset)?, start(dst_offset)?);
+        simd::bulk_copy(&mut self.cells, src, dst, len);
+        Ok(())
+    }
+
+    // what `[->+<]` with `factor` pluses `offset` cells away does in one go, a zero cell
+    // skips the loop so only a non-zero one can fail like a move would
+    pub fn multiply_add(&mut self, offset: isize, factor: u8) -> Result<(), RuntimeError> {
+        let value = self.get();
+        if value == 0 {
+            return Ok(());
+        }
+        let target = self
+            .pointer
+            .checked_add_signed(offset)
+            .ok_or(RuntimeError::PointerUnderflow)?;
+        if target >= self.cells.len() {
+            return Err(RuntimeError::PointerOverflow {
+                tape_size: self.cells.len(),
+            });
+        }
+        self.cells[target] = self.cells[target].wrapping_add(value.wrapping_mul(factor));
+        self.set(0);
+        Ok(())
+    }
+
+    // moves `direction` cells at a time until the current cell is zero, on running off the
+    // tape the pointer is left on the last cell it reached, as the equivalent loop would
+    pub fn find_zero(&mut self, direction: isize) -> Result<(), RuntimeError> {
+        let found = match direction {
+            1 => simd::find_zero(&self.cells, self.pointer),
+            -1 => simd::rfind_zero(&self.cells, self.pointer),
+            _ => {
+                let mut pointer = self.pointer;
+                loop {
+                    if self.cells[pointer] == 0 {
+                        break Some(pointer);
+                    }
+                    match pointer.checked_add_signed(direction) {
+                        Some(next) if next < self.cells.len() => pointer = next,
+                        _ => break None,
+                    }
+                }
+            }
+        };
+
+        match found {
+            Some(pointer) => {
+                self.pointer = pointer;
+                Ok(())
+            }
+            None => {
+                let stride = direction.unsigned_abs();
+                if direction > 0 {
+                    let last = self.cells.len() - 1;
+                    self.pointer += (last - self.pointer) / stride * stride;
+                } else {
+                    self.pointer %= stride;
+                }
+                self.move_by(direction)
+            }
+        }
+    }
+
+    pub fn move_by(&mut self, offset: isize) -> Result<(), RuntimeError> {
+        match self.pointer.checked_add_signed(offset) {
+            None => Err(RuntimeError::PointerUnderflow),
+            Some(pointer) if pointer >= self.cells.len() => Err(RuntimeError::PointerOverflow {
+                tape_size: self.cells.len(),
+            }),
+            Some(pointer) => {
+                self.pointer = pointer;
+                Ok(())
+            }
+        }
+    }
+}
+
+// exactly the given cells with the pointer on the first, the programmatic `--pre-fill-tape`
+impl FromIterator<u8> for Tape {
+    fn from_iter<I: IntoIterator<Item = u8>>(iter: I) -> Self {
+        iter.into_iter().collect::<Vec<u8>>().into()
+    }
+}
+
+// the bytes become the cells as they are, with the pointer on the first
+impl From<Vec<u8>> for Tape {
+    fn from(cells: Vec<u8>) -> Self {
+        Tape {
+            cells: Cells::Owned(cells),
+            pointer: 0,
+        }
+    }
+}
+
+impl From<Tape> for Vec<u8> {
+    fn from(tape: Tape) -> Self {
+        tape.into_inner()
+    }
+}
+
+struct NonZeroCells<'a> {
+    cells: &'a [u8],
+    index: usize,
+}
+
+impl Iterator for NonZeroCells<'_> {
+    type Item = (usize, u8);
+
+    fn next(&mut self) -> Option<(usize, u8)> {
+        const WORD: usize = std::mem::size_of::<u64>();
+
+        while let Some(word) = self.cells.get(self.index..self.index + WORD) {
+            if u64::from_ne_bytes(word.try_into().expect("slice is one word long")) != 0 {
+                break;
+            }
+            self.index += WORD;
+        }
+
+        while let Some(&cell) = self.cells.get(self.index) {
+            self.index += 1;
+            if cell != 0 {
+                return Some((self.index - 1, cell));
+            }
+        }
+
+        None
+    }
+}
+
+// output sink that can be handed to an interpreter and read back after the run
+#[derive(Debug, Clone, Default)]
+pub struct CapturedOutput {
+    buffer: Rc<RefCell<Vec<u8>>>,
+}
+
+impl CapturedOutput {
+    pub fn new() -> Self {
+        CapturedOutput::default()
+    }
+
+    pub fn contents(&self) -> Vec<u8> {
+        self.buffer.borrow().clone()
+    }
+}
+
+impl Write for CapturedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// output sink that records every write with the time it arrived, the interpreter buffers
+// output so set `output_buffer_size` to 0 for one entry per write instruction
+#[derive(Debug, Clone, Default)]
+pub struct OutputAccumulator {
+    entries: Rc<RefCell<Vec<OutputEntry>>>,
+}
+
+pub type OutputEntry = (Instant, Vec<u8>);
+
+impl OutputAccumulator {
+    pub fn new() -> Self {
+        OutputAccumulator::default()
+    }
+
+    pub fn entries(&self) -> Ref<'_, [OutputEntry]> {
+        Ref::map(self.entries.borrow(), Vec::as_slice)
+    }
+}
+
+impl Write for OutputAccumulator {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.entries
+            .borrow_mut()
+            .push((Instant::now(), buf.to_vec()));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// what a run touched, kept once statistics are enabled
+#[derive(Debug, Clone, Default)]
+pub struct RunStatistics {
+    written: Vec<bool>,
+    pub cells_written: usize,
+    pub lowest_pointer: usize,
+    pub highest_pointer: usize,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+}
+
+impl RunStatistics {
+    fn new(tape: &Tape) -> Self {
+        RunStatistics {
+            written: vec![false; tape.cells.len()],
+            lowest_pointer: tape.pointer,
+            highest_pointer: tape.pointer,
+            ..RunStatistics::default()
+        }
+    }
+
+    // number of cells between the leftmost and rightmost the pointer reached
+    pub fn peak_tape_size(&self) -> usize {
+        self.highest_pointer - self.lowest_pointer + 1
+    }
+
+    fn observe(&mut self, instr: &Instruction, pointer: usize, io: bool) {
+        match instr {
+            Instruction::IncrementPointer
+            | Instruction::DecrementPointer
+            | Instruction::Move(_)
+            | Instruction::FindZero { .. } => {
+                self.lowest_pointer = self.lowest_pointer.min(pointer);
+                self.highest_pointer = self.highest_pointer.max(pointer);
+                return;
+            }
+            // `bytes_written` is counted as the bytes go out, a write can be several
+            Instruction::Read if io => self.bytes_read += 1,
+            Instruction::Write | Instruction::Read | Instruction::Loop(_) => return,
+            Instruction::BulkZero { start_offset, len }
+            | Instruction::CopyRange {
+                dst_offset: start_offset,
+                len,
+                ..
+            } => {
+                let start = (pointer as isize + start_offset) as usize;
+                for cell in start..start + len {
+                    self.mark_written(cell);
+                }
+                return;
+            }
+            // the target isn't checked against the tape when the current cell is zero
+            Instruction::MultiplyAdd { offset, .. } => {
+                if let Some(target) = pointer.checked_add_signed(*offset) {
+                    self.mark_written(target);
+                }
+            }
+            _ => (),
+        }
+
+        self.mark_written(pointer);
+    }
+
+    fn mark_written(&mut self, cell: usize) {
+        if let Some(written) = self.written.get_mut(cell).filter(|written| !**written) {
+            *written = true;
+            self.cells_written += 1;
+        }
+    }
+}
+
+// one write to a watched cell, see `Interpreter::watch_cell`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellWrite {
+    pub step: u64,
+    pub position: usize,
+    pub value: u8,
+}
+
+impl fmt::Display for CellWrite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "step={} instr=pos:{} value={}",
+            self.step, self.position, self.value
+        )
+    }
+}
+
+// what a `StdinQueue` yields once its bytes run out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EofBehavior {
+    // reads return 0, so `Read` fails with an i/o error like it does on stdin
+    #[default]
+    Eof,
+    // every further read yields this byte
+    Value(u8),
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    data: Vec<u8>,
+    position: usize,
+    eof: EofBehavior,
+}
+
+// input source for tests, hands out one byte per read and can be checked afterwards
+// for how much of it the program consumed, clones share the same queue
+#[derive(Debug, Clone, Default)]
+pub struct StdinQueue {
+    state: Rc<RefCell<QueueState>>,
+}
+
+impl StdinQueue {
+    pub fn from_bytes(data: &[u8]) -> Self {
+        StdinQueue {
+            state: Rc::new(RefCell::new(QueueState {
+                data: data.to_vec(),
+                ..QueueState::default()
+            })),
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Self {
+        StdinQueue::from_bytes(s.as_bytes())
+    }
+
+    pub fn with_eof(self, eof: EofBehavior) -> Self {
+        self.state.borrow_mut().eof = eof;
+        self
+    }
+
+    pub fn remaining(&self) -> usize {
+        let state = self.state.borrow();
+        state.data.len() - state.position
+    }
+}
+
+impl Read for StdinQueue {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut state = self.state.borrow_mut();
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        match state.data.get(state.position) {
+            Some(&byte) => {
+                buf[0] = byte;
+                state.position += 1;
+                Ok(1)
+            }
+            None => match state.eof {
+                EofBehavior::Eof => Ok(0),
+                EofBehavior::Value(byte) => {
+                    buf[0] = byte;
+                    Ok(1)
+                }
+            },
+        }
+    }
+}
+
+// single-threaded by design: boxed I/O, tracers and `CapturedOutput`/`StdinQueue` handles
+// carry no `Send` bound, which keeps them cheap, `ThreadSafeInterpreter` is the `Send + Sync`
+// variant for thread pools
+pub struct Interpreter {
+    config: InterpreterConfig,
+    tape: Tape,
+    input: Box<dyn Read>,
+    stdin_queue: Option<StdinQueue>,
+    output: BufWriter<Box<dyn Write>>,
+    // start of a UTF-8 sequence `WriteMode::Utf8Bytes` hasn't seen the end of yet
+    utf8_pending: Vec<u8>,
+    tracer: Option<Box<dyn TraceSink>>,
+    // hit count per opcode position, only kept once coverage is enabled
+    coverage: Option<Vec<u64>>,
+    profiler: Option<Profiler>,
+    statistics: Option<RunStatistics>,
+    // every write so far to each cell passed to `watch_cell`
+    watched_cells: Vec<(usize, Vec<CellWrite>)>,
+    // iterations so far of the loop starting at each opcode position
+    loop_iterations: Vec<u64>,
+    // loop body passes under way, outermost first, with the step count each started at
+    loop_passes: Vec<(usize, u64)>,
+    output_bytes: u64,
+    steps: u64,
+    // raised by the watchdog once `timeout` runs out
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Interpreter {
+    pub fn new(config: InterpreterConfig) -> Self {
+        let mut tape = Tape::new(config.tape_size, config.start_pointer);
+        config.tape_init.fill(&mut tape.cells);
+
+        Interpreter {
+            tape,
+            output: BufWriter::with_capacity(config.output_buffer_size, Box::new(io::stdout())),
+            utf8_pending: Vec::new(),
+            config,
+            input: Box::new(io::stdin()),
+            stdin_queue: None,
+            tracer: None,
+            coverage: None,
+            profiler: None,
+            statistics: None,
+            watched_cells: Vec::new(),
+            loop_iterations: Vec::new(),
+            loop_passes: Vec::new(),
+            output_bytes: 0,
+            steps: 0,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // replaces the tape, zero-padding it up to `tape_size` when shorter, the pointer stays
+    // where `tape` has it
+    pub fn with_tape(mut self, mut tape: Tape) -> Self {
+        if let Cells::Owned(cells) = &mut tape.cells {
+            if cells.len() < self.config.tape_size {
+                cells.resize(self.config.tape_size, 0);
+            }
+        }
+        self.tape = tape;
+        if let Some(statistics) = self.statistics.as_mut() {
+            *statistics = RunStatistics::new(&self.tape);
+        }
+        self
+    }
+
+    // a copy of the current state that runs on its own, input and output can't be
+    // duplicated so the fork starts with no input and discards what it writes until
+    // `set_input` / `set_output` give it something else, the tracer stays with `self`
+    pub fn fork(&self) -> Interpreter {
+        Interpreter {
+            config: self.config.clone(),
+            tape: self.tape.clone(),
+            input: Box::new(io::empty()),
+            stdin_queue: None,
+            output: BufWriter::with_capacity(self.config.output_buffer_size, Box::new(io::sink())),
+            utf8_pending: Vec::new(),
+            tracer: None,
+            coverage: self.coverage.clone(),
+            profiler: self.profiler.clone(),
+            statistics: self.statistics.clone(),
+            watched_cells: self.watched_cells.clone(),
+            loop_iterations: self.loop_iterations.clone(),
+            loop_passes: self.loop_passes.clone(),
+            output_bytes: self.output_bytes,
+            steps: self.steps,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn config(&self) -> &InterpreterConfig {
+        &self.config
+    }
+
+    pub fn tape(&self) -> &Tape {
+        &self.tape
+    }
+
+    pub fn tape_mut(&mut self) -> &mut Tape {
+        &mut self.tape
+    }
+
+    // see `Tape::checksum`
+    pub fn state_checksum(&self) -> u64 {
+        self.tape.checksum()
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub(crate) fn set_steps(&mut self, steps: u64) {
+        self.steps = steps;
+    }
+
+    // `run` flushes when it returns, this is for output that is needed sooner
+    pub fn flush_output(&mut self) -> Result<(), RuntimeError> {
+        self.output.flush()?;
+        Ok(())
+    }
+
+    pub fn set_input(&mut self, input: impl Read + 'static) {
+        self.input = Box::new(input);
+        self.stdin_queue = None;
+    }
+
+    // like `set_input`, but the queue stays reachable through `stdin_queue`
+    pub fn set_stdin_queue(&mut self, queue: StdinQueue) {
+        self.input = Box::new(queue.clone());
+        self.stdin_queue = Some(queue);
+    }
+
+    pub fn stdin_queue(&self) -> Option<&StdinQueue> {
+        self.stdin_queue.as_ref()
+    }
+
+    // whatever is still buffered for the previous writer is flushed to it first
+    pub fn set_output(&mut self, output: impl Write + 'static) {
+        let _ = self.output.flush();
+        self.output = BufWriter::with_capacity(self.config.output_buffer_size, Box::new(output));
+    }
+
+    pub fn set_tracer(&mut self, tracer: impl TraceSink + 'static) {
+        self.tracer = Some(Box::new(tracer));
+    }
+
+    pub fn take_tracer(&mut self) -> Option<Box<dyn TraceSink>> {
+        self.tracer.take()
+    }
+
+    pub fn enable_coverage(&mut self) {
+        self.coverage.get_or_insert_with(Vec::new);
+    }
+
+    pub fn coverage(&self) -> Option<&[u64]> {
+        self.coverage.as_deref()
+    }
+
+    pub fn attach_profiler(&mut self, profiler: Profiler) {
+        self.profiler = Some(profiler);
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    pub fn enable_statistics(&mut self) {
+        self.statistics = Some(RunStatistics::new(&self.tape));
+    }
+
+    pub fn statistics(&self) -> Option<&RunStatistics> {
+        self.statistics.as_ref()
+    }
+
+    // records every instruction that writes `cell`, even when the value stays the same
+    pub fn watch_cell(&mut self, cell: usize) {
+        if !self
+            .watched_cells
+            .iter()
+            .any(|(watched, _)| *watched == cell)
+        {
+            self.watched_cells.push((cell, Vec::new()));
+        }
+    }
+
+    // the writes to each watched cell in the order they happened, cells in `watch_cell` order
+    pub fn cell_writes(&self) -> &[(usize, Vec<CellWrite>)] {
+        &self.watched_cells
+    }
+
+    fn tracks_positions(&self) -> bool {
+        self.tracer.is_some()
+            || self.coverage.is_some()
+            || !self.watched_cells.is_empty()
+            || self.config.max_loop_iterations.is_some()
+            || self.config.instruction_limit_per_loop.is_some()
+    }
+
+    pub fn run(&mut self, instructions: &[Instruction]) -> Result<(), RuntimeError> {
+        self.prepare(instructions);
+        info!(instructions = instructions.len(), "program started");
+        let watchdog = self.start_watchdog();
+        let result = self.run_block(instructions, 0);
+        drop(watchdog);
+        self.finish_output()?;
+        info!(steps = self.steps, ok = result.is_ok(), "program finished");
+        result
+    }
+
+    // runs `instructions` one step per `next`, a loop test counts as a step like it does
+    // for `max_steps`. the profiler isn't fed, everything else `run` tracks is
+    pub fn iter_steps<'a>(&'a mut self, instructions: &'a [Instruction]) -> StepIterator<'a> {
+        self.prepare(instructions);
+        let watchdog = self.start_watchdog();
+        StepIterator {
+            interpreter: self,
+            blocks: vec![(instructions, 0, 0)],
+            _watchdog: watchdog,
+            finished: false,
+        }
+    }
+
+    // sizes the per-position counters for a program before it runs
+    fn prepare(&mut self, instructions: &[Instruction]) {
+        let len = instructions.iter().map(Instruction::opcode_len).sum();
+        if let Some(coverage) = self.coverage.as_mut() {
+            if coverage.len() < len {
+                coverage.resize(len, 0);
+            }
+        }
+
+        if self.config.max_loop_iterations.is_some() && self.loop_iterations.len() < len {
+            self.loop_iterations.resize(len, 0);
+        }
+        // left over when an earlier run stopped with an error
+        self.loop_passes.clear();
+    }
+
+    // undoes `instructions`, last one first: increments and moves run inverted and i/o is
+    // skipped. sets, clears, copies, multiply-adds and searches lose the old values, so they
+    // end the run with `RuntimeError::Irreversible`. loops keep their test, so a loop only
+    // steps back through its reversed body while its cell is non-zero
+    pub fn run_reverse(&mut self, instructions: &[Instruction]) -> Result<(), RuntimeError> {
+        let len = instructions.iter().map(Instruction::opcode_len).sum();
+        self.run_block_reverse(instructions, len)
+    }
+
+    fn run_block_reverse(
+        &mut self,
+        instructions: &[Instruction],
+        end: usize,
+    ) -> Result<(), RuntimeError> {
+        let mut position = end;
+
+        for instr in instructions.iter().rev() {
+            position -= instr.opcode_len();
+            match instr {
+                Instruction::DebugPrint { .. } => (),
+                Instruction::Loop(body) => loop {
+                    self.step(position, instr)?;
+                    if self.tape.get() == 0 {
+                        break;
+                    }
+                    self.run_block_reverse(body, position + instr.opcode_len() - 1)?;
+                },
+                Instruction::Read | Instruction::Write => self.step(position, instr)?,
+                _ => {
+                    let inverse = inverse(instr).ok_or(RuntimeError::Irreversible {
+                        pos: position,
+                        op: instr.mnemonic(),
+                    })?;
+                    // like a forward run, the step is logged once the cell holds its new value
+                    self.execute(&inverse)?;
+                    self.step(position, instr)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // clears the cancel flag and, with a timeout set, starts the thread that raises it
+    pub(crate) fn start_watchdog(&self) -> Option<Watchdog> {
+        self.cancelled.store(false, Ordering::Relaxed);
+        self.config
+            .timeout
+            .map(|limit| Watchdog::start(limit, self.cancelled.clone()))
+    }
+
+    // polled every `cancel_check_interval` steps while a timeout is set
+    pub(crate) fn check_cancelled(&self, steps: u64) -> Result<(), RuntimeError> {
+        let Some(limit) = self.config.timeout else {
+            return Ok(());
+        };
+        if steps.is_multiple_of(self.config.cancel_check_interval.max(1))
+            && self.cancelled.load(Ordering::Relaxed)
+        {
+            return Err(RuntimeError::Timeout { limit });
+        }
+        Ok(())
+    }
+
+    // a UTF-8 sequence still open when the program stops is written as U+FFFD
+    pub(crate) fn finish_output(&mut self) -> Result<(), RuntimeError> {
+        if !self.utf8_pending.is_empty() {
+            self.utf8_pending.clear();
+            self.emit(
+                char::REPLACEMENT_CHARACTER
+                    .encode_utf8(&mut [0; 4])
+                    .as_bytes(),
+            )?;
+        }
+        self.output.flush()?;
+        Ok(())
+    }
+
+    // returns how many bytes the cell came out as, none while a UTF-8 sequence is still open
+    // and up to 7 when a cut-short one goes out as U+FFFD ahead of a complete one
+    fn write_cell(&mut self, cell: u8) -> Result<u64, RuntimeError> {
+        let mut encoded = [0; 4];
+
+        match self.config.write_mode {
+            WriteMode::Byte => self.emit(&[cell]),
+            WriteMode::UnicodeCodepoint => {
+                let c = char::from_u32(cell as u32).unwrap_or(char::REPLACEMENT_CHARACTER);
+                self.emit(c.encode_utf8(&mut encoded).as_bytes())
+            }
+            WriteMode::Utf8Bytes => {
+                let replacement = char::REPLACEMENT_CHARACTER
+                    .encode_utf8(&mut encoded)
+                    .as_bytes();
+                let mut out = Vec::new();
+                let pending = &mut self.utf8_pending;
+                // anything but a continuation byte cuts the open sequence short
+                if !pending.is_empty() && cell & 0xc0 != 0x80 {
+                    pending.clear();
+                    out.extend_from_slice(replacement);
+                }
+
+                pending.push(cell);
+                let len = match pending[0] {
+                    0x00..=0x7f => 1,
+                    0xc2..=0xdf => 2,
+                    0xe0..=0xef => 3,
+                    0xf0..=0xf4 => 4,
+                    _ => 1,
+                };
+                if pending.len() >= len {
+                    // overlong forms and surrogates only show up once the sequence is complete
+                    match std::str::from_utf8(pending) {
+                        Ok(valid) => out.extend_from_slice(valid.as_bytes()),
+                        Err(_) => out.extend_from_slice(replacement),
+                    }
+                    pending.clear();
+                }
+                self.emit(&out)
+            }
+        }
+    }
+
+    // writes program output, `max_output_bytes` counts every byte that goes out
+    fn emit(&mut self, bytes: &[u8]) -> Result<u64, RuntimeError> {
+        let len = bytes.len() as u64;
+        if let Some(limit) = self.config.max_output_bytes {
+            if self.output_bytes + len > limit {
+                return Err(RuntimeError::OutputLimitExceeded { limit });
+            }
+        }
+        self.output.write_all(bytes)?;
+        self.output_bytes += len;
+        Ok(len)
+    }
+
+    fn run_loop(
+        &mut self,
+        instr: &Instruction,
+        body: &[Instruction],
+        position: usize,
+    ) -> Result<(), RuntimeError> {
+        loop {
+            self.step(position, instr)?;
+            if self.tape.get() == 0 {
+                return Ok(());
+            }
+            if self.config.max_loop_iterations.is_some() {
+                self.count_iteration(position)?;
+            }
+            self.begin_pass(position);
+            self.run_block(body, position + 1)?;
+            self.end_pass();
+        }
+    }
+
+    // applies every iteration of a loop whose iterations are independent at once, split
+    // over threads when there are enough of them. `false` leaves the loop to `run_loop`:
+    // the body doesn't qualify, the tape is shared, an iteration would leave the tape or
+    // the step limit would be hit part way through
+    fn run_independent_loop(&mut self, body: &[Instruction]) -> Result<bool, RuntimeError> {
+        // most loops end where they started, those are ruled out before allocating
+        if LoopMeta::stride_of(body).unwrap_or(0) == 0 {
+            return Ok(false);
+        }
+        let meta = LoopMeta::of(body);
+        if !meta.iterations_independent() || matches!(self.tape.cells, Cells::Shared(_)) {
+            return Ok(false);
+        }
+
+        // no iteration writes a cell a later one tests, so the count is known up front
+        let len = self.tape.cells.len() as isize;
+        let start = self.tape.pointer as isize;
+        let mut iterations = 0;
+        loop {
+            let at = start + iterations * meta.stride;
+            if !(0..len).contains(&at) {
+                return Ok(false);
+            }
+            if self.tape.cells[at as usize] == 0 {
+                break;
+            }
+            if at + meta.reach.start() < 0 || at + meta.reach.end() >= len {
+                return Ok(false);
+            }
+            iterations += 1;
+        }
+
+        let steps = (iterations as u64 + 1) * (body.len() as u64 + 1) - body.len() as u64;
+        if let Some(limit) = self.config.max_steps {
+            if self.steps + steps > limit {
+                return Ok(false);
+            }
+        }
+
+        let count = iterations as usize;
+        let stride = meta.stride.unsigned_abs();
+        let window = meta.window();
+        let deltas = &meta.deltas;
+        let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+        let per_thread = count.div_ceil(threads).max(MIN_ITERATIONS_PER_THREAD);
+
+        // one block of `stride` cells per iteration, holding that iteration's window
+        if meta.stride > 0 {
+            let first = (start + window.start()) as usize;
+            let end = (first + count * stride).min(len as usize);
+            let apply = move |cells: &mut [u8]| {
+                for block in cells.chunks_mut(stride) {
+                    for &(offset, delta) in deltas {
+                        let cell = &mut block[(offset - window.start()) as usize];
+                        *cell = cell.wrapping_add(delta);
+                    }
+                }
+            };
+            let (apply, region) = (&apply, &mut self.tape.cells[first..end]);
+            if count <= per_thread {
+                apply(region);
+            } else {
+                thread::scope(|scope| {
+                    for group in region.chunks_mut(stride * per_thread) {
+                        scope.spawn(move || apply(group));
+                    }
+                });
+            }
+        } else {
+            let end = (start + window.end()) as usize + 1;
+            let first = end.saturating_sub(count * stride);
+            let apply = move |cells: &mut [u8]| {
+                for block in cells.rchunks_mut(stride) {
+                    let block_len = block.len() as isize;
+                    for &(offset, delta) in deltas {
+                        let cell = &mut block[(block_len - (window.end() + 1 - offset)) as usize];
+                        *cell = cell.wrapping_add(delta);
+                    }
+                }
+            };
+            let (apply, region) = (&apply, &mut self.tape.cells[first..end]);
+            if count <= per_thread {
+                apply(region);
+            } else {
+                thread::scope(|scope| {
+                    for group in region.rchunks_mut(stride * per_thread) {
+                        scope.spawn(move || apply(group));
+                    }
+                });
+            }
+        }
+
+        self.tape.pointer = (start + iterations * meta.stride) as usize;
+        self.steps += steps;
+        Ok(true)
+    }
+
+    fn run_block(
+        &mut self,
+        instructions: &[Instruction],
+        mut position: usize,
+    ) -> Result<(), RuntimeError> {
+        let tracks_positions = self.tracks_positions();
+
+        for instr in instructions {
+            match instr {
+                // not a step, it stands for no opcode
+                Instruction::DebugPrint { label } => self.debug_print(label),
+                Instruction::Loop(body) if self.profiler.is_some() => {
+                    self.run_profiled_loop(instr, body, position)?
+                }
+                Instruction::Loop(body)
+                    if self.config.parallel_loops
+                        && !tracks_positions
+                        && self.statistics.is_none() =>
+                {
+                    if !self.run_independent_loop(body)? {
+                        self.run_loop(instr, body, position)?;
+                    }
+                }
+                Instruction::Loop(body) => self.run_loop(instr, body, position)?,
+                // kept apart so plain runs don't pay for the instrumentation
+                _ if self.profiler.is_some() || self.statistics.is_some() => {
+                    let started = Instant::now();
+                    self.execute(instr)?;
+                    if let Some(profiler) = self.profiler.as_mut() {
+                        profiler.record(instr, started.elapsed());
+                    }
+                    if let Some(statistics) = self.statistics.as_mut() {
+                        let io = self.config.io_mode == IoMode::Standard;
+                        statistics.observe(instr, self.tape.pointer, io);
+                    }
+                    self.step(position, instr)?;
+                }
+                _ => {
+                    self.execute(instr)?;
+                    self.step(position, instr)?;
+                }
+            }
+
+            if tracks_positions {
+                position += instr.opcode_len();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn run_profiled_loop(
+        &mut self,
+        instr: &Instruction,
+        body: &[Instruction],
+        position: usize,
+    ) -> Result<(), RuntimeError> {
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.enter_loop();
+        }
+
+        loop {
+            self.step(position, instr)?;
+            let started = Instant::now();
+            let done = self.tape.get() == 0;
+            if let Some(profiler) = self.profiler.as_mut() {
+                profiler.record(instr, started.elapsed());
+                if !done {
+                    profiler.loop_iteration();
+                }
+            }
+            if done {
+                break;
+            }
+            self.count_iteration(position)?;
+            self.begin_pass(position);
+            self.run_block(body, position + 1)?;
+            self.end_pass();
+        }
+
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.exit_loop();
+        }
+        Ok(())
+    }
+
+    pub(crate) fn execute(&mut self, instr: &Instruction) -> Result<(), RuntimeError> {
+        // other processes on a shared tape see each instruction whole, except a read
+        // which would hold the lock while it waits for input
+        let _lock = match instr {
+            Instruction::Read => None,
+            _ => self.tape.lock(),
+        };
+        match instr {
+            Instruction::IncrementPointer => self.tape.move_right()?,
+            Instruction::DecrementPointer => self.tape.move_left()?,
+            Instruction::Increment => self.tape.set(self.tape.get().wrapping_add(1)),
+            Instruction::Decrement => self.tape.set(self.tape.get().wrapping_sub(1)),
+            Instruction::Write | Instruction::Read if self.config.io_mode != IoMode::Standard => {
+                if self.config.io_mode == IoMode::Forbidden {
+                    return Err(RuntimeError::IoForbidden);
+                }
+            }
+            Instruction::Write => {
+                let written = self.write_cell(self.tape.get())?;
+                if let Some(statistics) = self.statistics.as_mut() {
+                    statistics.bytes_written += written;
+                }
+            }
+            Instruction::Read => {
+                // a prompt written before the read has to be visible while the program waits
+                self.output.flush()?;
+                let mut input: [u8; 1] = [0; 1];
+                self.input.read_exact(&mut input)?;
+                self.tape.set(input[0]);
+            }
+            Instruction::Loop(_) => unreachable!("loops are executed by run_block"),
+            Instruction::Add(n) => self.tape.set(self.tape.get().wrapping_add(*n)),
+            Instruction::Move(offset) => self.tape.move_by(*offset)?,
+            Instruction::SetCell(value) => self.tape.set(*value),
+            Instruction::BulkZero { start_offset, len } => {
+                self.tape.zero_range(*start_offset, *len)?
+            }
+            Instruction::FindZero { direction } => self.tape.find_zero(*direction)?,
+            Instruction::MultiplyAdd { offset, factor } => {
+                self.tape.multiply_add(*offset, *factor)?
+            }
+            Instruction::CopyRange {
+                src_offset,
+                dst_offset,
+                len,
+            } => self.tape.copy_range(*src_offset, *dst_offset, *len)?,
+            Instruction::DebugPrint { label } => self.debug_print(label),
+            Instruction::MacroCall { .. } => return Err(RuntimeError::UnexpandedMacro),
+        }
+
+        Ok(())
+    }
+
+    fn debug_print(&self, label: &str) {
+        if self.config.debug_print {
+            eprintln!(
+                "[DEBUG {}] ptr={} cell={}",
+                label,
+                self.tape.pointer,
+                self.tape.get()
+            );
+        }
+    }
+
+    // `instr` has already run, none of the writing instructions move the pointer
+    fn record_cell_writes(&mut self, position: usize, instr: &Instruction) {
+        let pointer = self.tape.pointer;
+        let (written, target) = match instr {
+            Instruction::Increment
+            | Instruction::Decrement
+            | Instruction::Add(_)
+            | Instruction::SetCell(_) => (pointer..pointer + 1, None),
+            Instruction::Read if self.config.io_mode == IoMode::Standard => {
+                (pointer..pointer + 1, None)
+            }
+            Instruction::BulkZero { start_offset, len }
+            | Instruction::CopyRange {
+                dst_offset: start_offset,
+                len,
+                ..
+            } => {
+                let start = (pointer as isize + start_offset) as usize;
+                (start..start + len, None)
+            }
+            Instruction::MultiplyAdd { offset, .. } => (
+                pointer..pointer + 1,
+                Some((pointer as isize + offset) as usize),
+            ),
+            _ => return,
+        };
+
+        for (cell, writes) in &mut self.watched_cells {
+            if written.contains(cell) || target == Some(*cell) {
+                writes.push(CellWrite {
+                    step: self.steps,
+                    position,
+                    value: self.tape.cells[*cell],
+                });
+            }
+        }
+    }
+
+    fn count_iteration(&mut self, position: usize) -> Result<(), RuntimeError> {
+        let Some(limit) = self.config.max_loop_iterations else {
+            return Ok(());
+        };
+
+        let count = &mut self.loop_iterations[position];
+        *count += 1;
+        if *count > limit {
+            return Err(RuntimeError::LoopIterationLimitExceeded {
+                loop_pos: position,
+                count: limit,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn begin_pass(&mut self, position: usize) {
+        if self.config.instruction_limit_per_loop.is_some() {
+            self.loop_passes.push((position, self.steps));
+        }
+    }
+
+    fn end_pass(&mut self) {
+        if self.config.instruction_limit_per_loop.is_some() {
+            self.loop_passes.pop();
+        }
+    }
+
+    fn step(&mut self, position: usize, instr: &Instruction) -> Result<(), RuntimeError> {
+        self.steps += 1;
+        trace!(
+            step = self.steps,
+            position,
+            op = instr.mnemonic(),
+            pointer = self.tape.pointer,
+            cell = self.tape.get()
+        );
+
+        if let Some(limit) = self.config.max_steps {
+            if self.steps > limit {
+                return Err(RuntimeError::StepLimitExceeded { limit });
+            }
+        }
+        self.check_cancelled(self.steps)?;
+
+        // the outermost pass has run at least as long as any inside it
+        if let (Some(limit), Some(&(pos, started))) = (
+            self.config.instruction_limit_per_loop,
+            self.loop_passes.first(),
+        ) {
+            if self.steps - started > limit {
+                return Err(RuntimeError::LoopBodyTooLong { pos, steps: limit });
+            }
+        }
+
+        if let Some(hits) = self.coverage.as_mut().and_then(|c| c.get_mut(position)) {
+            *hits += 1;
+        }
+
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.record(&TraceEvent {
+                step: self.steps,
+                position,
+                op: instr.mnemonic(),
+                pointer: self.tape.pointer,
+                cell: self.tape.get(),
+            })?;
+        }
+
+        if !self.watched_cells.is_empty() {
+            self.record_cell_writes(position, instr);
+        }
+
+        Ok(())
+    }
+}
+
+// the tape right after a step of `StepIterator`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TapeSnapshot {
+    pub step: u64,
+    // opcode position of the instruction that just ran
+    pub position: usize,
+    pub pointer: usize,
+    pub tape: Vec<u8>,
+}
+
+// see `Interpreter::iter_steps`, the first error ends the iteration and output is flushed
+// once the program finishes
+pub struct StepIterator<'a> {
+    interpreter: &'a mut Interpreter,
+    // the block being run at each loop depth, with the index and opcode position of the
+    // next instruction in it. a loop stays the next instruction of its block while its body
+    // runs, so the test comes round again once the body is done
+    blocks: Vec<(&'a [Instruction], usize, usize)>,
+    _watchdog: Option<Watchdog>,
+    finished: bool,
+}
+
+impl StepIterator<'_> {
+    // the opcode position of the instruction that ran, `None` once the program is done
+    fn advance(&mut self) -> Result<Option<usize>, RuntimeError> {
+        let interpreter = &mut *self.interpreter;
+        loop {
+            let Some(&(block, index, position)) = self.blocks.last() else {
+                return Ok(None);
+            };
+            let Some(instr) = block.get(index) else {
+                self.blocks.pop();
+                if !self.blocks.is_empty() {
+                    interpreter.end_pass();
+                }
+                continue;
+            };
+            let next = (block, index + 1, position + instr.opcode_len());
+
+            match instr {
+                // not a step, it stands for no opcode
+                Instruction::DebugPrint { label } => {
+                    interpreter.debug_print(label);
+                    *self.blocks.last_mut().expect("checked above") = next;
+                }
+                Instruction::Loop(body) => {
+                    interpreter.step(position, instr)?;
+                    if interpreter.tape.get() == 0 {
+                        *self.blocks.last_mut().expect("checked above") = next;
+                    } else {
+                        interpreter.count_iteration(position)?;
+                        interpreter.begin_pass(position);
+                        self.blocks.push((body, 0, position + 1));
+                    }
+                    return Ok(Some(position));
+                }
+                _ => {
+                    interpreter.execute(instr)?;
+                    if let Some(statistics) = interpreter.statistics.as_mut() {
+                        let io = interpreter.config.io_mode == IoMode::Standard;
+                        statistics.observe(instr, interpreter.tape.pointer, io);
+                    }
+                    interpreter.step(position, instr)?;
+                    *self.blocks.last_mut().expect("checked above") = next;
+                    return Ok(Some(position));
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for StepIterator<'_> {
+    type Item = Result<TapeSnapshot, RuntimeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let stepped = self.advance().and_then(|position| {
+            if position.is_none() {
+                self.interpreter.finish_output()?;
+            }
+            Ok(position)
+        });
+        match stepped {
+            Ok(Some(position)) => {
+                let interpreter = &*self.interpreter;
+                Some(Ok(TapeSnapshot {
+                    step: interpreter.steps,
+                    position,
+                    pointer: interpreter.tape.pointer,
+                    tape: interpreter.tape.cells.to_vec(),
+                }))
+            }
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(err) => {
+                self.finished = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+// raises `cancelled` after `limit` unless dropped first, dropping wakes the thread and joins it
+pub(crate) struct Watchdog {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    fn start(limit: Duration, cancelled: Arc<AtomicBool>) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(limit) {
+                cancelled.store(true, Ordering::Relaxed);
+            }
+        });
+        Watchdog {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn inverse(instr: &Instruction) -> Option<Instruction> {
+    match instr {
+        Instruction::IncrementPointer => Some(Instruction::DecrementPointer),
+        Instruction::DecrementPointer => Some(Instruction::IncrementPointer),
+        Instruction::Increment => Some(Instruction::Decrement),
+        Instruction::Decrement => Some(Instruction::Increment),
+        Instruction::Add(n) => Some(Instruction::Add(n.wrapping_neg())),
+        Instruction::Move(offset) => Some(Instruction::Move(-offset)),
+        _ => None,
+    }
+}
+
+// runs a program against fixed input and returns everything it wrote
+pub fn run_with_input(
+    instructions: &[Instruction],
+    config: &InterpreterConfig,
+    input: &[u8],
+) -> Result<Vec<u8>, RuntimeError> {
+    let output = CapturedOutput::new();
+    let mut interpreter = Interpreter::new(config.clone());
+    interpreter.set_input(io::Cursor::new(input.to_vec()));
+    interpreter.set_output(output.clone());
+    interpreter.run(instructions)?;
+    Ok(output.contents())
+}
@@ -0,0 +1,120 @@
+use crate::lexer::{debug_print_label, OpCode};
+use crate::toml;
+
+// the key each opcode's keyword is given under in a dialect file
+const KEYS: [(&str, OpCode); 8] = [
+    ("increment", OpCode::Increment),
+    ("decrement", OpCode::Decrement),
+    ("increment_pointer", OpCode::IncrementPointer),
+    ("decrement_pointer", OpCode::DecrementPointer),
+    ("write", OpCode::Write),
+    ("read", OpCode::Read),
+    ("loop_begin", OpCode::LoopBegin),
+    ("loop_end", OpCode::LoopEnd),
+];
+
+// a keyword per opcode loaded from a dialect file, standing in for the `sv` + `o`s words
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeywordSet {
+    // longest first, so `popall` wins over `pop`
+    words: Vec<(String, OpCode)>,
+}
+
+impl KeywordSet {
+    // every opcode needs its own non-empty keyword, unknown keys are refused so typos show
+    pub fn from_toml(source: &str) -> Result<KeywordSet, String> {
+        let table = toml::parse(source).map_err(|err| err.to_string())?;
+
+        if let Some(key) = table
+            .keys()
+            .find(|key| !KEYS.iter().any(|(name, _)| name == key))
+        {
+            return Err(format!("unknown key `{}`", key));
+        }
+
+        let mut words: Vec<(String, OpCode)> = Vec::new();
+        for (i, (key, op)) in KEYS.into_iter().enumerate() {
+            let word = table
+                .get(key)
+                .ok_or_else(|| format!("missing `{}`", key))?
+                .as_str()
+                .filter(|word| !word.is_empty())
+                .ok_or_else(|| format!("`{}` must be a non-empty string", key))?;
+            if let Some(other) = words.iter().position(|(other, _)| other == word) {
+                return Err(format!(
+                    "`{}` and `{}` are both `{}`",
+                    KEYS[other].0, KEYS[i].0, word
+                ));
+            }
+            words.push((word.to_string(), op));
+        }
+
+        words.sort_by_key(|(word, _)| std::cmp::Reverse(word.len()));
+        Ok(KeywordSet { words })
+    }
+
+    // the longest keyword at each position wins, a keyword that starts or ends with a letter
+    // or digit only counts when it isn't part of a longer word. `#` starts a comment unless
+    // it begins a keyword, everything else is skipped
+    pub fn lex(&self, source: &str) -> Vec<OpCode> {
+        self.scan(source).0.into_iter().map(|(_, op)| op).collect()
+    }
+
+    // `lexer::debug_prints` for source in this dialect, the position counts the opcodes
+    // lexed before the comment
+    pub fn debug_prints(&self, source: &str) -> Vec<(usize, String)> {
+        let (opcodes, comments) = self.scan(source);
+        comments
+            .into_iter()
+            .filter_map(|(start, end)| {
+                let label = debug_print_label(&source[start..end])?;
+                let position = opcodes.iter().filter(|(at, _)| *at < start).count();
+                Some((position, label))
+            })
+            .collect()
+    }
+
+    // every opcode with the byte it starts at, and the byte range of every comment
+    #[allow(clippy::type_complexity)]
+    fn scan(&self, source: &str) -> (Vec<(usize, OpCode)>, Vec<(usize, usize)>) {
+        let mut opcodes = Vec::new();
+        let mut comments = Vec::new();
+        let mut i = 0;
+
+        while i < source.len() {
+            let rest = &source[i..];
+            let before = source[..i].chars().next_back();
+            let found = self.words.iter().find(|(word, _)| {
+                let Some(after) = rest.strip_prefix(word.as_str()) else {
+                    return false;
+                };
+                let glued_before = joins(before) && word.starts_with(is_word_char);
+                let glued_after = joins(after.chars().next()) && word.ends_with(is_word_char);
+                !glued_before && !glued_after
+            });
+
+            match found {
+                Some((word, op)) => {
+                    opcodes.push((i, *op));
+                    i += word.len();
+                }
+                None if rest.starts_with('#') => {
+                    let end = i + rest.find('\n').unwrap_or(rest.len());
+                    comments.push((i, end));
+                    i = end;
+                }
+                None => i += rest.chars().next().map_or(1, char::len_utf8),
+            }
+        }
+
+        (opcodes, comments)
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn joins(neighbour: Option<char>) -> bool {
+    neighbour.is_some_and(is_word_char)
+}
@@ -0,0 +1,47 @@
+pub mod archive;
+pub mod base64;
+pub mod benchmark;
+pub mod bytecode;
+pub mod cache;
+pub mod cfg;
+pub mod codegen;
+pub mod coverage;
+pub mod dataflow;
+pub mod db;
+pub mod diff;
+pub mod expand;
+pub mod format;
+pub mod gen;
+pub mod hex;
+pub mod interpreter;
+pub mod json;
+pub mod keywords;
+pub mod lint;
+pub mod loops;
+pub mod lsp;
+pub mod macros;
+pub mod mutate;
+pub mod optimize;
+pub mod profiler;
+pub mod program;
+pub mod rng;
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub mod seccomp;
+pub mod serve;
+pub mod session;
+pub mod sha256;
+pub mod shared;
+pub mod simd;
+pub mod span;
+pub mod stats;
+pub mod thread_safe;
+pub mod toml;
+pub mod trace;
+pub mod translate;
+
+// the lexer and parser live in `svolang-core` so the `svo!` macro can share them
+pub use svolang_core::{lexer, parser};
+pub use svolang_macros::svo;
@@ -0,0 +1,184 @@
+use std::fmt;
+
+use crate::lexer::{tokenize, OpCode, Token};
+use crate::loops::{unbounded_loops, LoopAnalyzer, LoopKind};
+use crate::parser::{parse, ParseError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintKind {
+    // a loop at the start of the program or right after another one, its cell is always zero
+    DeadLoop,
+    // `svo svoo`, `svoooooo svooooo` and the like undoing each other
+    CancellingPair,
+    // a clear loop right after another clear loop
+    DoubleClear,
+    // termination can't be shown statically, see `unbounded_loops`
+    UnboundedLoop,
+}
+
+impl LintKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LintKind::DeadLoop => "dead loop",
+            LintKind::CancellingPair => "cancelling pair",
+            LintKind::DoubleClear => "repeated clear loop",
+            LintKind::UnboundedLoop => "loop that may never terminate",
+        }
+    }
+
+    pub fn fixable(&self) -> bool {
+        *self != LintKind::UnboundedLoop
+    }
+}
+
+// `ranges` are the source bytes a fix removes, only the first matters for unfixable ones
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+    pub kind: LintKind,
+    pub ranges: Vec<(usize, usize)>,
+}
+
+impl Lint {
+    pub fn offset(&self) -> usize {
+        self.ranges[0].0
+    }
+
+    pub fn fix_message(&self) -> String {
+        format!(
+            "removed {} at byte offset {}",
+            self.kind.name(),
+            self.offset()
+        )
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte offset {}", self.kind.name(), self.offset())
+    }
+}
+
+pub fn lint(source: &str) -> Result<Vec<Lint>, ParseError> {
+    let tokens: Vec<Token> = tokenize(source)
+        .into_iter()
+        .filter(|token| token.op.is_some())
+        .collect();
+    let ops: Vec<OpCode> = tokens.iter().filter_map(|token| token.op).collect();
+    let instructions = parse(&ops)?;
+
+    // the program parsed, so every bracket has a partner
+    let mut partner = vec![0; ops.len()];
+    let mut open = Vec::new();
+    for (i, op) in ops.iter().enumerate() {
+        match op {
+            OpCode::LoopBegin => open.push(i),
+            OpCode::LoopEnd => {
+                let begin = open.pop().expect("brackets are balanced");
+                partner[begin] = i;
+                partner[i] = begin;
+            }
+            _ => (),
+        }
+    }
+
+    let is_clear = |begin: usize| {
+        parse(&ops[begin + 1..partner[begin]])
+            .is_ok_and(|body| LoopAnalyzer::classify(&body) == LoopKind::ClearCell)
+    };
+
+    let mut lints = Vec::new();
+    let mut dead = Vec::new();
+    // tokens waiting for a partner that undoes them, reset by anything but adds and moves
+    let mut pending: Vec<usize> = Vec::new();
+    let mut i = 0;
+    while i < ops.len() {
+        let op = ops[i];
+        if op == OpCode::LoopBegin && (i == 0 || ops[i - 1] == OpCode::LoopEnd) {
+            let end = partner[i];
+            let kind = if i > 0 && is_clear(partner[i - 1]) && is_clear(i) {
+                LintKind::DoubleClear
+            } else {
+                LintKind::DeadLoop
+            };
+            lints.push(Lint {
+                kind,
+                ranges: vec![(tokens[i].start, tokens[end].end)],
+            });
+            dead.push(tokens[i].start..tokens[end].end);
+            pending.clear();
+            i = end + 1;
+            continue;
+        }
+
+        match pending.last() {
+            Some(&last) if undoes(ops[last], op) => {
+                pending.pop();
+                lints.push(Lint {
+                    kind: LintKind::CancellingPair,
+                    ranges: vec![
+                        (tokens[last].start, tokens[last].end),
+                        (tokens[i].start, tokens[i].end),
+                    ],
+                });
+            }
+            _ if matches!(
+                op,
+                OpCode::LoopBegin | OpCode::LoopEnd | OpCode::Write | OpCode::Read
+            ) =>
+            {
+                pending.clear()
+            }
+            _ => pending.push(i),
+        }
+        i += 1;
+    }
+
+    for position in unbounded_loops(&instructions) {
+        let start = tokens[position].start;
+        if !dead.iter().any(|range| range.contains(&start)) {
+            lints.push(Lint {
+                kind: LintKind::UnboundedLoop,
+                ranges: vec![(start, tokens[partner[position]].end)],
+            });
+        }
+    }
+
+    lints.sort_by_key(Lint::offset);
+    Ok(lints)
+}
+
+// `first` followed by `second` leaves cell and pointer as they were, never true for
+// brackets and i/o
+fn undoes(first: OpCode, second: OpCode) -> bool {
+    matches!(
+        (first, second),
+        (OpCode::Increment, OpCode::Decrement)
+            | (OpCode::Decrement, OpCode::Increment)
+            | (OpCode::IncrementPointer, OpCode::DecrementPointer)
+            | (OpCode::DecrementPointer, OpCode::IncrementPointer)
+    )
+}
+
+// the source with every fixable lint's bytes removed, along with the spaces and tabs that
+// followed them so the remaining words keep their spacing
+pub fn fix(source: &str, lints: &[Lint]) -> String {
+    let mut ranges: Vec<(usize, usize)> = lints
+        .iter()
+        .filter(|lint| lint.kind.fixable())
+        .flat_map(|lint| lint.ranges.iter().copied())
+        .collect();
+    ranges.sort();
+
+    let bytes = source.as_bytes();
+    let mut out = String::with_capacity(source.len());
+    let mut kept = 0;
+    for (start, mut end) in ranges {
+        while end < bytes.len() && matches!(bytes[end], b' ' | b'\t') {
+            end += 1;
+        }
+        out.push_str(&source[kept..start]);
+        kept = end;
+    }
+    out.push_str(&source[kept..]);
+    out
+}
@@ -0,0 +1,277 @@
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+
+use crate::parser::Instruction;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LoopKind {
+    // `[-]`, `[+]` or any other odd step on the current cell
+    ClearCell,
+    // `[->+<]`, moves the current cell into the one `offset` away
+    CopyCell { offset: isize },
+    // `[->+++<]`, adds `factor` times the current cell to the one `offset` away
+    MultiplyAdd { offset: isize, factor: u8 },
+    // `[>]` / `[<<]`, steps `stride` cells at a time in `direction` until it lands on a zero
+    FindZero { direction: isize, stride: usize },
+    // anything else whose counter provably reaches zero
+    Computation,
+    // termination can't be shown statically
+    Unbounded,
+}
+
+// what the body does to the cell the loop tests
+enum Counter {
+    Step(u8),
+    Fixed(u8),
+    Unknown,
+}
+
+pub struct LoopAnalyzer;
+
+impl LoopAnalyzer {
+    // works on both parsed and optimized bodies
+    pub fn classify(body: &[Instruction]) -> LoopKind {
+        let Some((offset, deltas)) = straight_line(body) else {
+            return classify_complex(body);
+        };
+
+        if offset != 0 {
+            return if deltas.values().all(|&delta| delta == 0) {
+                LoopKind::FindZero {
+                    direction: offset.signum(),
+                    stride: offset.unsigned_abs(),
+                }
+            } else {
+                LoopKind::Unbounded
+            };
+        }
+
+        let counter = deltas.get(&0).copied().unwrap_or(0);
+        let targets: Vec<(isize, u8)> = deltas
+            .into_iter()
+            .filter(|&(offset, delta)| offset != 0 && delta != 0)
+            .collect();
+
+        match (counter, targets.as_slice()) {
+            (n, []) if n % 2 == 1 => LoopKind::ClearCell,
+            (u8::MAX, [(offset, 1)]) => LoopKind::CopyCell { offset: *offset },
+            (u8::MAX, [(offset, factor)]) => LoopKind::MultiplyAdd {
+                offset: *offset,
+                factor: *factor,
+            },
+            (n, _) if n % 2 == 1 => LoopKind::Computation,
+            _ => LoopKind::Unbounded,
+        }
+    }
+}
+
+// what one iteration of a loop body does, relative to where the iteration starts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoopMeta {
+    // only adds and moves, no i/o, sets, searches or nested loops
+    pub is_pure: bool,
+    // net pointer movement, 0 for impure bodies
+    pub stride: isize,
+    // non-zero cell deltas by offset, empty for impure bodies
+    pub deltas: Vec<(isize, u8)>,
+    // every pointer position the iteration passes through, its start and end included
+    pub reach: RangeInclusive<isize>,
+}
+
+impl LoopMeta {
+    pub fn of(body: &[Instruction]) -> LoopMeta {
+        let mut offset = 0;
+        let mut reach = 0..=0;
+        let mut deltas = BTreeMap::new();
+
+        for instr in body {
+            if let Some(by) = pointer_step(instr) {
+                offset += by;
+                reach = *reach.start().min(&offset)..=*reach.end().max(&offset);
+            } else if let Some(delta) = cell_step(instr) {
+                let cell: &mut u8 = deltas.entry(offset).or_default();
+                *cell = cell.wrapping_add(delta);
+            } else {
+                return LoopMeta {
+                    is_pure: false,
+                    stride: 0,
+                    deltas: Vec::new(),
+                    reach: 0..=0,
+                };
+            }
+        }
+
+        LoopMeta {
+            is_pure: true,
+            stride: offset,
+            deltas: deltas
+                .into_iter()
+                .filter(|&(_, delta)| delta != 0)
+                .collect(),
+            reach,
+        }
+    }
+
+    // net pointer movement of a pure body, `None` otherwise, without building the deltas
+    pub fn stride_of(body: &[Instruction]) -> Option<isize> {
+        body.iter()
+            .try_fold(0, |offset, instr| match pointer_step(instr) {
+                Some(by) => Some(offset + by),
+                None => cell_step(instr).map(|_| offset),
+            })
+    }
+
+    // cells the iteration reads or writes, the tested cell at offset 0 included
+    pub fn window(&self) -> RangeInclusive<isize> {
+        self.deltas.iter().fold(0..=0, |window, &(offset, _)| {
+            *window.start().min(&offset)..=*window.end().max(&offset)
+        })
+    }
+
+    // consecutive iterations touch disjoint cells and none writes a cell a later one
+    // tests, so the iterations can run in any order or all at once
+    pub fn iterations_independent(&self) -> bool {
+        let window = self.window();
+        self.is_pure && (window.end() - window.start()).unsigned_abs() < self.stride.unsigned_abs()
+    }
+}
+
+// net pointer offset and per-cell deltas of a body made only of adds and moves
+fn straight_line(body: &[Instruction]) -> Option<(isize, BTreeMap<isize, u8>)> {
+    let mut offset = 0;
+    let mut deltas = BTreeMap::new();
+
+    for instr in body {
+        if let Some(by) = pointer_step(instr) {
+            offset += by;
+        } else if let Some(delta) = cell_step(instr) {
+            let cell: &mut u8 = deltas.entry(offset).or_default();
+            *cell = cell.wrapping_add(delta);
+        } else {
+            return None;
+        }
+    }
+
+    Some((offset, deltas))
+}
+
+fn pointer_step(instr: &Instruction) -> Option<isize> {
+    match instr {
+        Instruction::IncrementPointer => Some(1),
+        Instruction::DecrementPointer => Some(-1),
+        Instruction::Move(by) => Some(*by),
+        _ => None,
+    }
+}
+
+fn cell_step(instr: &Instruction) -> Option<u8> {
+    match instr {
+        Instruction::Increment => Some(1),
+        Instruction::Decrement => Some(u8::MAX),
+        Instruction::Add(n) => Some(*n),
+        _ => None,
+    }
+}
+
+// bodies with i/o, sets or nested loops only terminate when the pointer ends where it
+// started, every nested loop terminates and the counter provably reaches zero
+fn classify_complex(body: &[Instruction]) -> LoopKind {
+    let mut offset = 0;
+    let mut counter = Counter::Step(0);
+
+    for instr in body {
+        if let Some(by) = pointer_step(instr) {
+            offset += by;
+            continue;
+        }
+
+        // a search, fused or not, leaves the pointer an unknown distance away
+        if let Instruction::FindZero { .. } = instr {
+            return LoopKind::Unbounded;
+        }
+
+        if let Instruction::Loop(inner) = instr {
+            counter = match (LoopAnalyzer::classify(inner), offset) {
+                (LoopKind::Unbounded | LoopKind::FindZero { .. }, _) => return LoopKind::Unbounded,
+                // every other kind ends where it started, on a zero cell
+                (_, 0) => Counter::Fixed(0),
+                (LoopKind::ClearCell, _) => counter,
+                (
+                    LoopKind::CopyCell { offset: to } | LoopKind::MultiplyAdd { offset: to, .. },
+                    _,
+                ) if offset + to != 0 => counter,
+                _ => Counter::Unknown,
+            };
+            continue;
+        }
+
+        // clears the cell it's on and adds to the one `to` away
+        if let Instruction::MultiplyAdd { offset: to, .. } = instr {
+            counter = match offset {
+                0 => Counter::Fixed(0),
+                _ if offset + to == 0 => Counter::Unknown,
+                _ => counter,
+            };
+            continue;
+        }
+
+        if let Instruction::BulkZero { start_offset, len } = instr {
+            if (*start_offset..*start_offset + *len as isize).contains(&-offset) {
+                counter = Counter::Fixed(0);
+            }
+            continue;
+        }
+
+        // built-in macros and the range copies they lower to leave the pointer where it was
+        // but may write any cell near it
+        if let Instruction::MacroCall { .. } | Instruction::CopyRange { .. } = instr {
+            counter = Counter::Unknown;
+            continue;
+        }
+
+        if offset != 0 {
+            continue;
+        }
+        counter = match (instr, counter) {
+            (Instruction::SetCell(value), _) => Counter::Fixed(*value),
+            (Instruction::Read, _) => Counter::Unknown,
+            (instr, Counter::Step(step)) => {
+                Counter::Step(step.wrapping_add(cell_step(instr).unwrap_or(0)))
+            }
+            (instr, Counter::Fixed(value)) => {
+                Counter::Fixed(value.wrapping_add(cell_step(instr).unwrap_or(0)))
+            }
+            (_, Counter::Unknown) => Counter::Unknown,
+        };
+    }
+
+    match counter {
+        _ if offset != 0 => LoopKind::Unbounded,
+        Counter::Step(step) if step % 2 == 1 => LoopKind::Computation,
+        Counter::Fixed(0) => LoopKind::Computation,
+        _ => LoopKind::Unbounded,
+    }
+}
+
+// opcode positions of innermost loops the analyzer can't prove terminate, outer loops
+// are left out since they're routinely driven by flags their nested loops clear
+pub fn unbounded_loops(instructions: &[Instruction]) -> Vec<usize> {
+    let mut positions = Vec::new();
+    collect_unbounded(instructions, 0, &mut positions);
+    positions
+}
+
+fn collect_unbounded(instructions: &[Instruction], mut position: usize, out: &mut Vec<usize>) {
+    for instr in instructions {
+        if let Instruction::Loop(body) = instr {
+            let innermost = !body
+                .iter()
+                .any(|instr| matches!(instr, Instruction::Loop(_)));
+            if innermost && LoopAnalyzer::classify(body) == LoopKind::Unbounded {
+                out.push(position);
+            }
+            collect_unbounded(body, position + 1, out);
+        }
+        position += instr.opcode_len();
+    }
+}
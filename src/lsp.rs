@@ -0,0 +1,352 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use crate::json::Value;
+use crate::lexer::{tokenize, OpCode, Token};
+use crate::parser::ParseError;
+
+const SEVERITY_ERROR: usize = 1;
+const SEVERITY_WARNING: usize = 2;
+const SYMBOL_KIND_FUNCTION: usize = 12;
+const METHOD_NOT_FOUND: f64 = -32601.0;
+
+// line starts of a document, converts byte offsets to LSP positions (UTF-16 columns) and back
+struct LineIndex<'a> {
+    text: &'a str,
+    starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    fn new(text: &'a str) -> Self {
+        let mut starts = vec![0];
+        starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+        LineIndex { text, starts }
+    }
+
+    fn position(&self, offset: usize) -> Value {
+        let line = self.starts.partition_point(|&start| start <= offset) - 1;
+        let character: usize = self.text[self.starts[line]..offset]
+            .chars()
+            .map(char::len_utf16)
+            .sum();
+        Value::object([("line", line.into()), ("character", character.into())])
+    }
+
+    fn range(&self, start: usize, end: usize) -> Value {
+        Value::object([("start", self.position(start)), ("end", self.position(end))])
+    }
+
+    fn offset(&self, line: usize, character: usize) -> usize {
+        let Some(&start) = self.starts.get(line) else {
+            return self.text.len();
+        };
+        let mut units = 0;
+        for (i, c) in self.text[start..].char_indices() {
+            if units >= character || c == '\n' {
+                return start + i;
+            }
+            units += c.len_utf16();
+        }
+        self.text.len()
+    }
+}
+
+fn describe(op: OpCode) -> &'static str {
+    match op {
+        OpCode::Increment => "add 1 to the current cell",
+        OpCode::Decrement => "subtract 1 from the current cell",
+        OpCode::LoopBegin => "skip past the matching `svoooo` when the current cell is zero",
+        OpCode::LoopEnd => "jump back to the matching `svooo` when the current cell is not zero",
+        OpCode::DecrementPointer => "move the data pointer one cell left",
+        OpCode::IncrementPointer => "move the data pointer one cell right",
+        OpCode::Write => "write the current cell to stdout",
+        OpCode::Read => "read one byte from stdin into the current cell",
+    }
+}
+
+fn diagnostic(index: &LineIndex, token: &Token, severity: usize, message: String) -> Value {
+    Value::object([
+        ("range", index.range(token.start, token.end)),
+        ("severity", severity.into()),
+        ("source", "svolang".into()),
+        ("message", message.into()),
+    ])
+}
+
+pub fn diagnostics(text: &str) -> Vec<Value> {
+    let index = LineIndex::new(text);
+    let tokens = tokenize(text);
+    let mut found = Vec::new();
+    let mut open: Vec<(usize, &Token)> = Vec::new();
+    let mut position = 0;
+
+    for token in &tokens {
+        let Some(op) = token.op else {
+            found.push(diagnostic(
+                &index,
+                token,
+                SEVERITY_WARNING,
+                format!(
+                    "`{}` is not an opcode and is ignored",
+                    &text[token.start..token.end]
+                ),
+            ));
+            continue;
+        };
+
+        match op {
+            OpCode::LoopBegin => open.push((position, token)),
+            OpCode::LoopEnd if open.pop().is_none() => {
+                let err = ParseError::UnmatchedLoopEnd { position };
+                found.push(diagnostic(&index, token, SEVERITY_ERROR, err.to_string()));
+            }
+            _ => (),
+        }
+
+        position += 1;
+    }
+
+    for (position, token) in open {
+        let err = ParseError::UnmatchedLoopBegin { position };
+        found.push(diagnostic(&index, token, SEVERITY_ERROR, err.to_string()));
+    }
+
+    found
+}
+
+pub fn hover(text: &str, line: usize, character: usize) -> Option<Value> {
+    let index = LineIndex::new(text);
+    let offset = index.offset(line, character);
+    let token = tokenize(text)
+        .into_iter()
+        .find(|token| (token.start..token.end).contains(&offset))?;
+    let word = &text[token.start..token.end];
+
+    let contents = match token.op {
+        Some(op) => format!("**{}** — {:?}\n\n{}", word, op, describe(op)),
+        None => format!("**{}** — not an opcode, ignored", word),
+    };
+
+    Some(Value::object([
+        (
+            "contents",
+            Value::object([("kind", "markdown".into()), ("value", contents.into())]),
+        ),
+        ("range", index.range(token.start, token.end)),
+    ]))
+}
+
+// every matched loop as a symbol, nested the same way the loops are
+pub fn document_symbols(text: &str) -> Vec<Value> {
+    let index = LineIndex::new(text);
+    let mut stack: Vec<(usize, Token, Vec<Value>)> = Vec::new();
+    let mut roots = Vec::new();
+    let mut position = 0;
+
+    for token in tokenize(text) {
+        let Some(op) = token.op else {
+            continue;
+        };
+
+        match op {
+            OpCode::LoopBegin => stack.push((position, token, Vec::new())),
+            OpCode::LoopEnd => {
+                if let Some((start_position, begin, children)) = stack.pop() {
+                    let symbol = Value::object([
+                        ("name", format!("loop #{}", start_position).into()),
+                        ("kind", SYMBOL_KIND_FUNCTION.into()),
+                        ("range", index.range(begin.start, token.end)),
+                        ("selectionRange", index.range(begin.start, begin.end)),
+                        ("children", Value::Array(children)),
+                    ]);
+                    match stack.last_mut() {
+                        Some((_, _, siblings)) => siblings.push(symbol),
+                        None => roots.push(symbol),
+                    }
+                }
+            }
+            _ => (),
+        }
+
+        position += 1;
+    }
+
+    roots
+}
+
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().ok();
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "message without Content-Length")
+    })?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "message is not UTF-8"))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+fn text_document_uri(params: Option<&Value>) -> Option<String> {
+    params?
+        .get("textDocument")?
+        .get("uri")?
+        .as_str()
+        .map(str::to_string)
+}
+
+// speaks JSON-RPC until `exit`, returns whether `shutdown` was requested first
+pub fn serve(mut input: impl BufRead, mut output: impl Write) -> io::Result<bool> {
+    let mut documents: HashMap<String, String> = HashMap::new();
+    let mut shut_down = false;
+
+    while let Some(body) = read_message(&mut input)? {
+        let Ok(message) = Value::parse(&body) else {
+            continue;
+        };
+        let method = message.get("method").and_then(Value::as_str).unwrap_or("");
+        let params = message.get("params");
+        let id = message.get("id").cloned();
+
+        let mut publish = None;
+        let result = match method {
+            "initialize" => Some(Value::object([
+                (
+                    "capabilities",
+                    Value::object([
+                        ("textDocumentSync", 1usize.into()),
+                        ("hoverProvider", true.into()),
+                        ("documentSymbolProvider", true.into()),
+                    ]),
+                ),
+                (
+                    "serverInfo",
+                    Value::object([
+                        ("name", "svolang".into()),
+                        ("version", env!("CARGO_PKG_VERSION").into()),
+                    ]),
+                ),
+            ])),
+            "shutdown" => {
+                shut_down = true;
+                Some(Value::Null)
+            }
+            "exit" => return Ok(shut_down),
+            "textDocument/didOpen" => {
+                let document = params.and_then(|p| p.get("textDocument"));
+                let uri = text_document_uri(params);
+                let text = document.and_then(|d| d.get("text")).and_then(Value::as_str);
+                if let (Some(uri), Some(text)) = (uri, text) {
+                    documents.insert(uri.clone(), text.to_string());
+                    publish = Some(uri);
+                }
+                None
+            }
+            "textDocument/didChange" => {
+                let uri = text_document_uri(params);
+                let text = params
+                    .and_then(|p| p.get("contentChanges"))
+                    .and_then(Value::as_array)
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change.get("text"))
+                    .and_then(Value::as_str);
+                if let (Some(uri), Some(text)) = (uri, text) {
+                    documents.insert(uri.clone(), text.to_string());
+                    publish = Some(uri);
+                }
+                None
+            }
+            "textDocument/didClose" => {
+                if let Some(uri) = text_document_uri(params) {
+                    documents.remove(&uri);
+                    write_message(&mut output, &publish_diagnostics(&uri, Vec::new()))?;
+                }
+                None
+            }
+            "textDocument/hover" => {
+                let text = text_document_uri(params).and_then(|uri| documents.get(&uri));
+                let position = params.and_then(|p| p.get("position"));
+                let line = position.and_then(|p| p.get("line")).and_then(Value::as_u64);
+                let character = position
+                    .and_then(|p| p.get("character"))
+                    .and_then(Value::as_u64);
+                match (text, line, character) {
+                    (Some(text), Some(line), Some(character)) => {
+                        Some(hover(text, line as usize, character as usize).unwrap_or(Value::Null))
+                    }
+                    _ => Some(Value::Null),
+                }
+            }
+            "textDocument/documentSymbol" => {
+                let text = text_document_uri(params).and_then(|uri| documents.get(&uri));
+                Some(Value::Array(
+                    text.map(|t| document_symbols(t)).unwrap_or_default(),
+                ))
+            }
+            _ => None,
+        };
+
+        if let Some(id) = id {
+            let response = match result {
+                Some(result) => {
+                    Value::object([("jsonrpc", "2.0".into()), ("id", id), ("result", result)])
+                }
+                None => Value::object([
+                    ("jsonrpc", "2.0".into()),
+                    ("id", id),
+                    (
+                        "error",
+                        Value::object([
+                            ("code", METHOD_NOT_FOUND.into()),
+                            ("message", format!("method not found: {}", method).into()),
+                        ]),
+                    ),
+                ]),
+            };
+            write_message(&mut output, &response)?;
+        }
+
+        if let Some(uri) = publish {
+            let found = diagnostics(&documents[&uri]);
+            write_message(&mut output, &publish_diagnostics(&uri, found))?;
+        }
+    }
+
+    Ok(shut_down)
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    Value::object([
+        ("jsonrpc", "2.0".into()),
+        ("method", "textDocument/publishDiagnostics".into()),
+        (
+            "params",
+            Value::object([
+                ("uri", uri.into()),
+                ("diagnostics", Value::Array(diagnostics)),
+            ]),
+        ),
+    ])
+}
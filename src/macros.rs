@@ -0,0 +1,75 @@
+// `svo_test!` and `svo_test_err!` expand to a `#[test]` that parses a program and runs it
+// against fixed input, `source =` takes the program text and `path =` a file that is
+// embedded with `include_str!` relative to the calling file:
+//
+//     svo_test!(hello, path = "../examples/helloworld.svo", expected_output = b"Hello World!\n");
+//     svo_test_err!(eof, source = "svoooooooo", error = RuntimeError::Io(_));
+
+#[macro_export]
+macro_rules! svo_test {
+    ($name:ident, path = $path:literal $(, input = $input:expr)?, expected_output = $expected:expr $(,)?) => {
+        $crate::svo_test!($name, source = include_str!($path) $(, input = $input)?, expected_output = $expected);
+    };
+    ($name:ident, source = $source:expr, expected_output = $expected:expr $(,)?) => {
+        $crate::svo_test!($name, source = $source, input = b"", expected_output = $expected);
+    };
+    ($name:ident, source = $source:expr, input = $input:expr, expected_output = $expected:expr $(,)?) => {
+        #[test]
+        fn $name() {
+            let output = $crate::macros::run_test_program($source, $input)
+                .unwrap_or_else(|err| panic!("{}", err));
+            let expected: &[u8] = $expected;
+            assert_eq!(
+                output,
+                expected,
+                "output {:?} differs from expected {:?}",
+                String::from_utf8_lossy(&output),
+                String::from_utf8_lossy(expected)
+            );
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! svo_test_err {
+    ($name:ident, path = $path:literal $(, input = $input:expr)?, error = $error:pat $(,)?) => {
+        $crate::svo_test_err!($name, source = include_str!($path) $(, input = $input)?, error = $error);
+    };
+    ($name:ident, source = $source:expr, error = $error:pat $(,)?) => {
+        $crate::svo_test_err!($name, source = $source, input = b"", error = $error);
+    };
+    ($name:ident, source = $source:expr, input = $input:expr, error = $error:pat $(,)?) => {
+        #[test]
+        fn $name() {
+            let program = $crate::program::Program::from_source($source)
+                .unwrap_or_else(|err| panic!("program does not parse: {}", err));
+            let result = $crate::interpreter::run_with_input(
+                &program.instructions,
+                &$crate::interpreter::InterpreterConfig::default(),
+                $input,
+            );
+            match result {
+                Err($error) => (),
+                Err(err) => panic!("expected {}, got `{}`", stringify!($error), err),
+                Ok(output) => panic!(
+                    "expected {}, program finished with output {:?}",
+                    stringify!($error),
+                    String::from_utf8_lossy(&output)
+                ),
+            }
+        }
+    };
+}
+
+// shared by the expansions of `svo_test!`, not meant to be called directly
+#[doc(hidden)]
+pub fn run_test_program(source: &str, input: &[u8]) -> Result<Vec<u8>, String> {
+    let program = crate::program::Program::from_source(source)
+        .map_err(|err| format!("program does not parse: {}", err))?;
+    crate::interpreter::run_with_input(
+        &program.instructions,
+        &crate::interpreter::InterpreterConfig::default(),
+        input,
+    )
+    .map_err(|err| format!("runtime error: {}", err))
+}
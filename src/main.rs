@@ -1,198 +1,3562 @@
+use std::cell::RefCell;
 use std::env;
-use std::fs::File;
-use std::io::{Read, Write};
-
-#[derive(Debug, Clone)]
-enum OpCode {
-    IncrementPointer,
-    DecrementPointer,
-    Increment,
-    Decrement,
-    Write,
-    Read,
-    LoopBegin,
-    LoopEnd,
-}
-
-#[derive(Debug, Clone)]
-enum Instruction {
-    IncrementPointer,
-    DecrementPointer,
-    Increment,
-    Decrement,
-    Write,
-    Read,
-    Loop(Vec<Instruction>),
-}
-
-fn lex(source: String) -> Vec<OpCode> {
-    let mut operations = Vec::new();
-    let chars: Vec<char> = source.chars().collect();
-    let mut i = 0;
-
-    while i < chars.len() {
-        if i + 1 < chars.len() && chars[i] == 's' && chars[i + 1] == 'v' {
-            let mut o_count = 0;
-            let mut j = i + 2;
-
-            while j < chars.len() && chars[j] == 'o' {
-                o_count += 1;
-                j += 1;
-            }
-
-            let op = match o_count {
-                1 => Some(OpCode::Increment),        // svo
-                2 => Some(OpCode::Decrement),        // svoo
-                3 => Some(OpCode::LoopBegin),        // svooo
-                4 => Some(OpCode::LoopEnd),          // svoooo
-                5 => Some(OpCode::DecrementPointer), // svooooo
-                6 => Some(OpCode::IncrementPointer), // svoooooo
-                7 => Some(OpCode::Write),            // svooooooo
-                8 => Some(OpCode::Read),             // svoooooooo
-                _ => None,
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Cursor, IsTerminal, Read, Write};
+use std::net::TcpListener;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use svolang::archive;
+use svolang::benchmark::{BenchmarkReport, BenchmarkResult, REGRESSION_THRESHOLD, SUITE};
+use svolang::bytecode::{ByteCodeProgram, ByteCodeVM};
+use svolang::codegen;
+use svolang::coverage;
+use svolang::diff::DiffEntry;
+use svolang::expand;
+use svolang::format;
+use svolang::gen::Generator;
+use svolang::hex;
+use svolang::interpreter::{
+    run_with_input, CapturedOutput, Interpreter, InterpreterConfig, IoMode, RuntimeError, Tape,
+    TapeInit, WriteMode,
+};
+use svolang::json::Value;
+use svolang::keywords::KeywordSet;
+use svolang::lexer::{debug_prints, lex, tokenize, OpCode};
+use svolang::lint;
+use svolang::loops::unbounded_loops;
+use svolang::lsp;
+use svolang::mutate;
+use svolang::optimize::{self, Optimizer, OptimizerPipeline};
+use svolang::parser::{
+    parse, parse_with_debug_prints, parse_with_progress, Instruction, ParseError,
+};
+use svolang::profiler::Profiler;
+use svolang::program::Program;
+use svolang::rng::Rng;
+use svolang::serve;
+use svolang::session::{Recorder, Session};
+use svolang::sha256;
+use svolang::simd;
+use svolang::stats::{count_instructions, OpcodeStats, ProgramStats};
+use svolang::thread_safe::{SharedCapture, ThreadSafeInterpreter};
+use svolang::trace::{ReplayVerifier, Trace, TraceWriter};
+use svolang::translate::{translate, translate_bf_with_comments, validate_bf, Dialect};
+use tracing::level_filters::LevelFilter;
+use tracing::{error, warn};
+
+struct Flag {
+    name: &'static str,
+    // placeholder shown in usage, `None` for boolean switches. `a> <b` takes two values
+    value: Option<&'static str>,
+}
+
+impl Flag {
+    // one placeholder per value the flag takes
+    fn placeholders(&self) -> Vec<&'static str> {
+        self.value
+            .map_or(Vec::new(), |value| value.split("> <").collect())
+    }
+}
+
+struct Command {
+    name: &'static str,
+    args: &'static str,
+    flags: &'static [Flag],
+    handler: fn(&Matches) -> Result<(), CliError>,
+}
+
+const COMMANDS: &[Command] = &[
+    Command {
+        name: "run",
+        args: "<file.svo>",
+        flags: &[
+            Flag {
+                name: "trace",
+                value: Some("file"),
+            },
+            Flag {
+                name: "trace-timing",
+                value: None,
+            },
+            Flag {
+                name: "check-output",
+                value: Some("expected-file"),
+            },
+            Flag {
+                name: "diff-output",
+                value: Some("expected-file"),
+            },
+            Flag {
+                name: "pre-fill-tape",
+                value: Some("hex"),
+            },
+            Flag {
+                name: "tape-init",
+                value: Some("pattern"),
+            },
+            Flag {
+                name: "shared-tape",
+                value: Some("file"),
+            },
+            Flag {
+                name: "parallel-loops",
+                value: None,
+            },
+            Flag {
+                name: "debug-print",
+                value: None,
+            },
+            Flag {
+                name: "dialect-file",
+                value: Some("file.toml"),
+            },
+            Flag {
+                name: "cell-value-trace",
+                value: Some("cell-index"),
+            },
+            Flag {
+                name: "pipe",
+                value: None,
+            },
+            Flag {
+                name: "tape-size",
+                value: Some("cells"),
+            },
+            Flag {
+                name: "start-pointer",
+                value: Some("cell"),
+            },
+            Flag {
+                name: "randomize-start-pointer",
+                value: Some("seed|random"),
+            },
+            Flag {
+                name: "max-steps",
+                value: Some("n"),
+            },
+            Flag {
+                name: "timeout",
+                value: Some("seconds"),
+            },
+            Flag {
+                name: "max-loop-iterations",
+                value: Some("n"),
+            },
+            Flag {
+                name: "instruction-limit-per-loop",
+                value: Some("n"),
+            },
+            Flag {
+                name: "max-output-bytes",
+                value: Some("n"),
+            },
+            Flag {
+                name: "output-buffer-size",
+                value: Some("bytes"),
+            },
+            Flag {
+                name: "write-mode",
+                value: Some("mode"),
+            },
+            Flag {
+                name: "sandboxed",
+                value: None,
+            },
+            Flag {
+                name: "seccomp",
+                value: None,
+            },
+            Flag {
+                name: "coverage",
+                value: None,
+            },
+            Flag {
+                name: "coverage-output",
+                value: Some("file"),
+            },
+            Flag {
+                name: "record",
+                value: Some("session-file"),
+            },
+            Flag {
+                name: "replay",
+                value: Some("session-file"),
+            },
+            Flag {
+                name: "profile",
+                value: None,
+            },
+            Flag {
+                name: "profile-output",
+                value: Some("json|csv> <file"),
+            },
+            Flag {
+                name: "optimize",
+                value: None,
+            },
+            Flag {
+                name: "opt-level",
+                value: Some("0-3"),
+            },
+            Flag {
+                name: "statistics",
+                value: None,
+            },
+            Flag {
+                name: "bytecode",
+                value: None,
+            },
+            Flag {
+                name: "tape-dump",
+                value: None,
+            },
+            Flag {
+                name: "ascii-art",
+                value: None,
+            },
+            Flag {
+                name: "benchmark-iterations",
+                value: Some("n"),
+            },
+            Flag {
+                name: "verbose-parse",
+                value: None,
+            },
+        ],
+        handler: cmd_run,
+    },
+    Command {
+        name: "benchmark",
+        args: "",
+        flags: &[
+            Flag {
+                name: "iterations",
+                value: Some("n"),
+            },
+            Flag {
+                name: "json",
+                value: None,
+            },
+            Flag {
+                name: "compare",
+                value: Some("previous-results.json"),
+            },
+        ],
+        handler: cmd_benchmark,
+    },
+    Command {
+        name: "translate",
+        args: "<file.bf> <file.svo>",
+        flags: &[
+            Flag {
+                name: "dialect",
+                value: Some("bf|ook|whitespace"),
+            },
+            Flag {
+                name: "validate",
+                value: None,
+            },
+            Flag {
+                name: "interleave-comments",
+                value: None,
+            },
+        ],
+        handler: cmd_translate,
+    },
+    Command {
+        name: "replay",
+        args: "<trace-file>",
+        flags: &[Flag {
+            name: "program",
+            value: Some("file.svo"),
+        }],
+        handler: cmd_replay,
+    },
+    Command {
+        name: "trace-diff",
+        args: "<trace1> <trace2>",
+        flags: &[],
+        handler: cmd_trace_diff,
+    },
+    Command {
+        name: "compile",
+        args: "<file.svo>",
+        flags: &[
+            Flag {
+                name: "target",
+                value: Some("native|c"),
+            },
+            Flag {
+                name: "o",
+                value: Some("output"),
+            },
+        ],
+        handler: cmd_compile,
+    },
+    Command {
+        name: "pack",
+        args: "<file.svo>",
+        flags: &[
+            Flag {
+                name: "o",
+                value: Some("binary"),
+            },
+            Flag {
+                name: "no-io",
+                value: None,
+            },
+        ],
+        handler: cmd_pack,
+    },
+    Command {
+        name: "stats",
+        args: "<file.svo>",
+        flags: &[
+            Flag {
+                name: "json",
+                value: None,
+            },
+            Flag {
+                name: "opcode-stats",
+                value: None,
+            },
+        ],
+        handler: cmd_stats,
+    },
+    Command {
+        name: "mutate",
+        args: "<file.svo>",
+        flags: &[
+            Flag {
+                name: "expected-output",
+                value: Some("file"),
+            },
+            Flag {
+                name: "input",
+                value: Some("file"),
+            },
+            Flag {
+                name: "max-steps",
+                value: Some("n"),
+            },
+        ],
+        handler: cmd_mutate,
+    },
+    Command {
+        name: "coverage",
+        args: "<file.svo>",
+        flags: &[
+            Flag {
+                name: "input",
+                value: Some("file"),
+            },
+            Flag {
+                name: "o",
+                value: Some("file.info"),
+            },
+            Flag {
+                name: "max-steps",
+                value: Some("n"),
+            },
+        ],
+        handler: cmd_coverage,
+    },
+    Command {
+        name: "lsp",
+        args: "",
+        flags: &[],
+        handler: cmd_lsp,
+    },
+    Command {
+        name: "diff",
+        args: "<file1.svo> <file2.svo>",
+        flags: &[],
+        handler: cmd_diff,
+    },
+    Command {
+        name: "ast-diff",
+        args: "<file1.svo> <file2.svo>",
+        flags: &[],
+        handler: cmd_ast_diff,
+    },
+    Command {
+        name: "dump-cfg",
+        args: "<file.svo>",
+        flags: &[
+            Flag {
+                name: "optimize",
+                value: None,
+            },
+            Flag {
+                name: "opt-level",
+                value: Some("0-3"),
+            },
+        ],
+        handler: cmd_dump_cfg,
+    },
+    Command {
+        name: "batch",
+        args: "<file.svo>...",
+        flags: &[
+            Flag {
+                name: "parallel",
+                value: Some("n"),
+            },
+            Flag {
+                name: "stream",
+                value: None,
+            },
+            Flag {
+                name: "tape-size",
+                value: Some("cells"),
+            },
+            Flag {
+                name: "max-steps",
+                value: Some("n"),
+            },
+            Flag {
+                name: "min-opcode-count",
+                value: Some("n"),
+            },
+            Flag {
+                name: "max-opcode-count",
+                value: Some("n"),
+            },
+            Flag {
+                name: "verbose",
+                value: None,
+            },
+        ],
+        handler: cmd_batch,
+    },
+    Command {
+        name: "checksum",
+        args: "<file.svo>",
+        flags: &[
+            Flag {
+                name: "embed",
+                value: None,
+            },
+            Flag {
+                name: "verify",
+                value: None,
+            },
+        ],
+        handler: cmd_checksum,
+    },
+    Command {
+        name: "minify",
+        args: "<file.svo>",
+        flags: &[Flag {
+            name: "separator",
+            value: Some("text"),
+        }],
+        handler: cmd_minify,
+    },
+    Command {
+        name: "expand",
+        args: "<file.svo>",
+        flags: &[Flag {
+            name: "o",
+            value: Some("file.svo"),
+        }],
+        handler: cmd_expand,
+    },
+    Command {
+        name: "lint",
+        args: "<file.svo>",
+        flags: &[Flag {
+            name: "fix",
+            value: None,
+        }],
+        handler: cmd_lint,
+    },
+    Command {
+        name: "format-check",
+        args: "<file.svo>...",
+        flags: &[],
+        handler: cmd_format_check,
+    },
+    Command {
+        name: "init",
+        args: "<project-name>",
+        flags: &[],
+        handler: cmd_init,
+    },
+    Command {
+        name: "doctor",
+        args: "",
+        flags: &[],
+        handler: cmd_doctor,
+    },
+    Command {
+        name: "repl",
+        args: "",
+        flags: &[
+            Flag {
+                name: "history-file",
+                value: Some("file"),
+            },
+            Flag {
+                name: "tape-size",
+                value: Some("cells"),
+            },
+        ],
+        handler: cmd_repl,
+    },
+    Command {
+        name: "serve",
+        args: "",
+        flags: &[
+            Flag {
+                name: "port",
+                value: Some("n"),
+            },
+            Flag {
+                name: "tape-size",
+                value: Some("cells"),
+            },
+            Flag {
+                name: "max-steps",
+                value: Some("n"),
+            },
+            Flag {
+                name: "timeout",
+                value: Some("seconds"),
+            },
+            Flag {
+                name: "max-output-bytes",
+                value: Some("n"),
+            },
+            Flag {
+                name: "sandboxed",
+                value: None,
+            },
+        ],
+        handler: cmd_serve,
+    },
+    Command {
+        name: "install-completions",
+        args: "<bash|zsh|fish|powershell>",
+        flags: &[],
+        handler: cmd_install_completions,
+    },
+];
+
+struct Matches {
+    positional: Vec<String>,
+    values: Vec<(&'static str, Vec<String>)>,
+    switches: Vec<&'static str>,
+    // the subcommand's name when --machine-readable was given
+    machine_readable: Option<&'static str>,
+}
+
+impl Matches {
+    fn parse(args: &[String], flags: &'static [Flag]) -> Result<Matches, String> {
+        let mut matches = Matches {
+            positional: Vec::new(),
+            values: Vec::new(),
+            switches: Vec::new(),
+            machine_readable: None,
+        };
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let name = match arg.strip_prefix("--") {
+                Some(name) => name,
+                None if arg.len() == 2 && arg.starts_with('-') => &arg[1..],
+                None => {
+                    matches.positional.push(arg.clone());
+                    continue;
+                }
+            };
+            let (name, inline_value) = match name.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (name, None),
             };
+            let flag = flags
+                .iter()
+                .find(|flag| flag.name == name)
+                .ok_or_else(|| format!("unknown flag `{}`", arg))?;
+
+            if flag.value.is_some() {
+                let placeholders = flag.placeholders();
+                let mut values: Vec<String> = inline_value.into_iter().collect();
+                while values.len() < placeholders.len() {
+                    let value = args
+                        .next()
+                        .cloned()
+                        .ok_or_else(|| match placeholders.len() {
+                            1 => format!("flag `{}` expects a value", arg),
+                            n => format!("flag `{}` expects {} values", arg, n),
+                        })?;
+                    values.push(value);
+                }
+                matches.values.push((flag.name, values));
+            } else {
+                matches.switches.push(flag.name);
+            }
+        }
+
+        Ok(matches)
+    }
+
+    fn arg(&self, index: usize, name: &str) -> Result<&str, String> {
+        self.positional
+            .get(index)
+            .map(String::as_str)
+            .ok_or_else(|| format!("missing argument {}", name))
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.switches.contains(&name)
+    }
+
+    fn value(&self, name: &str) -> Option<&str> {
+        self.values(name).map(|values| values[0].as_str())
+    }
+
+    // all the values of a flag that takes more than one, from its last occurrence
+    fn values(&self, name: &str) -> Option<&[String]> {
+        self.values
+            .iter()
+            .rev()
+            .find(|(flag, _)| *flag == name)
+            .map(|(_, values)| values.as_slice())
+    }
+
+    // every value of a flag that may be given more than once, in command line order
+    fn all_values(&self, name: &str) -> Vec<&str> {
+        self.values
+            .iter()
+            .filter(|(flag, _)| *flag == name)
+            .map(|(_, values)| values[0].as_str())
+            .collect()
+    }
+
+    fn machine_readable(&self) -> bool {
+        self.machine_readable.is_some()
+    }
+
+    fn message(&self, level: Level, message: &str, data: Value) -> Value {
+        machine_message(self.machine_readable, level, message, data)
+    }
+
+    // one line of NDJSON on stdout, only meant for --machine-readable
+    fn report(&self, level: Level, message: &str, data: Value) {
+        println!("{}", self.message(level, message, data));
+    }
+
+    // a warning logged as usual, or reported when output is machine readable
+    fn warn(&self, message: &str, data: Value) {
+        if self.machine_readable() {
+            self.report(Level::Warn, message, data);
+        } else {
+            warn!("{}", message);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    fn name(&self) -> &'static str {
+        match self {
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        }
+    }
+}
+
+// `{"level": ..., "subcommand": ..., "message": ..., "data": {...}}`, what every message
+// becomes with --machine-readable
+fn machine_message(subcommand: Option<&str>, level: Level, message: &str, data: Value) -> Value {
+    Value::object([
+        ("level", level.name().into()),
+        ("subcommand", subcommand.into()),
+        ("message", message.into()),
+        ("data", data),
+    ])
+}
+
+fn usage() -> String {
+    let mut usage = String::from("usage: svolang [--json-errors] [--machine-readable]");
+
+    for command in COMMANDS {
+        usage.push_str(&format!("\n\t{}", command.name));
+        if !command.args.is_empty() {
+            usage.push_str(&format!(" {}", command.args));
+        }
+        for flag in command.flags {
+            let dashes = if flag.name.len() == 1 { "-" } else { "--" };
+            match flag.value {
+                Some(value) => usage.push_str(&format!(" [{}{} <{}>]", dashes, flag.name, value)),
+                None => usage.push_str(&format!(" [{}{}]", dashes, flag.name)),
+            }
+        }
+    }
+
+    usage
+}
+
+// `-` reads stdin and `archive.zip::program.svo` a file inside an archive
+fn read_file(filename: &str) -> Result<String, String> {
+    if filename == "-" {
+        return io::read_to_string(io::stdin())
+            .map_err(|err| format!("failed to read stdin: {}", err));
+    }
+    if let Some((archive_path, entry)) = archive::split_path(filename) {
+        return archive::read_entry(archive_path, entry).map_err(|err| err.to_string());
+    }
+    fs::read_to_string(filename).map_err(|err| format!("failed to read {}: {}", filename, err))
+}
+
+fn load_program(filename: &str) -> Result<Vec<Instruction>, CliError> {
+    parse_source(filename, &read_file(filename)?)
+}
+
+fn parse_source(filename: &str, source: &str) -> Result<Vec<Instruction>, CliError> {
+    let opcodes = lex(source);
+    parse_with_debug_prints(&opcodes, debug_prints(source))
+        .map_err(|err| parse_error(filename, err))
+}
+
+fn parse_error(filename: &str, err: ParseError) -> CliError {
+    CliError {
+        kind: ErrorKind::Parse,
+        message: err.to_string(),
+        position: Some(err.position()),
+        context: Some(filename.to_string()),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for --{}: `{}`", flag, value))
+}
+
+// the passes --opt-level picks, --optimize is level 2. level 0 is no pipeline at all
+fn optimizer(matches: &Matches) -> Result<Option<OptimizerPipeline>, String> {
+    let level = match (matches.value("opt-level"), matches.flag("optimize")) {
+        (Some(_), true) => return Err("--optimize is --opt-level 2, pass only one".to_string()),
+        (Some(value), false) => parse_number("opt-level", value)?,
+        (None, true) => 2,
+        (None, false) => return Ok(None),
+    };
+    match OptimizerPipeline::level(level) {
+        Some(_) if level == 0 => Ok(None),
+        Some(pipeline) => Ok(Some(pipeline)),
+        None => Err(format!(
+            "--opt-level must be 0 to {}, got {}",
+            OptimizerPipeline::MAX_LEVEL,
+            level
+        )),
+    }
+}
+
+const CONFIG_FILE: &str = "svolang.toml";
+
+// defaults, then svolang.toml from the current directory, then command line flags
+fn interpreter_config(matches: &Matches) -> Result<InterpreterConfig, String> {
+    let mut config = match fs::read_to_string(CONFIG_FILE) {
+        Ok(source) => InterpreterConfig::from_toml(&source)
+            .map_err(|err| format!("{}: {}", CONFIG_FILE, err))?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => InterpreterConfig::default(),
+        Err(err) => return Err(format!("failed to read {}: {}", CONFIG_FILE, err)),
+    };
+
+    if let Some(value) = matches.value("tape-size") {
+        config.tape_size = parse_number("tape-size", value)?;
+    }
+    if let Some(value) = matches.value("start-pointer") {
+        config.start_pointer = parse_number("start-pointer", value)?;
+    }
+    if let Some(value) = matches.value("max-steps") {
+        config.max_steps = Some(parse_number("max-steps", value)?);
+    }
+    if let Some(value) = matches.value("timeout") {
+        let seconds = value
+            .parse::<f64>()
+            .ok()
+            .filter(|seconds| *seconds > 0.0 && seconds.is_finite())
+            .ok_or_else(|| {
+                format!(
+                    "invalid value for --timeout: `{}`, expected a positive number of seconds",
+                    value
+                )
+            })?;
+        config.timeout = Some(Duration::from_secs_f64(seconds));
+    }
+    if let Some(value) = matches.value("output-buffer-size") {
+        config.output_buffer_size = parse_number("output-buffer-size", value)?;
+    }
+    if let Some(value) = matches.value("max-output-bytes") {
+        config.max_output_bytes = Some(parse_number("max-output-bytes", value)?);
+    }
+    if let Some(value) = matches.value("max-loop-iterations") {
+        config.max_loop_iterations = Some(parse_number("max-loop-iterations", value)?);
+    }
+    if let Some(value) = matches.value("instruction-limit-per-loop") {
+        config.instruction_limit_per_loop =
+            Some(parse_number("instruction-limit-per-loop", value)?);
+    }
+    if let Some(value) = matches.value("tape-init") {
+        config.tape_init = TapeInit::from_name(value).ok_or_else(|| {
+            format!(
+                "invalid value for --tape-init: `{}`, expected zeros, ones, sequential or random:<seed>",
+                value
+            )
+        })?;
+    }
+    if let Some(value) = matches.value("write-mode") {
+        config.write_mode = WriteMode::from_name(value).ok_or_else(|| {
+            format!(
+                "invalid value for --write-mode: `{}`, expected byte, codepoint or utf8",
+                value
+            )
+        })?;
+    }
+    if let Some(value) = matches.value("randomize-start-pointer") {
+        let seed = if value == "random" {
+            let seed = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_nanos() as u64)
+                .unwrap_or_default();
+            eprintln!("start pointer seed: {}", seed);
+            seed
+        } else {
+            parse_number("randomize-start-pointer", value)?
+        };
+        config.start_pointer = Rng::new(seed).below(config.tape_size as u64) as usize;
+    }
+    if matches.flag("sandboxed") {
+        config.io_mode = IoMode::Sandboxed;
+    }
+    if matches.flag("parallel-loops") {
+        config.parallel_loops = true;
+    }
+    if matches.flag("debug-print") {
+        config.debug_print = true;
+    }
+
+    if config.start_pointer >= config.tape_size {
+        return Err(format!(
+            "start pointer {} is outside a tape of {} cells",
+            config.start_pointer, config.tape_size
+        ));
+    }
+
+    Ok(config)
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs_f64();
+    if seconds >= 1.0 {
+        format!("{:.2} s", seconds)
+    } else if seconds >= 1e-3 {
+        format!("{:.1} ms", seconds * 1e3)
+    } else {
+        format!("{:.1} µs", seconds * 1e6)
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+fn runtime_error(err: RuntimeError) -> CliError {
+    CliError {
+        kind: ErrorKind::Runtime,
+        message: err.to_string(),
+        position: None,
+        context: None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorKind {
+    Cli,
+    Parse,
+    Runtime,
+}
+
+impl ErrorKind {
+    fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Cli => "cli",
+            ErrorKind::Parse => "parse",
+            ErrorKind::Runtime => "runtime",
+        }
+    }
+}
+
+// what a command failed with, `--json-errors` prints it as one JSON object per line,
+// `context` names the file the error is in where that's known
+#[derive(Debug)]
+struct CliError {
+    kind: ErrorKind,
+    message: String,
+    position: Option<usize>,
+    context: Option<String>,
+}
+
+impl CliError {
+    fn to_json(&self) -> Value {
+        Value::object([
+            ("type", self.kind.name().into()),
+            ("message", self.message.clone().into()),
+            ("source_position", self.position.into()),
+            ("context", self.context.clone().into()),
+        ])
+    }
+}
+
+impl From<&str> for CliError {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+impl From<String> for CliError {
+    fn from(message: String) -> Self {
+        CliError {
+            kind: ErrorKind::Cli,
+            message,
+            position: None,
+            context: None,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.kind, &self.context) {
+            (ErrorKind::Parse, Some(context)) => write!(f, "{}: {}", context, self.message),
+            (ErrorKind::Runtime, _) => write!(f, "runtime error: {}", self.message),
+            _ => f.write_str(&self.message),
+        }
+    }
+}
+
+// forwards program output to stdout while keeping a copy for --check-output
+struct Tee {
+    stdout: io::Stdout,
+    capture: CapturedOutput,
+}
+
+impl Write for Tee {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stdout.write_all(buf)?;
+        self.capture.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdout.flush()
+    }
+}
+
+// the non-zero stretches of the tape, one line per stretch
+fn tape_dump(tape: &Tape) -> String {
+    let mut regions: Vec<(usize, Vec<u8>)> = Vec::new();
+
+    for (index, cell) in tape.iter_nonzero() {
+        match regions.last_mut() {
+            Some((start, cells)) if *start + cells.len() == index => cells.push(cell),
+            _ => regions.push((index, vec![cell])),
+        }
+    }
+
+    let mut out = format!("pointer: {}\n", tape.pointer());
+    for (start, cells) in regions {
+        let values: Vec<String> = cells.iter().map(u8::to_string).collect();
+        out.push_str(&format!(
+            "{}..{}: {}\n",
+            start,
+            start + cells.len(),
+            values.join(" ")
+        ));
+    }
+    out
+}
+
+const ART_HEIGHT: usize = 16;
+const ART_WIDTH: usize = 64;
+
+// one column per cell, `ART_HEIGHT` blocks tall at 255, covering the non-zero cells and
+// the pointer, or `ART_WIDTH` cells around the pointer when that's too wide
+fn tape_art(tape: &Tape) -> String {
+    let cells = tape.cells();
+    let pointer = tape.pointer();
+    let mut nonzero = tape.iter_nonzero().map(|(index, _)| index);
+    let first = nonzero.next().unwrap_or(pointer);
+    let last = nonzero.last().unwrap_or(first);
+    let (mut start, mut end) = (first.min(pointer), last.max(pointer) + 1);
+    if end - start > ART_WIDTH {
+        start = pointer
+            .saturating_sub(ART_WIDTH / 2)
+            .min(cells.len().saturating_sub(ART_WIDTH));
+        end = (start + ART_WIDTH).min(cells.len());
+    }
+
+    let heights: Vec<usize> = cells[start..end]
+        .iter()
+        .map(|&cell| (cell as usize * ART_HEIGHT).div_ceil(u8::MAX as usize))
+        .collect();
+
+    let mut out = format!("pointer: {}, cells {}..{}\n", pointer, start, end);
+    for row in (1..=ART_HEIGHT).rev() {
+        let line: String = heights
+            .iter()
+            .map(|&height| if height >= row { '█' } else { ' ' })
+            .collect();
+        out.push_str(&format!("|{}\n", line.trim_end()));
+    }
+    out.push_str(&format!("+{}\n", "-".repeat(end - start)));
+    out.push_str(&format!(" {}^\n", " ".repeat(pointer - start)));
+    out
+}
+
+fn check_output(expected_filename: &str, expected: &[u8], actual: &[u8]) -> Result<(), String> {
+    if expected == actual {
+        return Ok(());
+    }
+
+    let offset = expected
+        .iter()
+        .zip(actual)
+        .position(|(e, a)| e != a)
+        .unwrap_or(expected.len().min(actual.len()));
+    let line_start = expected[..offset]
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |i| i + 1);
+    let line_number = expected[..offset].iter().filter(|&&b| b == b'\n').count() + 1;
+    let line_at = |data: &[u8]| -> String {
+        let rest = data.get(line_start..).unwrap_or_default();
+        let end = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+        rest[..end].escape_ascii().to_string()
+    };
+
+    Err(format!(
+        "output does not match {}\nfirst difference at byte {} (line {})\n- {}\n+ {}\nexpected {} bytes, got {}",
+        expected_filename,
+        offset,
+        line_number,
+        line_at(expected),
+        line_at(actual),
+        expected.len(),
+        actual.len()
+    ))
+}
+
+// most differing bytes --diff-output lists before it gives up
+const DIFF_OUTPUT_LIMIT: usize = 64;
+
+fn show_byte(byte: Option<&u8>) -> String {
+    match byte {
+        Some(&byte) => format!("0x{:02x} '{}'", byte, byte.escape_ascii()),
+        None => "end of output".to_string(),
+    }
+}
+
+fn diff_output(expected_filename: &str, expected: &[u8], actual: &[u8]) -> Result<(), String> {
+    if expected == actual {
+        return Ok(());
+    }
+
+    let color = io::stderr().is_terminal();
+    let paint = |code: &'static str| if color { code } else { "" };
+    let same = |i: usize| expected.get(i) == actual.get(i);
+    let len = expected.len().max(actual.len());
+
+    let mut lines = vec![format!("output does not match {}", expected_filename)];
+    let mut offset = 0;
+    let mut listed = 0;
+
+    while offset < len {
+        if same(offset) {
+            let start = offset;
+            while offset < len && same(offset) {
+                offset += 1;
+            }
+            lines.push(format!("bytes {}-{}: match", start, offset - 1));
+            continue;
+        }
+
+        if listed == DIFF_OUTPUT_LIMIT {
+            lines.push(format!("... {} more bytes not compared", len - offset));
+            break;
+        }
+
+        lines.push(format!(
+            "byte {}: expected {}{}{}, actual {}{}{}",
+            offset,
+            paint(GREEN),
+            show_byte(expected.get(offset)),
+            paint(RESET),
+            paint(RED),
+            show_byte(actual.get(offset)),
+            paint(RESET)
+        ));
+        listed += 1;
+        offset += 1;
+    }
+
+    lines.push(format!(
+        "expected {} bytes, got {}",
+        expected.len(),
+        actual.len()
+    ));
+    Err(lines.join("\n"))
+}
+
+fn compare_output(
+    matches: &Matches,
+    expected_filename: &str,
+    expected: &[u8],
+    actual: &[u8],
+) -> Result<(), String> {
+    if matches.value("diff-output").is_some() {
+        diff_output(expected_filename, expected, actual)
+    } else {
+        check_output(expected_filename, expected, actual)
+    }
+}
+
+fn read_expected_output(matches: &Matches) -> Result<Option<(&str, Vec<u8>)>, String> {
+    if matches.value("check-output").is_some() && matches.value("diff-output").is_some() {
+        return Err("--check-output and --diff-output can't be combined".to_string());
+    }
+
+    match matches
+        .value("check-output")
+        .or(matches.value("diff-output"))
+    {
+        Some(expected_filename) => {
+            let expected = fs::read(expected_filename)
+                .map_err(|err| format!("failed to read {}: {}", expected_filename, err))?;
+            Ok(Some((expected_filename, expected)))
+        }
+        None => Ok(None),
+    }
+}
+
+// runs every program in order on a fresh tape, each one reading what the previous one wrote
+fn run_pipeline(matches: &Matches) -> Result<(), CliError> {
+    for flag in ["trace", "pre-fill-tape", "shared-tape"] {
+        if matches.value(flag).is_some() {
+            return Err(format!("--pipe cannot be combined with --{}", flag).into());
+        }
+    }
+    if matches.flag("seccomp") {
+        return Err("--pipe cannot be combined with --seccomp".into());
+    }
+
+    if matches.positional.is_empty() {
+        return Err("--pipe needs at least one program".into());
+    }
+
+    let programs = matches
+        .positional
+        .iter()
+        .map(|filename| load_program(filename).map(|program| (filename, program)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let expected_output = read_expected_output(matches)?;
+    let capture = CapturedOutput::new();
+    let config = interpreter_config(matches)?;
+    let mut input = None;
+
+    for (i, (filename, program)) in programs.iter().enumerate() {
+        let mut interpreter = Interpreter::new(config.clone());
+        if let Some(data) = input.take() {
+            interpreter.set_input(Cursor::new(data));
+        }
+
+        let stage_output = CapturedOutput::new();
+        if i + 1 < programs.len() {
+            interpreter.set_output(stage_output.clone());
+        } else if expected_output.is_some() {
+            interpreter.set_output(Tee {
+                stdout: io::stdout(),
+                capture: capture.clone(),
+            });
+        }
+
+        interpreter.run(program).map_err(|err| {
+            format!(
+                "pipeline stage {} ({}) failed: {}",
+                i + 1,
+                filename,
+                runtime_error(err)
+            )
+        })?;
+        input = Some(stage_output.contents());
+    }
+
+    match expected_output {
+        Some((expected_filename, expected)) => Ok(compare_output(
+            matches,
+            expected_filename,
+            &expected,
+            &capture.contents(),
+        )?),
+        None => Ok(()),
+    }
+}
+
+fn canonical_path(filename: &str) -> String {
+    fs::canonicalize(filename)
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| filename.to_string())
+}
+
+fn reads_input(instructions: &[Instruction]) -> bool {
+    instructions.iter().any(|instr| match instr {
+        Instruction::Read => true,
+        Instruction::Loop(body) => reads_input(body),
+        _ => false,
+    })
+}
+
+// runs the program `iterations` times on a fresh tape with output discarded,
+// stdin is read once up front and replayed to every run
+fn benchmark(
+    program: &[Instruction],
+    config: &InterpreterConfig,
+    iterations: usize,
+    bytecode: bool,
+) -> Result<(), CliError> {
+    if iterations == 0 {
+        return Err("--benchmark-iterations must be at least 1".into());
+    }
+
+    let mut input = Vec::new();
+    if reads_input(program) {
+        io::stdin()
+            .read_to_end(&mut input)
+            .map_err(|err| format!("failed to read stdin: {}", err))?;
+    }
+    let compiled = bytecode.then(|| ByteCodeProgram::compile(program));
+
+    let mut times = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let mut interpreter = Interpreter::new(config.clone());
+        interpreter.set_input(Cursor::new(input.clone()));
+        interpreter.set_output(io::sink());
+
+        let started = Instant::now();
+        match &compiled {
+            Some(compiled) => ByteCodeVM::new(compiled.clone(), interpreter).run(),
+            None => interpreter.run(program),
+        }
+        .map_err(runtime_error)?;
+        times.push(started.elapsed());
+    }
+
+    times.sort();
+    let total: Duration = times.iter().sum();
+    let percentile = |p: usize| times[(times.len() * p).div_ceil(100).max(1) - 1];
+
+    println!("iterations: {}", iterations);
+    println!("min:        {}", format_duration(times[0]));
+    println!("max:        {}", format_duration(times[times.len() - 1]));
+    println!(
+        "mean:       {}",
+        format_duration(total.div_f64(iterations as f64))
+    );
+    println!("median:     {}", format_duration(percentile(50)));
+    println!("p99:        {}", format_duration(percentile(99)));
+    Ok(())
+}
+
+// the built-in suite on the fastest engine, the bytecode VM running `--opt-level 3` output,
+// with default settings so scores don't depend on svolang.toml
+fn cmd_benchmark(matches: &Matches) -> Result<(), CliError> {
+    let iterations: usize = match matches.value("iterations") {
+        Some(value) => parse_number("iterations", value)?,
+        None => 3,
+    };
+    if iterations == 0 {
+        return Err("--iterations must be at least 1".into());
+    }
+    let previous = match matches.value("compare") {
+        Some(filename) => Some(
+            Value::parse(&read_file(filename)?)
+                .and_then(|json| BenchmarkReport::from_json(&json))
+                .map_err(|err| format!("{}: {}", filename, err))?,
+        ),
+        None => None,
+    };
+    let quiet = matches.flag("json") || matches.machine_readable();
+    let pipeline = OptimizerPipeline::level(3).expect("level 3 exists");
+
+    let mut results = Vec::new();
+    for benchmark in &SUITE {
+        let program = parse(&lex(benchmark.source)).expect("built-in benchmarks parse");
+        let compiled = ByteCodeProgram::compile(&pipeline.optimize(program));
+
+        let mut times = Vec::with_capacity(iterations);
+        for _ in 0..iterations {
+            let output = CapturedOutput::new();
+            let mut interpreter = Interpreter::new(InterpreterConfig::default());
+            interpreter.set_input(io::empty());
+            interpreter.set_output(output.clone());
+
+            let started = Instant::now();
+            ByteCodeVM::new(compiled.clone(), interpreter)
+                .run()
+                .map_err(|err| format!("{}: {}", benchmark.name, runtime_error(err)))?;
+            times.push(started.elapsed());
+
+            if hex::encode(&sha256::digest(&output.contents())) != benchmark.output_sha256 {
+                return Err(format!("{} printed the wrong output", benchmark.name).into());
+            }
+        }
+
+        times.sort();
+        let result = BenchmarkResult {
+            name: benchmark.name.to_string(),
+            min: times[0],
+            median: times[(times.len() - 1) / 2],
+        };
+        if !quiet {
+            println!(
+                "{:<12} {:>10}  (min {})",
+                result.name,
+                format_duration(result.median),
+                format_duration(result.min)
+            );
+        }
+        results.push(result);
+    }
+
+    let report = BenchmarkReport::new(results);
+    let comparisons = match &previous {
+        Some(previous) => report.compare(previous),
+        None => Vec::new(),
+    };
+    if matches.flag("json") {
+        println!("{}", report.to_json());
+    } else if matches.machine_readable() {
+        for result in &report.results {
+            matches.report(
+                Level::Info,
+                &format!("{}: {}", result.name, format_duration(result.median)),
+                Value::object([
+                    ("benchmark", result.name.as_str().into()),
+                    ("median_ns", (result.median.as_nanos() as u64).into()),
+                    ("min_ns", (result.min.as_nanos() as u64).into()),
+                ]),
+            );
+        }
+        matches.report(
+            Level::Info,
+            &format!("speed score: {}", report.score),
+            Value::object([("score", report.score.into())]),
+        );
+    } else {
+        println!("speed score: {}", report.score);
+    }
+
+    let mut regressed = 0;
+    for (now, before) in comparisons {
+        let change = now.median.as_secs_f64() / before.median.as_secs_f64() - 1.0;
+        let message = format!(
+            "{}: {} against {} before, {:+.1}%",
+            now.name,
+            format_duration(now.median),
+            format_duration(before.median),
+            change * 100.0
+        );
+        if now.regressed_from(before) {
+            regressed += 1;
+            matches.warn(
+                &format!("regression in {}", message),
+                Value::object([
+                    ("benchmark", now.name.as_str().into()),
+                    ("median_ns", (now.median.as_nanos() as u64).into()),
+                    (
+                        "previous_median_ns",
+                        (before.median.as_nanos() as u64).into(),
+                    ),
+                ]),
+            );
+        } else if !quiet {
+            println!("{}", message);
+        }
+    }
+
+    if regressed > 0 {
+        return Err(format!(
+            "{} of the benchmarks got more than {:.0}% slower",
+            regressed,
+            (REGRESSION_THRESHOLD - 1.0) * 100.0
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+fn install_seccomp(config: &InterpreterConfig) -> Result<(), CliError> {
+    svolang::seccomp::install(config.io_mode)
+        .map_err(|err| format!("failed to install the seccomp filter: {}", err).into())
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+fn install_seccomp(_config: &InterpreterConfig) -> Result<(), CliError> {
+    Err("--seccomp is only available on Linux on x86_64 and aarch64".into())
+}
+
+fn cmd_run(matches: &Matches) -> Result<(), CliError> {
+    if matches.flag("pipe") {
+        return run_pipeline(matches);
+    }
+
+    let started = Instant::now();
+    let filename = matches.arg(0, "<file.svo>")?;
+    let source = read_file(filename)?;
+    let keywords = match matches.value("dialect-file") {
+        Some(dialect_filename) => Some(
+            KeywordSet::from_toml(&read_file(dialect_filename)?)
+                .map_err(|err| format!("{}: {}", dialect_filename, err))?,
+        ),
+        None => None,
+    };
+    let mut program = if matches.flag("verbose-parse") {
+        let (opcodes, prints) = match &keywords {
+            Some(keywords) => (keywords.lex(&source), keywords.debug_prints(&source)),
+            None => (lex(&source), debug_prints(&source)),
+        };
+        let progress = |parsed, total| eprintln!("parsed {} / {} opcodes", parsed, total);
+        parse_with_progress(&opcodes, prints, Some(Box::new(progress)))
+            .map_err(|err| parse_error(filename, err))?
+    } else if let Some(keywords) = &keywords {
+        parse_with_debug_prints(&keywords.lex(&source), keywords.debug_prints(&source))
+            .map_err(|err| parse_error(filename, err))?
+    } else {
+        parse_source(filename, &source)?
+    };
+
+    let coverage_output = matches.value("coverage-output");
+    let coverage = matches.flag("coverage") || coverage_output.is_some();
+    if coverage && keywords.is_some() {
+        return Err("--coverage maps opcodes back to `sv` words, drop --dialect-file".into());
+    }
+    let mut optimized = None;
+    if let Some(pipeline) = optimizer(matches)? {
+        if coverage {
+            return Err(
+                "--coverage reports source ranges and can't be combined with optimization".into(),
+            );
+        }
+        let before = count_instructions(&program);
+        program = pipeline.optimize(program);
+        optimized = Some((before, count_instructions(&program)));
+    }
+
+    if let Some(value) = matches.value("benchmark-iterations") {
+        if matches.flag("seccomp") {
+            return Err("--benchmark-iterations cannot be combined with --seccomp".into());
+        }
+        let iterations = parse_number("benchmark-iterations", value)?;
+        let config = interpreter_config(matches)?;
+        return benchmark(&program, &config, iterations, matches.flag("bytecode"));
+    }
+
+    let mut interpreter = Interpreter::new(interpreter_config(matches)?);
+
+    if matches.flag("seccomp") {
+        // the filter goes in before the run, so nothing after it may open a file
+        if let Some(flag) = ["record", "profile-output", "coverage-output"]
+            .iter()
+            .find(|flag| matches.value(flag).is_some())
+        {
+            return Err(format!(
+                "--{} writes a file after the run, which --seccomp doesn't allow",
+                flag
+            )
+            .into());
+        }
+        // and the watchdog and loop workers are threads the filter would stop from starting
+        if interpreter.config().timeout.is_some() {
+            return Err("--seccomp doesn't allow the thread behind --timeout".into());
+        }
+        if interpreter.config().parallel_loops {
+            return Err("--seccomp doesn't allow the threads behind --parallel-loops".into());
+        }
+    }
+
+    if matches.flag("bytecode") {
+        let unsupported = [
+            "trace",
+            "coverage",
+            "coverage-output",
+            "profile",
+            "profile-output",
+            "statistics",
+            "cell-value-trace",
+            "shared-tape",
+            "parallel-loops",
+            "debug-print",
+        ];
+        if let Some(flag) = unsupported
+            .iter()
+            .find(|flag| matches.value(flag).is_some() || matches.flag(flag))
+        {
+            return Err(format!(
+                "--{} needs the tree-walking interpreter, drop --bytecode",
+                flag
+            )
+            .into());
+        }
+        if interpreter.config().max_loop_iterations.is_some() {
+            return Err(
+                "max_loop_iterations needs the tree-walking interpreter, drop --bytecode".into(),
+            );
+        }
+        if interpreter.config().instruction_limit_per_loop.is_some() {
+            return Err(
+                "instruction_limit_per_loop needs the tree-walking interpreter, drop --bytecode"
+                    .into(),
+            );
+        }
+    }
+
+    if let Some(path) = matches.value("shared-tape") {
+        let config = interpreter.config();
+        let tape = Tape::shared(Path::new(path), config.tape_size, config.start_pointer)
+            .map_err(runtime_error)?;
+        interpreter = interpreter.with_tape(tape);
+    }
+
+    if let Some(fill) = matches.value("pre-fill-tape") {
+        let bytes = hex::decode(fill).map_err(|err| format!("--pre-fill-tape: {}", err))?;
+        let cells = interpreter.tape_mut().cells_mut();
+        if bytes.len() > cells.len() {
+            return Err(format!(
+                "--pre-fill-tape: {} bytes do not fit on a tape of {} cells",
+                bytes.len(),
+                cells.len()
+            )
+            .into());
+        }
+        cells[..bytes.len()].copy_from_slice(&bytes);
+    }
+
+    let expected_output = read_expected_output(matches)?;
+    let capture = CapturedOutput::new();
+    if expected_output.is_some() {
+        interpreter.set_output(Tee {
+            stdout: io::stdout(),
+            capture: capture.clone(),
+        });
+    }
+
+    if let Some(trace_filename) = matches.value("trace") {
+        let file = File::create(trace_filename)
+            .map_err(|err| format!("failed to create {}: {}", trace_filename, err))?;
+        let writer = TraceWriter::new(BufWriter::new(file), &canonical_path(filename))
+            .map_err(runtime_error)?;
+        if matches.flag("trace-timing") {
+            interpreter.set_tracer(writer.with_timing());
+        } else {
+            interpreter.set_tracer(writer);
+        }
+    } else if matches.flag("trace-timing") {
+        return Err("--trace-timing needs --trace <file>".into());
+    }
+
+    if coverage {
+        interpreter.enable_coverage();
+    }
+    if matches.flag("statistics") {
+        interpreter.enable_statistics();
+    }
+
+    let record = matches.value("record");
+    let session = Rc::new(RefCell::new(Session {
+        program: Some(canonical_path(filename)),
+        chunks: Vec::new(),
+    }));
+    match (record, matches.value("replay")) {
+        (Some(_), Some(_)) => return Err("--record and --replay can't be combined".into()),
+        (Some(_), None) => interpreter.set_input(Recorder::new(io::stdin(), session.clone())),
+        (None, Some(session_filename)) => {
+            let replayed = Session::parse(&read_file(session_filename)?)
+                .map_err(|err| format!("{}: {}", session_filename, err))?;
+            interpreter.set_input(Cursor::new(replayed.input()));
+        }
+        (None, None) => (),
+    }
+
+    // `json <file>` or `csv <file>`, written instead of the table on stderr
+    let profile_output = match matches.values("profile-output") {
+        None => None,
+        Some([format, path]) if matches!(format.as_str(), "json" | "csv") => {
+            Some((format.as_str(), path.as_str()))
+        }
+        Some(values) => {
+            return Err(format!(
+                "invalid format for --profile-output: `{}`, expected json or csv",
+                values[0]
+            )
+            .into())
+        }
+    };
+    if matches.flag("profile") || profile_output.is_some() {
+        interpreter.attach_profiler(Profiler::new());
+    }
+    for value in matches.all_values("cell-value-trace") {
+        let cell: usize = parse_number("cell-value-trace", value)?;
+        if cell >= interpreter.config().tape_size {
+            return Err(format!(
+                "--cell-value-trace {} is outside a tape of {} cells",
+                cell,
+                interpreter.config().tape_size
+            )
+            .into());
+        }
+        interpreter.watch_cell(cell);
+    }
+
+    if matches.flag("seccomp") {
+        install_seccomp(interpreter.config())?;
+    }
+
+    let result = if matches.flag("bytecode") {
+        let mut vm = ByteCodeVM::new(ByteCodeProgram::compile(&program), interpreter);
+        let result = vm.run();
+        interpreter = vm.interpreter;
+        result
+    } else {
+        interpreter.run(&program)
+    };
+
+    if let Some(session_filename) = record {
+        fs::write(session_filename, session.borrow().to_toml())
+            .map_err(|err| format!("failed to write {}: {}", session_filename, err))?;
+    }
+
+    if let Some(mut tracer) = interpreter.take_tracer() {
+        tracer
+            .finish(interpreter.state_checksum())
+            .map_err(runtime_error)?;
+    }
+
+    // stdout belongs to the program, so run's own reports stay on stderr
+    let emit = |message: &str, data: Value| {
+        eprintln!("{}", matches.message(Level::Info, message, data));
+    };
+
+    if matches.flag("tape-dump") && matches.machine_readable() {
+        let cells: Vec<Value> = interpreter
+            .tape()
+            .iter_nonzero()
+            .map(|(index, cell)| {
+                Value::object([("index", index.into()), ("value", (cell as usize).into())])
+            })
+            .collect();
+        emit(
+            "tape",
+            Value::object([
+                ("pointer", interpreter.tape().pointer().into()),
+                ("cells", cells.into()),
+            ]),
+        );
+    } else if matches.flag("tape-dump") {
+        if matches.flag("ascii-art") {
+            eprint!("{}", tape_art(interpreter.tape()));
+        } else {
+            eprint!("{}", tape_dump(interpreter.tape()));
+        }
+    }
+
+    for (cell, writes) in interpreter.cell_writes() {
+        if matches.machine_readable() {
+            let writes: Vec<Value> = writes
+                .iter()
+                .map(|write| {
+                    Value::object([
+                        ("step", write.step.into()),
+                        ("position", write.position.into()),
+                        ("value", (write.value as usize).into()),
+                    ])
+                })
+                .collect();
+            emit(
+                &format!("cell {}: {} writes", cell, writes.len()),
+                Value::object([("cell", (*cell).into()), ("writes", writes.into())]),
+            );
+            continue;
+        }
+        eprintln!("cell {}: {} writes", cell, writes.len());
+        for write in writes {
+            eprintln!("{}", write);
+        }
+    }
+
+    if let Some(profiler) = interpreter.profiler() {
+        let report = profiler.report();
+        match profile_output {
+            Some((format, profile_filename)) => {
+                let contents = match format {
+                    "json" => format!("{}\n", report.to_json()),
+                    _ => report.to_csv(),
+                };
+                fs::write(profile_filename, contents)
+                    .map_err(|err| format!("failed to write {}: {}", profile_filename, err))?
+            }
+            None if matches.machine_readable() => emit("profile", report.to_json()),
+            None => eprint!("{}", report),
+        }
+    }
+
+    if let (Some(statistics), true) = (interpreter.statistics(), matches.machine_readable()) {
+        let mut data = vec![
+            ("elapsed_ns", (started.elapsed().as_nanos() as u64).into()),
+            ("instructions_executed", interpreter.steps().into()),
+            ("cells_written", statistics.cells_written.into()),
+            ("peak_tape_size", statistics.peak_tape_size().into()),
+            ("bytes_read", statistics.bytes_read.into()),
+            ("bytes_written", statistics.bytes_written.into()),
+        ];
+        if let Some((before, after)) = optimized {
+            data.push(("instructions_before_optimization", before.into()));
+            data.push(("instructions_after_optimization", after.into()));
+        }
+        emit("statistics", Value::object(data));
+    } else if let Some(statistics) = interpreter.statistics() {
+        eprintln!(
+            "elapsed:               {}",
+            format_duration(started.elapsed())
+        );
+        eprintln!("instructions executed: {}", interpreter.steps());
+        eprintln!("cells written:         {}", statistics.cells_written);
+        eprintln!(
+            "peak tape size:        {}",
+            format_bytes(statistics.peak_tape_size() as u64)
+        );
+        eprintln!(
+            "bytes read:            {}",
+            format_bytes(statistics.bytes_read)
+        );
+        eprintln!(
+            "bytes written:         {}",
+            format_bytes(statistics.bytes_written)
+        );
+        if let Some((before, after)) = optimized {
+            eprintln!(
+                "instructions:          {} before optimization, {} after",
+                before, after
+            );
+        }
+    }
+
+    if let Some(hits) = interpreter.coverage() {
+        let tokens = tokenize(&source);
+        let ranges = coverage::ranges(&program, hits, &tokens);
+        let report: String = ranges.iter().map(|range| format!("{}\n", range)).collect();
+        match coverage_output {
+            None if matches.machine_readable() => {
+                let ranges: Vec<Value> = ranges
+                    .iter()
+                    .map(|range| {
+                        Value::object([
+                            ("start", range.start.into()),
+                            ("end", range.end.into()),
+                            ("covered", range.covered.into()),
+                        ])
+                    })
+                    .collect();
+                emit("coverage", Value::object([("ranges", ranges.into())]));
+            }
+            Some(coverage_filename) => fs::write(coverage_filename, report)
+                .map_err(|err| format!("failed to write {}: {}", coverage_filename, err))?,
+            None => eprint!("{}", report),
+        }
+    }
+
+    result.map_err(|err| CliError {
+        context: Some(filename.to_string()),
+        ..runtime_error(err)
+    })?;
+
+    match expected_output {
+        Some((expected_filename, expected)) => Ok(compare_output(
+            matches,
+            expected_filename,
+            &expected,
+            &capture.contents(),
+        )?),
+        None => Ok(()),
+    }
+}
+
+fn cmd_translate(matches: &Matches) -> Result<(), CliError> {
+    let from_filename = matches.arg(0, "<file.bf>")?;
+    let to_filename = matches.arg(1, "<file.svo>")?;
+
+    let source = read_file(from_filename)?;
+    let dialect = match matches.value("dialect") {
+        Some(name) => Dialect::from_name(name)
+            .ok_or_else(|| format!("unknown dialect `{}`, expected bf, ook or whitespace", name))?,
+        None => Dialect::Bf,
+    };
+    let (result, warnings) = if matches.flag("interleave-comments") {
+        if dialect != Dialect::Bf {
+            return Err("--interleave-comments only keeps bf comments".into());
+        }
+        (translate_bf_with_comments(&source), Vec::new())
+    } else {
+        translate(&source, dialect)
+    };
+    for warning in warnings {
+        matches.warn(
+            &format!("{}: {}", from_filename, warning),
+            Value::object([("file", from_filename.into())]),
+        );
+    }
+
+    if matches.flag("validate") {
+        if dialect != Dialect::Bf {
+            return Err("--validate only checks bf translations".into());
+        }
+        let count =
+            validate_bf(&source, &result).map_err(|err| format!("{}: {}", from_filename, err))?;
+        let message = format!("round trip matched all {} opcodes", count);
+        if matches.machine_readable() {
+            matches.report(
+                Level::Info,
+                &message,
+                Value::object([("file", from_filename.into()), ("opcodes", count.into())]),
+            );
+        } else {
+            eprintln!("{}", message);
+        }
+    }
+
+    if to_filename == "-" {
+        return io::stdout()
+            .write_all(result.as_bytes())
+            .map_err(|err| format!("failed to write stdout: {}", err).into());
+    }
+    fs::write(to_filename, result)
+        .map_err(|err| format!("failed to write {}: {}", to_filename, err).into())
+}
+
+fn read_trace(filename: &str) -> Result<Trace, String> {
+    Trace::parse(&read_file(filename)?).map_err(|err| format!("{}: {}", filename, err))
+}
+
+fn cmd_replay(matches: &Matches) -> Result<(), CliError> {
+    let trace_filename = matches.arg(0, "<trace-file>")?;
+    let trace = read_trace(trace_filename)?;
+
+    let program_filename = matches
+        .value("program")
+        .or(trace.program.as_deref())
+        .ok_or("trace does not name its program, pass --program <file.svo>")?;
+    let program = load_program(program_filename)?;
+
+    let mut interpreter = Interpreter::new(interpreter_config(matches)?);
+    interpreter.set_input(Cursor::new(trace.input()));
+    interpreter.set_tracer(ReplayVerifier::new(&trace));
+
+    interpreter.run(&program).map_err(runtime_error)?;
+
+    if let Some(mut verifier) = interpreter.take_tracer() {
+        verifier
+            .finish(interpreter.state_checksum())
+            .map_err(runtime_error)?;
+    }
+
+    let message = format!("replay matched all {} recorded steps", trace.events.len());
+    if matches.machine_readable() {
+        matches.report(
+            Level::Info,
+            &message,
+            Value::object([
+                ("trace", trace_filename.into()),
+                ("steps", trace.events.len().into()),
+            ]),
+        );
+    } else {
+        eprintln!("{}", message);
+    }
+    Ok(())
+}
+
+// events shown on each side of the first difference
+const TRACE_DIFF_CONTEXT: usize = 5;
+
+fn cmd_trace_diff(matches: &Matches) -> Result<(), CliError> {
+    let a_filename = matches.arg(0, "<trace1>")?;
+    let b_filename = matches.arg(1, "<trace2>")?;
+    let a = read_trace(a_filename)?;
+    let b = read_trace(b_filename)?;
+
+    let Some(divergence) = a.divergence(&b) else {
+        let message = match (a.checksum, b.checksum) {
+            (Some(x), Some(y)) if x != y => format!(
+                "all {} events match but the final tapes differ, checksum {:016x} vs {:016x}",
+                a.events.len(),
+                x,
+                y
+            ),
+            _ => format!("traces are identical, {} events", a.events.len()),
+        };
+        if matches.machine_readable() {
+            let data = Value::object([
+                ("events", a.events.len().into()),
+                ("checksums_match", (a.checksum == b.checksum).into()),
+            ]);
+            matches.report(Level::Info, &message, data);
+        } else {
+            println!("{}", message);
+        }
+        return Ok(());
+    };
+
+    let shown = |trace: &Trace, i: usize| {
+        trace
+            .events
+            .get(i)
+            .map_or("(end of trace)".to_string(), |event| event.to_string())
+    };
+    let start = divergence.saturating_sub(TRACE_DIFF_CONTEXT);
+    let end = (divergence + TRACE_DIFF_CONTEXT + 1).min(a.events.len().max(b.events.len()));
+    let step = a
+        .events
+        .get(divergence)
+        .or(b.events.get(divergence))
+        .map_or(0, |event| event.step);
+    let message = format!("traces diverge at step {}", step);
+
+    if matches.machine_readable() {
+        let data = Value::object([
+            ("step", step.into()),
+            ("first", shown(&a, divergence).into()),
+            ("second", shown(&b, divergence).into()),
+        ]);
+        matches.report(Level::Info, &message, data);
+        return Ok(());
+    }
+
+    let rows: Vec<(String, String)> = (start..end).map(|i| (shown(&a, i), shown(&b, i))).collect();
+    let width = rows
+        .iter()
+        .map(|(left, _)| left.len())
+        .chain([a_filename.len()])
+        .max()
+        .unwrap_or_default();
+    println!("{}", message);
+    println!("  {:<width$}  {}", a_filename, b_filename);
+    for (i, (left, right)) in (start..end).zip(rows) {
+        let marker = if i == divergence { '>' } else { ' ' };
+        println!("{} {:<width$}  {}", marker, left, right);
+    }
+    Ok(())
+}
+
+fn cmd_compile(matches: &Matches) -> Result<(), CliError> {
+    let filename = matches.arg(0, "<file.svo>")?;
+    let program = load_program(filename)?;
+    let source = codegen::to_c(&program, &interpreter_config(matches)?)?;
+
+    let target = matches.value("target").unwrap_or("native");
+    let extension = match target {
+        "native" => "o",
+        "c" => "c",
+        _ => return Err(format!("unknown target `{}`, expected native or c", target).into()),
+    };
+    let output = match matches.value("o") {
+        Some(output) => Path::new(output).to_path_buf(),
+        None => Path::new(filename).with_extension(extension),
+    };
+
+    if target == "c" {
+        return fs::write(&output, source)
+            .map_err(|err| format!("failed to write {}: {}", output.display(), err).into());
+    }
+
+    Ok(codegen::compile_c(&source, &output, &["-O2", "-c"])?)
+}
+
+fn cmd_pack(matches: &Matches) -> Result<(), CliError> {
+    let filename = matches.arg(0, "<file.svo>")?;
+    let program = load_program(filename)?;
+    let config = interpreter_config(matches)?;
+    let source = if matches.flag("no-io") {
+        codegen::to_c_without_stdin(&program, &config)?
+    } else {
+        codegen::to_c(&program, &config)?
+    };
+
+    let output = match matches.value("o") {
+        Some(output) => Path::new(output).to_path_buf(),
+        None => Path::new(filename).with_extension(""),
+    };
+    Ok(codegen::compile_c(&source, &output, &["-O2"])?)
+}
+
+fn cmd_stats(matches: &Matches) -> Result<(), CliError> {
+    let filename = matches.arg(0, "<file.svo>")?;
+    let source = read_file(filename)?;
+    let stats = ProgramStats::collect(&source).map_err(|err| format!("{}: {}", filename, err))?;
+    for position in unbounded_loops(&parse_source(filename, &source)?) {
+        matches.warn(
+            &format!("{}: loop #{} may never terminate", filename, position),
+            Value::object([("file", filename.into()), ("loop", position.into())]),
+        );
+    }
+
+    let opcode_stats = if matches.flag("opcode-stats") {
+        Some(OpcodeStats::collect(&source).map_err(|err| format!("{}: {}", filename, err))?)
+    } else {
+        None
+    };
+
+    if matches.flag("json") || matches.machine_readable() {
+        let mut json = stats.to_json();
+        if let (Value::Object(fields), Some(opcode_stats)) = (&mut json, &opcode_stats) {
+            fields.push(("opcode_stats".to_string(), opcode_stats.to_json()));
+        }
+        if matches.machine_readable() {
+            matches.report(Level::Info, &format!("{}: statistics", filename), json);
+        } else {
+            println!("{}", json);
+        }
+        return Ok(());
+    }
+
+    println!("source bytes:          {}", stats.source_bytes);
+    println!("opcodes:               {}", stats.opcodes);
+    println!("instructions:          {}", stats.instructions);
+    println!("optimized:             {}", stats.optimized_instructions);
+    println!("estimated cycles:      {}", stats.estimated_cycles);
+    println!(
+        "  optimized:           {}",
+        stats.optimized_estimated_cycles
+    );
+    println!("loops:                 {}", stats.loops);
+    println!("complexity score:      {:.1}", stats.complexity_score);
+
+    if let Some(opcode_stats) = opcode_stats {
+        println!("\nopcode frequencies:");
+        for (name, count) in &opcode_stats.opcodes {
+            println!("  {:<20} {}", name, count);
+        }
+        println!("\ninstruction frequencies:");
+        println!("  {:<20} {:>6}  {:>9}", "", "before", "optimized");
+        let rows = opcode_stats
+            .instructions
+            .iter()
+            .zip(&opcode_stats.optimized_instructions);
+        for ((name, before), (_, after)) in rows {
+            if *before > 0 || *after > 0 {
+                println!("  {:<20} {:>6}  {:>9}", name, before, after);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn cmd_mutate(matches: &Matches) -> Result<(), CliError> {
+    let filename = matches.arg(0, "<file.svo>")?;
+    let program = load_program(filename)?;
+
+    let expected_filename = matches
+        .value("expected-output")
+        .ok_or("mutate needs --expected-output <file>")?;
+    let expected = fs::read(expected_filename)
+        .map_err(|err| format!("failed to read {}: {}", expected_filename, err))?;
+    let input = match matches.value("input") {
+        Some(input_filename) => fs::read(input_filename)
+            .map_err(|err| format!("failed to read {}: {}", input_filename, err))?,
+        None => Vec::new(),
+    };
+
+    // mutants that loop forever are cut off well after the original would have finished
+    let mut config = interpreter_config(matches)?;
+    let original_output = CapturedOutput::new();
+    let mut original = Interpreter::new(config.clone());
+    original.set_input(Cursor::new(input.clone()));
+    original.set_output(original_output.clone());
+    original.run(&program).map_err(runtime_error)?;
+    check_output(expected_filename, &expected, &original_output.contents())?;
+
+    config.max_steps = Some(match matches.value("max-steps") {
+        Some(value) => parse_number("max-steps", value)?,
+        None => original.steps().saturating_mul(10).max(10_000),
+    });
+
+    let mutations = mutate::mutations(&program);
+    let mut survived = 0;
+
+    for mutation in &mutations {
+        let mutant = mutate::apply(&program, mutation);
+        if let Ok(output) = run_with_input(&mutant, &config, &input) {
+            if output == expected {
+                survived += 1;
+                if matches.machine_readable() {
+                    matches.report(
+                        Level::Warn,
+                        &format!("survived: {}", mutation),
+                        Value::object([("mutation", mutation.to_string().into())]),
+                    );
+                } else {
+                    println!("survived: {}", mutation);
+                }
+            }
+        }
+    }
+
+    let killed = mutations.len() - survived;
+    let message = format!(
+        "{} mutants, {} killed, {} survived",
+        mutations.len(),
+        killed,
+        survived
+    );
+    if matches.machine_readable() {
+        matches.report(
+            Level::Info,
+            &message,
+            Value::object([
+                ("mutants", mutations.len().into()),
+                ("killed", killed.into()),
+                ("survived", survived.into()),
+            ]),
+        );
+    } else {
+        println!("{}", message);
+    }
+    Ok(())
+}
+
+// lines are the source file's, each opcode's token says which line it's written on
+fn cmd_coverage(matches: &Matches) -> Result<(), CliError> {
+    let filename = matches.arg(0, "<file.svo>")?;
+    let source = read_file(filename)?;
+    let program = parse_source(filename, &source)?;
+    let input = match matches.value("input") {
+        Some(input_filename) => fs::read(input_filename)
+            .map_err(|err| format!("failed to read {}: {}", input_filename, err))?,
+        None => Vec::new(),
+    };
+
+    let mut interpreter = Interpreter::new(interpreter_config(matches)?);
+    interpreter.set_input(Cursor::new(input));
+    interpreter.set_output(CapturedOutput::new());
+    interpreter.enable_coverage();
+    // a run that fails still shows how far it got, so the tracefile is written either way
+    let result = interpreter.run(&program);
+
+    let counts = coverage::position_hits(&program, interpreter.coverage().unwrap_or_default());
+    let lines = coverage::line_hits(&source, &tokenize(&source), &counts);
+    let output = match matches.value("o") {
+        Some(output) => Path::new(output).to_path_buf(),
+        None => Path::new(filename).with_extension("info"),
+    };
+    fs::write(&output, coverage::to_lcov(filename, &lines))
+        .map_err(|err| format!("failed to write {}: {}", output.display(), err))?;
+    result.map_err(runtime_error)?;
+
+    let covered = lines.iter().filter(|(_, hits)| *hits > 0).count();
+    let message = format!(
+        "{} of {} lines covered, wrote {}",
+        covered,
+        lines.len(),
+        output.display()
+    );
+    if matches.machine_readable() {
+        matches.report(
+            Level::Info,
+            &message,
+            Value::object([
+                ("lines", lines.len().into()),
+                ("covered", covered.into()),
+                ("output", output.display().to_string().into()),
+            ]),
+        );
+    } else {
+        println!("{}", message);
+    }
+    Ok(())
+}
+
+fn cmd_lsp(_: &Matches) -> Result<(), CliError> {
+    let shut_down = lsp::serve(io::stdin().lock(), io::stdout().lock())
+        .map_err(|err| format!("language server: {}", err))?;
+
+    if !shut_down {
+        return Err("language server got `exit` before `shutdown`".into());
+    }
+
+    Ok(())
+}
+
+fn cmd_dump_cfg(matches: &Matches) -> Result<(), CliError> {
+    let filename = matches.arg(0, "<file.svo>")?;
+    let mut program = Program::new(load_program(filename)?);
+
+    if let Some(pipeline) = optimizer(matches)? {
+        program = program.optimize_with(&pipeline);
+    }
+
+    print!("{}", program.to_dot());
+    Ok(())
+}
+
+struct BatchResult {
+    output: Vec<u8>,
+    steps: u64,
+    elapsed: Duration,
+    error: Option<String>,
+    // opcode count of a program left out by --min-opcode-count / --max-opcode-count
+    skipped: Option<usize>,
+}
+
+impl BatchResult {
+    fn not_run(elapsed: Duration, error: Option<String>, skipped: Option<usize>) -> Self {
+        BatchResult {
+            output: Vec::new(),
+            steps: 0,
+            elapsed,
+            error,
+            skipped,
+        }
+    }
+}
+
+// every program gets its own interpreter with empty stdin and captured output,
+// programs whose opcode count falls outside `opcodes` are skipped before parsing
+fn run_batch_program(
+    filename: &str,
+    config: &InterpreterConfig,
+    opcodes: &RangeInclusive<usize>,
+) -> BatchResult {
+    let started = Instant::now();
+    let failed = |err| BatchResult::not_run(started.elapsed(), Some(err), None);
+    let source = match read_file(filename) {
+        Ok(source) => source,
+        Err(err) => return failed(err),
+    };
+    let count = lex(&source).len();
+    if !opcodes.contains(&count) {
+        return BatchResult::not_run(started.elapsed(), None, Some(count));
+    }
+    let program = match parse_source(filename, &source) {
+        Ok(program) => program,
+        Err(err) => return failed(err.to_string()),
+    };
+
+    let capture = SharedCapture::new();
+    let mut interpreter = ThreadSafeInterpreter::new(config.clone());
+    interpreter.set_input(io::empty());
+    interpreter.set_output(capture.clone());
+    let result = interpreter.run(&program);
+
+    BatchResult {
+        output: capture.contents(),
+        steps: interpreter.steps(),
+        elapsed: started.elapsed(),
+        error: result.err().map(|err| runtime_error(err).to_string()),
+        skipped: None,
+    }
+}
+
+fn print_batch_result(matches: &Matches, filename: &str, result: &BatchResult, verbose: bool) {
+    if matches.machine_readable() {
+        let (level, message) = match (&result.error, result.skipped) {
+            (_, Some(count)) if verbose => (Level::Info, format!("skipped: {} opcodes", count)),
+            (_, Some(_)) => return,
+            (Some(err), None) => (Level::Error, format!("failed: {}", err)),
+            (None, None) => (
+                Level::Info,
+                format!(
+                    "ok: {} steps in {}",
+                    result.steps,
+                    format_duration(result.elapsed)
+                ),
+            ),
+        };
+        let data = Value::object([
+            ("file", filename.into()),
+            (
+                "output",
+                String::from_utf8_lossy(&result.output).into_owned().into(),
+            ),
+            ("steps", result.steps.into()),
+            ("elapsed_ns", (result.elapsed.as_nanos() as u64).into()),
+            ("error", result.error.clone().into()),
+            ("skipped", result.skipped.into()),
+        ]);
+        matches.report(level, &format!("{}: {}", filename, message), data);
+        return;
+    }
+
+    let mut stdout = io::stdout().lock();
+    if let Some(count) = result.skipped {
+        if verbose {
+            let _ = writeln!(stdout, "==> {} <==", filename);
+            let _ = writeln!(stdout, "skipped: {} opcodes", count);
+        }
+        return;
+    }
+    let _ = writeln!(stdout, "==> {} <==", filename);
+    let _ = stdout.write_all(&result.output);
+    if !result.output.is_empty() && !result.output.ends_with(b"\n") {
+        let _ = writeln!(stdout);
+    }
+    match &result.error {
+        Some(err) => {
+            let _ = writeln!(stdout, "failed: {}", err);
+        }
+        None => {
+            let _ = writeln!(
+                stdout,
+                "ok: {} steps in {}",
+                result.steps,
+                format_duration(result.elapsed)
+            );
+        }
+    }
+}
+
+// workers pull the next file index off a shared counter, results are printed in
+// argument order once everything finishes, or as each one completes with --stream
+fn cmd_batch(matches: &Matches) -> Result<(), CliError> {
+    let filenames = &matches.positional;
+    if filenames.is_empty() {
+        return Err("batch needs at least one program".into());
+    }
+    let workers = match matches.value("parallel") {
+        Some(value) => parse_number::<usize>("parallel", value)?,
+        None => 1,
+    };
+    if workers == 0 {
+        return Err("--parallel must be at least 1".into());
+    }
+    let config = interpreter_config(matches)?;
+    let stream = matches.flag("stream");
+    let verbose = matches.flag("verbose");
+    let min_opcodes = match matches.value("min-opcode-count") {
+        Some(value) => parse_number("min-opcode-count", value)?,
+        None => 0,
+    };
+    let max_opcodes = match matches.value("max-opcode-count") {
+        Some(value) => parse_number("max-opcode-count", value)?,
+        None => usize::MAX,
+    };
+    let opcodes = min_opcodes..=max_opcodes;
+
+    let started = Instant::now();
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    let mut results: Vec<Option<BatchResult>> = filenames.iter().map(|_| None).collect();
+
+    thread::scope(|scope| {
+        for _ in 0..workers.min(filenames.len()) {
+            let sender = sender.clone();
+            let (next, config, opcodes) = (&next, &config, &opcodes);
+            scope.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(filename) = filenames.get(i) else {
+                    break;
+                };
+                if sender
+                    .send((i, run_batch_program(filename, config, opcodes)))
+                    .is_err()
+                {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        for (i, result) in receiver {
+            if stream {
+                print_batch_result(matches, &filenames[i], &result, verbose);
+            }
+            results[i] = Some(result);
+        }
+    });
+
+    let results: Vec<BatchResult> = results.into_iter().flatten().collect();
+    if !stream {
+        for (filename, result) in filenames.iter().zip(&results) {
+            print_batch_result(matches, filename, result, verbose);
+        }
+    }
+
+    let failed = results
+        .iter()
+        .filter(|result| result.error.is_some())
+        .count();
+    let skipped = results
+        .iter()
+        .filter(|result| result.skipped.is_some())
+        .count();
+    let message = format!(
+        "{} programs, {} failed, {} skipped, {}",
+        results.len(),
+        failed,
+        skipped,
+        format_duration(started.elapsed())
+    );
+    if matches.machine_readable() {
+        let data = Value::object([
+            ("programs", results.len().into()),
+            ("failed", failed.into()),
+            ("skipped", skipped.into()),
+            ("elapsed_ns", (started.elapsed().as_nanos() as u64).into()),
+        ]);
+        matches.report(Level::Info, &message, data);
+    } else {
+        eprintln!("{}", message);
+    }
+    if failed > 0 {
+        return Err(format!("{} of {} programs failed", failed, results.len()).into());
+    }
+    Ok(())
+}
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
 
-            if let Some(op) = op {
-                operations.push(op);
+fn describe(instr: &Instruction) -> String {
+    match instr {
+        Instruction::Loop(body) => format!("LOOP ({} instructions)", body.len()),
+        Instruction::Add(n) => format!("ADD {}", n),
+        Instruction::Move(offset) => format!("MOVE {}", offset),
+        Instruction::SetCell(value) => format!("SET {}", value),
+        Instruction::BulkZero { start_offset, len } => format!("ZERO {} {}", start_offset, len),
+        Instruction::FindZero { direction } => format!("FIND {}", direction),
+        Instruction::MultiplyAdd { offset, factor } => format!("MULADD {} {}", offset, factor),
+        Instruction::CopyRange {
+            src_offset,
+            dst_offset,
+            len,
+        } => format!("COPY {} {} {}", src_offset, dst_offset, len),
+        Instruction::MacroCall { name, args } => format!("MACRO {} {:?}", name, args),
+        other => other.mnemonic().to_string(),
+    }
+}
+
+fn cmd_diff(matches: &Matches) -> Result<(), CliError> {
+    let a = Program::new(load_program(matches.arg(0, "<file1.svo>")?)?);
+    let b = Program::new(load_program(matches.arg(1, "<file2.svo>")?)?);
+
+    let entries = Program::diff(&a, &b);
+    if entries.is_empty() {
+        if matches.machine_readable() {
+            matches.report(
+                Level::Info,
+                "programs are identical",
+                Value::object::<&str>([]),
+            );
+        } else {
+            println!("programs are identical");
+        }
+        return Ok(());
+    }
+
+    let color = io::stdout().is_terminal() && !matches.machine_readable();
+    let paint = |code: &'static str| if color { code } else { "" };
+    let reset = paint(RESET);
+
+    for entry in &entries {
+        let (code, change, from, to) = match entry {
+            DiffEntry::Added(instr) => (GREEN, "added", None, Some(describe(instr))),
+            DiffEntry::Removed(instr) => (RED, "removed", Some(describe(instr)), None),
+            DiffEntry::Changed { from, to } => {
+                (YELLOW, "changed", Some(describe(from)), Some(describe(to)))
+            }
+        };
+        let line = match (&from, &to) {
+            (Some(from), Some(to)) => format!("~ {} -> {}", from, to),
+            (Some(from), None) => format!("- {}", from),
+            (None, to) => format!("+ {}", to.as_deref().unwrap_or_default()),
+        };
+        if matches.machine_readable() {
+            let data = Value::object([
+                ("change", change.into()),
+                ("from", from.into()),
+                ("to", to.into()),
+            ]);
+            matches.report(Level::Info, &line, data);
+        } else {
+            println!("{}{}{}", paint(code), line, reset);
+        }
+    }
+
+    Ok(())
+}
+
+// `line:column` of every opcode position in `source`, both counted from 1
+fn opcode_locations(source: &str) -> Vec<(usize, usize)> {
+    let (mut line, mut line_start, mut scanned) = (1, 0, 0);
+    tokenize(source)
+        .iter()
+        .filter(|token| token.op.is_some())
+        .map(|token| {
+            for (offset, c) in source[scanned..token.start].char_indices() {
+                if c == '\n' {
+                    line += 1;
+                    line_start = scanned + offset + 1;
+                }
+            }
+            scanned = token.start;
+            (line, source[line_start..token.start].chars().count() + 1)
+        })
+        .collect()
+}
+
+// like `diff`, but every entry says where it is in both files and differing programs
+// exit with 1, so layout and comments never count as a difference
+fn cmd_ast_diff(matches: &Matches) -> Result<(), CliError> {
+    let a_filename = matches.arg(0, "<file1.svo>")?;
+    let b_filename = matches.arg(1, "<file2.svo>")?;
+    let a_source = read_file(a_filename)?;
+    let b_source = read_file(b_filename)?;
+    let a = Program::new(parse_source(a_filename, &a_source)?);
+    let b = Program::new(parse_source(b_filename, &b_source)?);
+
+    let entries = Program::located_diff(&a, &b);
+    if entries.is_empty() {
+        if matches.machine_readable() {
+            matches.report(
+                Level::Info,
+                "programs are semantically identical",
+                Value::object::<&str>([]),
+            );
+        } else {
+            println!("programs are semantically identical");
+        }
+        return Ok(());
+    }
+
+    let (a_locations, b_locations) = (opcode_locations(&a_source), opcode_locations(&b_source));
+    let located = |filename: &str, locations: &[(usize, usize)], position: Option<usize>| {
+        position.map(|position| {
+            let (line, column) = locations[position];
+            format!("{}:{}:{}", filename, line, column)
+        })
+    };
+
+    for located_entry in &entries {
+        let from = located(a_filename, &a_locations, located_entry.from);
+        let to = located(b_filename, &b_locations, located_entry.to);
+        let line = match &located_entry.entry {
+            DiffEntry::Added(instr) => format!(
+                "+ {} at {}",
+                describe(instr),
+                to.as_deref().expect("added on the second side")
+            ),
+            DiffEntry::Removed(instr) => format!(
+                "- {} at {}",
+                describe(instr),
+                from.as_deref().expect("removed from the first side")
+            ),
+            DiffEntry::Changed { from: x, to: y } => format!(
+                "~ {} -> {} at {} / {}",
+                describe(x),
+                describe(y),
+                from.as_deref().expect("changed on both sides"),
+                to.as_deref().expect("changed on both sides")
+            ),
+        };
+        if matches.machine_readable() {
+            let data = Value::object([
+                ("from", from.into()),
+                ("to", to.into()),
+                ("from_position", located_entry.from.into()),
+                ("to_position", located_entry.to.into()),
+            ]);
+            matches.report(Level::Info, &line, data);
+        } else {
+            println!("{}", line);
+        }
+    }
+
+    Err(match entries.len() {
+        1 => "programs differ in 1 place".into(),
+        n => format!("programs differ in {} places", n).into(),
+    })
+}
+
+// first line of a file carrying its own checksum, a comment so the program is unchanged
+const CHECKSUM_PREFIX: &str = "# sha256: ";
+
+fn cmd_checksum(matches: &Matches) -> Result<(), CliError> {
+    let filename = matches.arg(0, "<file.svo>")?;
+    let source = read_file(filename)?;
+    let program = Program::new(parse_source(filename, &source)?);
+    let hash = hex::encode(&program.semantic_hash());
+
+    let (first, rest) = source.split_once('\n').unwrap_or((&source, ""));
+    let embedded = first.strip_prefix(CHECKSUM_PREFIX).map(str::trim);
+    let print = |line: String| {
+        if matches.machine_readable() {
+            let data = Value::object([("file", filename.into()), ("hash", hash.as_str().into())]);
+            matches.report(Level::Info, &line, data);
+        } else {
+            println!("{}", line);
+        }
+    };
+
+    match (matches.flag("embed"), matches.flag("verify")) {
+        (true, true) => Err("--embed and --verify can't be combined".into()),
+        (true, false) => {
+            if filename == "-" {
+                return Err("--embed needs a file to write to".into());
+            }
+            let body = if embedded.is_some() { rest } else { &source };
+            fs::write(filename, format!("{}{}\n{}", CHECKSUM_PREFIX, hash, body))
+                .map_err(|err| format!("failed to write {}: {}", filename, err))?;
+            print(format!("{}  {}", hash, filename));
+            Ok(())
+        }
+        (false, true) => match embedded {
+            None => Err(format!("{}: no embedded checksum", filename).into()),
+            Some(expected) if expected == hash => {
+                print(format!("{}: OK", filename));
+                Ok(())
             }
+            Some(expected) => Err(format!(
+                "{}: checksum mismatch, embedded {} but the program hashes to {}",
+                filename, expected, hash
+            )
+            .into()),
+        },
+        (false, false) => {
+            print(format!("{}  {}", hash, filename));
+            Ok(())
+        }
+    }
+}
+
+// keywords are self-delimiting, so even an empty separator lexes back to the same opcodes
+fn cmd_minify(matches: &Matches) -> Result<(), CliError> {
+    let filename = matches.arg(0, "<file.svo>")?;
+    let opcodes = lex(&read_file(filename)?);
+    parse(&opcodes).map_err(|err| format!("{}: {}", filename, err))?;
+
+    let separator = matches.value("separator").unwrap_or(" ");
+    let keywords: Vec<String> = opcodes.iter().map(OpCode::to_string).collect();
+    print!("{}", keywords.join(separator));
+    io::stdout()
+        .flush()
+        .map_err(|err| format!("failed to write output: {}", err).into())
+}
+
+// first line of everything `expand` writes, any comment in its input is dropped so
+// expanding its own output again gives the same file
+const EXPAND_HEADER: &str = "# generated by svolang expand";
+
+fn cmd_expand(matches: &Matches) -> Result<(), CliError> {
+    let filename = matches.arg(0, "<file.svo>")?;
+    let program = load_program(filename)?;
+    let body = expand::to_source(&program).map_err(|err| format!("{}: {}", filename, err))?;
+    let expanded = format::format(&format!("{}\n{}", EXPAND_HEADER, body))
+        .expect("expanded source has balanced loops");
+
+    match matches.value("o") {
+        Some(output) => fs::write(output, expanded)
+            .map_err(|err| format!("failed to write {}: {}", output, err).into()),
+        None => {
+            print!("{}", expanded);
+            Ok(())
+        }
+    }
+}
+
+fn cmd_lint(matches: &Matches) -> Result<(), CliError> {
+    let filename = matches.arg(0, "<file.svo>")?;
+    let source = read_file(filename)?;
+    let lints = lint::lint(&source).map_err(|err| parse_error(filename, err))?;
 
-            i = j;
+    let print = |lint: &lint::Lint, fixed: bool| {
+        let line = if fixed {
+            format!("Fixed: {}", lint.fix_message())
         } else {
-            i += 1;
+            format!("{}: {}", filename, lint)
+        };
+        if matches.machine_readable() {
+            let level = if fixed { Level::Info } else { Level::Warn };
+            let data = Value::object([
+                ("file", filename.into()),
+                ("kind", lint.kind.name().into()),
+                ("offset", lint.offset().into()),
+                ("fixable", lint.kind.fixable().into()),
+                ("fixed", fixed.into()),
+            ]);
+            matches.report(level, &line, data);
+        } else {
+            println!("{}", line);
+        }
+    };
+
+    if !matches.flag("fix") {
+        for lint in &lints {
+            print(lint, false);
         }
+        return Ok(());
     }
 
-    operations
+    if filename == "-" || archive::split_path(filename).is_some() {
+        return Err(format!("--fix can't write back to {}", filename).into());
+    }
+    if lints.iter().any(|lint| lint.kind.fixable()) {
+        fs::write(filename, lint::fix(&source, &lints))
+            .map_err(|err| format!("failed to write {}: {}", filename, err))?;
+    }
+    for lint in &lints {
+        print(lint, lint.kind.fixable());
+    }
+    Ok(())
 }
 
-fn parse(opcodes: Vec<OpCode>) -> Vec<Instruction> {
-    let mut program: Vec<Instruction> = Vec::new();
-    let mut loop_stack = 0;
-    let mut loop_start = 0;
+// what the shell should offer for a flag's value or a command's arguments
+enum Completion {
+    Nothing,
+    // empty for any file
+    Files(Vec<&'static str>),
+    Choices(Vec<&'static str>),
+}
 
-    for (i, op) in opcodes.iter().enumerate() {
-        if loop_stack == 0 {
-            let instr = match op {
-                OpCode::IncrementPointer => Some(Instruction::IncrementPointer),
-                OpCode::DecrementPointer => Some(Instruction::DecrementPointer),
-                OpCode::Increment => Some(Instruction::Increment),
-                OpCode::Decrement => Some(Instruction::Decrement),
-                OpCode::Write => Some(Instruction::Write),
-                OpCode::Read => Some(Instruction::Read),
+impl Completion {
+    // placeholders naming files complete paths, `a|b` lists the accepted words
+    // only the first of several values is completed
+    fn of_flag(flag: &Flag) -> Completion {
+        match flag.placeholders().first().copied() {
+            None => Completion::Nothing,
+            Some(value) if value.contains('|') => Completion::Choices(
+                value
+                    .split('|')
+                    // `seed|random` mixes a placeholder in with the keyword
+                    .filter(|choice| *choice != "seed")
+                    .collect(),
+            ),
+            Some(value) => match value.rsplit_once('.') {
+                Some((_, extension)) => Completion::Files(vec![extension]),
+                None if value.contains("file") || value == "output" => {
+                    Completion::Files(Vec::new())
+                }
+                None => Completion::Nothing,
+            },
+        }
+    }
 
-                OpCode::LoopBegin => {
-                    loop_start = i;
-                    loop_stack += 1;
-                    None
+    // `<file.bf> <file.svo>` completes .bf and .svo files, `<trace-file>` any file and
+    // `<a|b>` one of the words
+    fn of_args(args: &'static str) -> Completion {
+        if args.is_empty() {
+            return Completion::Nothing;
+        }
+        if let Some(choices) = args
+            .strip_prefix('<')
+            .and_then(|args| args.strip_suffix('>'))
+        {
+            if choices.contains('|') {
+                return Completion::Choices(choices.split('|').collect());
+            }
+        }
+        let mut extensions = Vec::new();
+        for arg in args.split_whitespace() {
+            let arg = arg.trim_start_matches('<').trim_end_matches("...");
+            if let Some((_, extension)) = arg.trim_end_matches('>').rsplit_once('.') {
+                if !extensions.contains(&extension) {
+                    extensions.push(extension);
                 }
+            }
+        }
+        Completion::Files(extensions)
+    }
+}
+
+fn flag_name(flag: &Flag) -> String {
+    let dashes = if flag.name.len() == 1 { "-" } else { "--" };
+    format!("{}{}", dashes, flag.name)
+}
+
+fn bash_completions() -> String {
+    let names: Vec<&str> = COMMANDS.iter().map(|command| command.name).collect();
+    let mut out = String::from("_svolang() {\n");
+    out.push_str(
+        "    local cur=\"${COMP_WORDS[COMP_CWORD]}\" prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n",
+    );
+    out.push_str("    if [ \"$COMP_CWORD\" -eq 1 ]; then\n");
+    out.push_str(&format!(
+        "        COMPREPLY=($(compgen -W \"--json-errors --machine-readable {}\" -- \"$cur\"))\n",
+        names.join(" ")
+    ));
+    out.push_str("        return\n    fi\n");
+    out.push_str("    local flags=\"\" files=\"\" any_file=\"\"\n");
+    out.push_str("    case \"${COMP_WORDS[1]}\" in\n");
 
-                OpCode::LoopEnd => panic!("loop ending at #{} has no beginning", i),
+    for command in COMMANDS {
+        let flags: Vec<String> = command.flags.iter().map(flag_name).collect();
+        out.push_str(&format!("        {})\n", command.name));
+        out.push_str(&format!("            flags=\"{}\"\n", flags.join(" ")));
+        match Completion::of_args(command.args) {
+            Completion::Files(extensions) if extensions.is_empty() => {
+                out.push_str("            any_file=1\n")
+            }
+            Completion::Files(extensions) => {
+                out.push_str(&format!("            files=\"{}\"\n", extensions.join(" ")))
+            }
+            Completion::Choices(choices) => out.push_str(&format!(
+                "            [ \"$COMP_CWORD\" -eq 2 ] && COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")) && return\n",
+                choices.join(" ")
+            )),
+            Completion::Nothing => (),
+        }
+
+        let mut cases = String::new();
+        for flag in command.flags {
+            let reply = match Completion::of_flag(flag) {
+                Completion::Nothing if flag.value.is_none() => continue,
+                Completion::Nothing => "return".to_string(),
+                Completion::Files(extensions) if extensions.is_empty() => {
+                    "COMPREPLY=($(compgen -f -- \"$cur\")); return".to_string()
+                }
+                Completion::Files(extensions) => format!(
+                    "COMPREPLY=($(compgen -f -X '!*.{}' -- \"$cur\")); return",
+                    extensions[0]
+                ),
+                Completion::Choices(choices) => format!(
+                    "COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return",
+                    choices.join(" ")
+                ),
             };
+            cases.push_str(&format!(
+                "                {}) {} ;;\n",
+                flag_name(flag),
+                reply
+            ));
+        }
+        if !cases.is_empty() {
+            out.push_str("            case \"$prev\" in\n");
+            out.push_str(&cases);
+            out.push_str("            esac\n");
+        }
+        out.push_str("            ;;\n");
+    }
 
-            match instr {
-                Some(instr) => program.push(instr),
-                None => (),
+    out.push_str("    esac\n");
+    out.push_str("    if [[ \"$cur\" == -* ]]; then\n");
+    out.push_str("        COMPREPLY=($(compgen -W \"$flags --json-errors --machine-readable\" -- \"$cur\"))\n");
+    out.push_str("    elif [ -n \"$any_file\" ]; then\n");
+    out.push_str("        COMPREPLY=($(compgen -f -- \"$cur\"))\n");
+    out.push_str("    else\n");
+    out.push_str("        local extension\n");
+    out.push_str("        for extension in $files; do\n");
+    out.push_str("            COMPREPLY+=($(compgen -f -X \"!*.$extension\" -- \"$cur\"))\n");
+    out.push_str("        done\n");
+    out.push_str("    fi\n");
+    out.push_str("}\n");
+    out.push_str("complete -o filenames -o plusdirs -F _svolang svolang\n");
+    out
+}
+
+fn zsh_completions() -> String {
+    let names: Vec<&str> = COMMANDS.iter().map(|command| command.name).collect();
+    let mut out = String::from("#compdef svolang\n\n_svolang() {\n");
+    out.push_str(&format!("    local -a commands=({})\n", names.join(" ")));
+    out.push_str("    if (( CURRENT == 2 )); then\n");
+    out.push_str("        compadd -- --json-errors --machine-readable $commands\n");
+    out.push_str("        return\n    fi\n");
+    out.push_str("    local command=$words[2]\n");
+    out.push_str("    shift words\n    (( CURRENT-- ))\n");
+    out.push_str("    case $command in\n");
+
+    for command in COMMANDS {
+        let mut specs = vec![
+            "'--json-errors'".to_string(),
+            "'--machine-readable'".to_string(),
+        ];
+        for flag in command.flags {
+            let name = flag_name(flag);
+            specs.push(match (Completion::of_flag(flag), flag.value) {
+                (_, None) => format!("'{}'", name),
+                (Completion::Files(extensions), Some(value)) if extensions.is_empty() => {
+                    format!("'{}:{}:_files'", name, value)
+                }
+                (Completion::Files(extensions), Some(value)) => {
+                    format!("'{}:{}:_files -g \"*.{}\"'", name, value, extensions[0])
+                }
+                (Completion::Choices(choices), Some(value)) => {
+                    format!("'{}:{}:({})'", name, value, choices.join(" "))
+                }
+                (Completion::Nothing, Some(value)) => format!("'{}:{}: '", name, value),
+            });
+        }
+        match Completion::of_args(command.args) {
+            Completion::Files(extensions) if extensions.is_empty() => {
+                specs.push("'*:file:_files'".to_string())
             }
-        } else {
-            match op {
-                OpCode::LoopBegin => {
-                    loop_stack += 1;
+            Completion::Files(extensions) if extensions.len() == 1 => {
+                specs.push(format!("'*:file:_files -g \"*.{}\"'", extensions[0]))
+            }
+            Completion::Files(extensions) => specs.push(format!(
+                "'*:file:_files -g \"*.({})\"'",
+                extensions.join("|")
+            )),
+            Completion::Choices(choices) => {
+                specs.push(format!("':{}:({})'", command.args, choices.join(" ")))
+            }
+            Completion::Nothing => (),
+        }
+
+        out.push_str(&format!("        {})\n", command.name));
+        out.push_str(&format!("            _arguments {}\n", specs.join(" ")));
+        out.push_str("            ;;\n");
+    }
+
+    out.push_str("    esac\n}\n\n_svolang \"$@\"\n");
+    out
+}
+
+fn fish_completions() -> String {
+    let names: Vec<&str> = COMMANDS.iter().map(|command| command.name).collect();
+    let mut out = String::from("complete -c svolang -f\n");
+    out.push_str("complete -c svolang -l json-errors\n");
+    out.push_str("complete -c svolang -l machine-readable\n");
+    out.push_str(&format!(
+        "complete -c svolang -n __fish_use_subcommand -a '{}'\n",
+        names.join(" ")
+    ));
+
+    for command in COMMANDS {
+        let condition = format!("-n '__fish_seen_subcommand_from {}'", command.name);
+        for flag in command.flags {
+            let name = if flag.name.len() == 1 {
+                format!("-s {}", flag.name)
+            } else {
+                format!("-l {}", flag.name)
+            };
+            let value = match Completion::of_flag(flag) {
+                Completion::Nothing if flag.value.is_none() => String::new(),
+                Completion::Nothing => " -x".to_string(),
+                Completion::Files(extensions) if extensions.is_empty() => " -r -F".to_string(),
+                Completion::Files(extensions) => {
+                    format!(" -x -a '(__fish_complete_suffix .{})'", extensions[0])
                 }
-                OpCode::LoopEnd => {
-                    loop_stack -= 1;
-
-                    if loop_stack == 0 {
-                        program.push(Instruction::Loop(parse(
-                            opcodes[loop_start + 1..i].to_vec(),
-                        )));
-                    }
+                Completion::Choices(choices) => format!(" -x -a '{}'", choices.join(" ")),
+            };
+            out.push_str(&format!(
+                "complete -c svolang {} {}{}\n",
+                condition, name, value
+            ));
+        }
+        match Completion::of_args(command.args) {
+            Completion::Files(extensions) if extensions.is_empty() => {
+                out.push_str(&format!("complete -c svolang {} -F\n", condition))
+            }
+            Completion::Files(extensions) => {
+                for extension in extensions {
+                    out.push_str(&format!(
+                        "complete -c svolang {} -a '(__fish_complete_suffix .{})'\n",
+                        condition, extension
+                    ));
                 }
-                _ => (),
             }
+            Completion::Choices(choices) => out.push_str(&format!(
+                "complete -c svolang {} -a '{}'\n",
+                condition,
+                choices.join(" ")
+            )),
+            Completion::Nothing => (),
+        }
+    }
+
+    out
+}
+
+// positional arguments fall back to PowerShell's own path completion
+fn powershell_completions() -> String {
+    let mut out =
+        String::from("Register-ArgumentCompleter -Native -CommandName svolang -ScriptBlock {\n");
+    out.push_str("    param($wordToComplete, $commandAst, $cursorPosition)\n");
+    out.push_str("    $commands = @{\n");
+    for command in COMMANDS {
+        let flags: Vec<String> = command
+            .flags
+            .iter()
+            .map(|flag| format!("'{}'", flag_name(flag)))
+            .collect();
+        out.push_str(&format!(
+            "        '{}' = @({})\n",
+            command.name,
+            flags.join(", ")
+        ));
+    }
+    out.push_str("    }\n");
+    out.push_str(
+        "    $words = @($commandAst.CommandElements | ForEach-Object { $_.ToString() })\n",
+    );
+    out.push_str("    if ($words.Count -lt 2 -or ($words.Count -eq 2 -and $wordToComplete)) {\n");
+    out.push_str("        $candidates = @('--json-errors', '--machine-readable') + @($commands.Keys | Sort-Object)\n");
+    out.push_str("    } elseif ($wordToComplete -like '-*') {\n");
+    out.push_str(
+        "        $candidates = @('--json-errors', '--machine-readable') + $commands[$words[1]]\n",
+    );
+    out.push_str("    } else {\n");
+    out.push_str("        return\n");
+    out.push_str("    }\n");
+    out.push_str(
+        "    $candidates | Where-Object { $_ -like \"$wordToComplete*\" } | ForEach-Object {\n",
+    );
+    out.push_str("        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)\n");
+    out.push_str("    }\n");
+    out.push_str("}\n");
+    out
+}
+
+// prints what formatting would change as a unified diff, and fails if anything would
+fn cmd_format_check(matches: &Matches) -> Result<(), CliError> {
+    let filenames = &matches.positional;
+    if filenames.is_empty() {
+        return Err("format-check needs at least one program".into());
+    }
+
+    let mut unformatted = Vec::new();
+    for filename in filenames {
+        let source = read_file(filename)?;
+        let formatted = format::format(&source).map_err(|err| parse_error(filename, err))?;
+        let diff = format::unified_diff(
+            &source,
+            &formatted,
+            filename,
+            &format!("{} (formatted)", filename),
+        );
+        if diff.is_empty() {
+            continue;
+        }
+        if matches.machine_readable() {
+            let data = Value::object([("file", filename.as_str().into()), ("diff", diff.into())]);
+            matches.report(Level::Warn, &format!("{} is not formatted", filename), data);
+        } else {
+            print!("{}", diff);
+        }
+        unformatted.push(filename.as_str());
+    }
+
+    match unformatted.as_slice() {
+        [] => Ok(()),
+        [filename] => Err(format!("{} is not formatted", filename).into()),
+        filenames => Err(format!("{} files are not formatted", filenames.len()).into()),
+    }
+}
+
+const HELLO_WORLD: &str = "Hello, World!\n";
+
+// one cell stepped from each character to the next, laid out like format-check wants
+fn hello_world() -> String {
+    let mut generator = Generator::new();
+    let mut current = 0u8;
+    for byte in HELLO_WORLD.bytes() {
+        let delta = byte.wrapping_sub(current);
+        if delta <= 128 {
+            generator.increment(delta);
+        } else {
+            generator.decrement(delta.wrapping_neg());
+        }
+        generator.write();
+        current = byte;
+    }
+
+    let keywords: Vec<String> = generator.opcodes().iter().map(OpCode::to_string).collect();
+    let source = format!("# prints {:?}\n{}", HELLO_WORLD, keywords.join(" "));
+    format::format(&source).expect("the program has no loops to mismatch")
+}
+
+// refuses to touch a directory that's already there, like `cargo new`
+fn cmd_init(matches: &Matches) -> Result<(), CliError> {
+    let name = matches.arg(0, "<project-name>")?;
+    let root = Path::new(name);
+    if root.exists() {
+        return Err(format!("{} already exists", name).into());
+    }
+
+    let test = format!(
+        "# runs the program with `input` and compares what it prints with `expected_output`\n\
+         program = \"../main.svo\"\n\
+         input = \"\"\n\
+         expected_output = {:?}\n",
+        HELLO_WORLD
+    );
+    let files = [
+        ("main.svo", hello_world()),
+        ("svolang.toml", InterpreterConfig::default().to_toml()),
+        (".gitignore", "*.svoc\n".to_string()),
+        ("tests/hello.svot", test),
+    ];
+    for (path, contents) in files {
+        let path = root.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| format!("failed to create {}: {}", parent.display(), err))?;
         }
+        fs::write(&path, contents)
+            .map_err(|err| format!("failed to write {}: {}", path.display(), err))?;
+    }
+
+    if matches.machine_readable() {
+        matches.report(
+            Level::Info,
+            &format!("created project {}", name),
+            Value::object([("path", name.into())]),
+        );
+    } else {
+        println!("created project {}", name);
     }
+    Ok(())
+}
+
+// what the environment offers, `ok` is `None` for lines that are only information
+struct Check {
+    name: &'static str,
+    ok: Option<bool>,
+    detail: String,
+}
+
+fn cmd_doctor(matches: &Matches) -> Result<(), CliError> {
+    let compiler = codegen::compiler();
+    // `cc --version` starts with a line naming the compiler, which is all that's shown
+    let compiler_check = match process::Command::new(&compiler).arg("--version").output() {
+        Ok(output) if output.status.success() => {
+            let version = String::from_utf8_lossy(&output.stdout);
+            let version = version.lines().next().unwrap_or_default();
+            (true, format!("{}, {}", compiler, version))
+        }
+        Ok(output) => (false, format!("{} exited with {}", compiler, output.status)),
+        Err(err) => (
+            false,
+            format!("{} not found, pack and compile need it: {}", compiler, err),
+        ),
+    };
 
-    if loop_stack != 0 {
-        panic!(
-            "loop that starts at #{} has no matching ending!",
-            loop_start
+    let program = parse(&lex(&hello_world())).expect("the hello world program parses");
+    let config = InterpreterConfig::default();
+    let optimized = optimize::optimize(program.clone());
+    let self_test = [("plain", program), ("optimized", optimized)]
+        .iter()
+        .try_for_each(
+            |(name, program)| match run_with_input(program, &config, &[]) {
+                Ok(output) if output == HELLO_WORLD.as_bytes() => Ok(()),
+                Ok(output) => Err(format!(
+                    "{} hello world printed {:?}",
+                    name,
+                    String::from_utf8_lossy(&output)
+                )),
+                Err(err) => Err(format!("{} hello world failed: {}", name, err)),
+            },
         );
+
+    let checks = [
+        Check {
+            name: "svolang",
+            ok: None,
+            detail: env!("CARGO_PKG_VERSION").to_string(),
+        },
+        Check {
+            name: "rustc",
+            ok: None,
+            detail: env!("SVOLANG_RUSTC_VERSION").to_string(),
+        },
+        Check {
+            name: "platform",
+            ok: None,
+            detail: format!("{} {}", env::consts::OS, env::consts::ARCH),
+        },
+        Check {
+            name: "jit",
+            ok: None,
+            detail: "not available, programs are interpreted or compiled ahead of time".to_string(),
+        },
+        Check {
+            name: "simd",
+            ok: None,
+            detail: simd::instruction_set().to_string(),
+        },
+        Check {
+            name: "c compiler",
+            ok: Some(compiler_check.0),
+            detail: compiler_check.1,
+        },
+        Check {
+            name: "self-test",
+            ok: Some(self_test.is_ok()),
+            detail: self_test
+                .err()
+                .unwrap_or_else(|| "hello world ran".to_string()),
+        },
+    ];
+
+    for check in &checks {
+        if matches.machine_readable() {
+            let level = match check.ok {
+                Some(false) => Level::Error,
+                _ => Level::Info,
+            };
+            matches.report(
+                level,
+                &format!("{}: {}", check.name, check.detail),
+                Value::object([
+                    ("check", check.name.into()),
+                    ("ok", check.ok.into()),
+                    ("detail", check.detail.as_str().into()),
+                ]),
+            );
+        } else {
+            let marker = match check.ok {
+                Some(true) => "[ok]",
+                Some(false) => "[!!]",
+                None => "[--]",
+            };
+            println!("{} {}: {}", marker, check.name, check.detail);
+        }
+    }
+
+    let failed = checks
+        .iter()
+        .filter(|check| check.ok == Some(false))
+        .count();
+    if failed > 0 {
+        return Err(format!("{} of the checks failed", failed).into());
+    }
+    Ok(())
+}
+
+// generated from `COMMANDS`, so new commands and flags show up without touching this
+fn cmd_install_completions(matches: &Matches) -> Result<(), CliError> {
+    let shell = matches.arg(0, "<bash|zsh|fish|powershell>")?;
+    let script = match shell {
+        "bash" => bash_completions(),
+        "zsh" => zsh_completions(),
+        "fish" => fish_completions(),
+        "powershell" => powershell_completions(),
+        _ => {
+            return Err(format!(
+                "unknown shell `{}`, expected bash, zsh, fish or powershell",
+                shell
+            )
+            .into())
+        }
+    };
+    print!("{}", script);
+    Ok(())
+}
+
+const DEFAULT_PORT: u16 = 8080;
+
+// listens on localhost only, put a reverse proxy in front to expose it
+fn cmd_serve(matches: &Matches) -> Result<(), CliError> {
+    let port = match matches.value("port") {
+        Some(value) => parse_number("port", value)?,
+        None => DEFAULT_PORT,
+    };
+    let config = interpreter_config(matches)?;
+    if config.max_steps.is_none() && config.timeout.is_none() {
+        warn!("neither --max-steps nor --timeout is set, a looping program will hold its connection forever");
     }
 
-    program
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .map_err(|err| format!("failed to listen on port {}: {}", port, err))?;
+    eprintln!("listening on http://127.0.0.1:{}", port);
+    serve::serve(listener, config).map_err(|err| format!("server: {}", err).into())
 }
 
-fn run(instructions: &Vec<Instruction>, tape: &mut Vec<u8>, data_pointer: &mut usize) {
-    for instr in instructions {
-        match instr {
-            Instruction::IncrementPointer => *data_pointer += 1,
-            Instruction::DecrementPointer => *data_pointer -= 1,
-            Instruction::Increment => tape[*data_pointer] += 1,
-            Instruction::Decrement => tape[*data_pointer] -= 1,
-            Instruction::Write => print!("{}", tape[*data_pointer] as char),
-            Instruction::Read => {
-                let mut input: [u8; 1] = [0; 1];
-                std::io::stdin()
-                    .read_exact(&mut input)
-                    .expect("failed to read stdin");
-                tape[*data_pointer] = input[0];
+const HISTORY_FILE: &str = ".svolang_history";
+
+// every line runs on the same interpreter, so the tape carries over from one to the next,
+// a line that leaves a loop open is continued on the next one
+fn cmd_repl(matches: &Matches) -> Result<(), CliError> {
+    let history_file = match matches.value("history-file") {
+        Some(path) => PathBuf::from(path),
+        None => env::var_os("HOME")
+            .map(|home| Path::new(&home).join(HISTORY_FILE))
+            .ok_or("HOME is not set, pass --history-file")?,
+    };
+
+    let mut editor =
+        DefaultEditor::new().map_err(|err| format!("failed to start the repl: {}", err))?;
+    // one entered line per line of the file, kept as typed
+    let history = match fs::read_to_string(&history_file) {
+        Ok(history) => history,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => {
+            return Err(format!("failed to read {}: {}", history_file.display(), err).into())
+        }
+    };
+    for line in history.lines() {
+        let _ = editor.add_history_entry(line);
+    }
+    println!(
+        "loaded {} history lines from {}",
+        history.lines().count(),
+        history_file.display()
+    );
+    let mut history = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&history_file)
+        .map_err(|err| format!("failed to open {}: {}", history_file.display(), err))?;
+
+    let mut interpreter = Interpreter::new(interpreter_config(matches)?);
+    let mut pending = String::new();
+    loop {
+        let prompt = if pending.is_empty() { "svo> " } else { "...> " };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => {
+                pending.clear();
+                continue;
             }
-            Instruction::Loop(nested_instructions) => {
-                while tape[*data_pointer] != 0 {
-                    run(&nested_instructions, tape, data_pointer)
-                }
+            Err(ReadlineError::Eof) => return Ok(()),
+            Err(err) => return Err(format!("failed to read input: {}", err).into()),
+        };
+        if !line.trim().is_empty() {
+            let _ = editor.add_history_entry(line.as_str());
+            writeln!(history, "{}", line)
+                .map_err(|err| format!("failed to write {}: {}", history_file.display(), err))?;
+        }
+
+        if let Some(command) = line.trim().strip_prefix('.').filter(|_| pending.is_empty()) {
+            if let Err(err) = repl_command(&mut interpreter, command) {
+                eprintln!("{}", err);
             }
+            continue;
+        }
+
+        pending.push_str(&line);
+        pending.push('\n');
+        let instructions = match parse(&lex(&pending)) {
+            Ok(instructions) => instructions,
+            Err(ParseError::UnmatchedLoopBegin { .. }) => continue,
+            Err(err) => {
+                eprintln!("{}", err);
+                pending.clear();
+                continue;
+            }
+        };
+        pending.clear();
+
+        if let Err(err) = interpreter.run(&instructions) {
+            eprintln!("{}", runtime_error(err));
         }
     }
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+// a line starting with `.` is a command for the repl rather than svo source
+fn repl_command(interpreter: &mut Interpreter, command: &str) -> Result<(), String> {
+    let args: Vec<&str> = command.split_whitespace().collect();
+    let tape = interpreter.tape_mut();
 
-    if args.len() < 3 {
-        println!("usage: svolang\n\trun <file.svo>\n\ttranslate <file.bf> <file.svo>");
-        std::process::exit(1);
+    match args.as_slice() {
+        ["inspect", "*"] => {
+            for (index, cell) in tape.iter_nonzero() {
+                println!("{}", describe_cell(index, cell));
+            }
+        }
+        ["inspect", "range", start, end] => {
+            let range = repl_range(tape, start, end)?;
+            for index in range {
+                println!("{}", describe_cell(index, tape.cells()[index]));
+            }
+        }
+        ["inspect", offset] => {
+            let index = repl_cell(tape, parse_repl_number(offset)?)?;
+            println!("{}", describe_cell(index, tape.cells()[index]));
+        }
+        ["inspect", ..] => {
+            return Err(
+                "usage: .inspect <offset> | .inspect * | .inspect range <start> <end>".to_string(),
+            )
+        }
+        ["set", offset, value] => {
+            let index = repl_cell(tape, parse_repl_number(offset)?)?;
+            let value = match value.strip_prefix("0x") {
+                Some(hex) => u8::from_str_radix(hex, 16).ok(),
+                None => value.parse().ok(),
+            }
+            .ok_or_else(|| {
+                format!(
+                    "invalid cell value `{}`, expected 0-255 or 0x00-0xff",
+                    value
+                )
+            })?;
+            tape.cells_mut()[index] = value;
+        }
+        ["set-pointer", index] => {
+            let index: usize = parse_repl_number(index)?;
+            let offset = index as isize - tape.pointer() as isize;
+            tape.move_by(offset)
+                .map_err(|_| format!("cell {} is not on the tape", index))?;
+        }
+        ["zero", start, end] => {
+            let range = repl_range(tape, start, end)?;
+            tape.cells_mut()[range].fill(0);
+        }
+        ["load", _, ..] => {
+            // everything after `load`, so paths may contain spaces
+            let filename = command.trim_start()["load".len()..].trim();
+            let program = load_program(filename).map_err(|err| err.to_string())?;
+            let snapshot = tape.clone();
+            if let Err(err) = interpreter.run(&program) {
+                *interpreter.tape_mut() = snapshot;
+                return Err(format!("{}, tape rolled back", runtime_error(err)));
+            }
+        }
+        ["set", ..] => return Err("usage: .set <offset> <value>".to_string()),
+        ["set-pointer", ..] => return Err("usage: .set-pointer <index>".to_string()),
+        ["zero", ..] => return Err("usage: .zero <start> <end>".to_string()),
+        ["load"] => return Err("usage: .load <file.svo>".to_string()),
+        _ => return Err(format!("unknown command .{}", command)),
     }
 
-    let command = &args[1];
+    Ok(())
+}
+
+// absolute cells `start` to `end`, both included
+fn repl_range(tape: &Tape, start: &str, end: &str) -> Result<RangeInclusive<usize>, String> {
+    let start: usize = parse_repl_number(start)?;
+    let end: usize = parse_repl_number(end)?;
+    if start > end || end >= tape.cells().len() {
+        return Err(format!("{}..={} is not on the tape", start, end));
+    }
+    Ok(start..=end)
+}
 
-    if command == "run" {
-        let filename = &args[2];
-        let mut file = File::open(filename).expect("program file not found");
-        let mut source = String::new();
-        file.read_to_string(&mut source)
-            .expect("failed to read program file");
+fn parse_repl_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid number `{}`", value))
+}
 
-        let opcodes = lex(source);
+// the index `offset` cells from the pointer
+fn repl_cell(tape: &Tape, offset: isize) -> Result<usize, String> {
+    tape.pointer()
+        .checked_add_signed(offset)
+        .filter(|&index| index < tape.cells().len())
+        .ok_or_else(|| format!("offset {} leaves the tape", offset))
+}
 
-        let program = parse(opcodes);
+fn describe_cell(index: usize, cell: u8) -> String {
+    let ascii = if cell.is_ascii_graphic() || cell == b' ' {
+        cell as char
+    } else {
+        '.'
+    };
+    format!("{:>6}: {:>3}  0x{:02x}  {}", index, cell, cell, ascii)
+}
 
-        let mut tape: Vec<u8> = vec![0; 1024];
-        let mut data_pointer = 512;
+// `RUST_LOG` takes a single level, warnings and errors show by default
+fn init_logging() {
+    let level = env::var("RUST_LOG")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(LevelFilter::WARN);
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal())
+        .with_target(false)
+        .without_time()
+        .init();
+}
 
-        run(&program, &mut tape, &mut data_pointer);
-    } else if command == "translate" {
-        let from_filename = &args[2];
-        let to_filename = &args[3];
+fn main() {
+    init_logging();
+    let mut args: Vec<String> = env::args().collect();
+    // global, accepted anywhere on the command line
+    let json_errors = args.iter().any(|arg| arg == "--json-errors");
+    let machine_readable = args.iter().any(|arg| arg == "--machine-readable");
+    args.retain(|arg| arg != "--json-errors" && arg != "--machine-readable");
 
-        let mut file = File::open(from_filename).expect("program file not found");
-        let mut source = String::new();
-        file.read_to_string(&mut source)
-            .expect("failed to read program file");
+    let Some(command) = args
+        .get(1)
+        .and_then(|name| COMMANDS.iter().find(|command| command.name == name))
+    else {
+        let err = CliError::from(match args.get(1) {
+            Some(name) => format!("unknown command `{}`", name),
+            None => "missing command".to_string(),
+        });
+        if machine_readable {
+            let data = err.to_json();
+            println!(
+                "{}",
+                machine_message(None, Level::Error, &err.message, data)
+            );
+        } else {
+            println!("{}", usage());
+        }
+        if json_errors {
+            eprintln!("{}", err.to_json());
+        }
+        process::exit(1);
+    };
 
-        let result = source
-            .replace("+", "svo")
-            .replace("-", "svoo")
-            .replace("[", "svooo")
-            .replace("]", "svoooo")
-            .replace("<", "svooooo")
-            .replace(">", "svoooooo")
-            .replace(".", "svooooooo")
-            .replace(",", "svoooooooo");
+    let result = Matches::parse(&args[2..], command.flags)
+        .map_err(CliError::from)
+        .and_then(|mut matches| {
+            if machine_readable {
+                matches.machine_readable = Some(command.name);
+            }
+            (command.handler)(&matches)
+        });
 
-        let mut file_write = File::create(to_filename).expect("error create svo file");
-        file_write
-            .write(result.as_bytes())
-            .expect("error write to svo file");
+    if let Err(err) = result {
+        if machine_readable {
+            let data = err.to_json();
+            let message = machine_message(Some(command.name), Level::Error, &err.message, data);
+            println!("{}", message);
+        } else if json_errors {
+            eprintln!("{}", err.to_json());
+        } else {
+            error!("{}", err);
+        }
+        process::exit(1);
     }
 }
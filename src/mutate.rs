@@ -0,0 +1,117 @@
+use std::fmt;
+
+use crate::parser::Instruction;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MutationKind {
+    Remove,
+    Flip,
+    SkipLoop,
+}
+
+// a single-instruction change, `position` is the opcode index the instruction starts at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mutation {
+    pub position: usize,
+    pub kind: MutationKind,
+    pub op: &'static str,
+}
+
+impl fmt::Display for Mutation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let verb = match self.kind {
+            MutationKind::Remove => "remove",
+            MutationKind::Flip => "flip",
+            MutationKind::SkipLoop => "skip",
+        };
+        write!(f, "{} {} at #{}", verb, self.op, self.position)
+    }
+}
+
+fn flipped(instr: &Instruction) -> Option<Instruction> {
+    match instr {
+        Instruction::Increment => Some(Instruction::Decrement),
+        Instruction::Decrement => Some(Instruction::Increment),
+        Instruction::IncrementPointer => Some(Instruction::DecrementPointer),
+        Instruction::DecrementPointer => Some(Instruction::IncrementPointer),
+        Instruction::Add(n) => Some(Instruction::Add(n.wrapping_neg())),
+        Instruction::Move(offset) => Some(Instruction::Move(-offset)),
+        _ => None,
+    }
+}
+
+pub fn mutations(instructions: &[Instruction]) -> Vec<Mutation> {
+    let mut found = Vec::new();
+    collect(instructions, 0, &mut found);
+    found
+}
+
+fn collect(instructions: &[Instruction], mut position: usize, found: &mut Vec<Mutation>) {
+    for instr in instructions {
+        let op = instr.mnemonic();
+
+        match instr {
+            Instruction::Loop(body) => {
+                found.push(Mutation {
+                    position,
+                    kind: MutationKind::SkipLoop,
+                    op,
+                });
+                collect(body, position + 1, found);
+            }
+            // covers no opcode, removing it changes nothing the tests can see
+            Instruction::DebugPrint { .. } => (),
+            _ => {
+                found.push(Mutation {
+                    position,
+                    kind: MutationKind::Remove,
+                    op,
+                });
+                if flipped(instr).is_some() {
+                    found.push(Mutation {
+                        position,
+                        kind: MutationKind::Flip,
+                        op,
+                    });
+                }
+            }
+        }
+
+        position += instr.opcode_len();
+    }
+}
+
+pub fn apply(instructions: &[Instruction], mutation: &Mutation) -> Vec<Instruction> {
+    apply_at(instructions, 0, mutation)
+}
+
+fn apply_at(
+    instructions: &[Instruction],
+    mut position: usize,
+    mutation: &Mutation,
+) -> Vec<Instruction> {
+    let mut mutated = Vec::with_capacity(instructions.len());
+
+    for instr in instructions {
+        let len = instr.opcode_len();
+
+        // a debug print shares its position with the opcode after it
+        if len > 0 && position == mutation.position {
+            if mutation.kind == MutationKind::Flip {
+                mutated.extend(flipped(instr));
+            }
+        } else if let Instruction::Loop(body) = instr {
+            if (position..position + len).contains(&mutation.position) {
+                mutated.push(Instruction::Loop(apply_at(body, position + 1, mutation)));
+            } else {
+                mutated.push(instr.clone());
+            }
+        } else {
+            mutated.push(instr.clone());
+        }
+
+        position += len;
+    }
+
+    mutated
+}
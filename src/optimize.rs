@@ -0,0 +1,335 @@
+use std::collections::HashSet;
+
+use tracing::debug;
+
+use crate::dataflow::DataFlowAnalyzer;
+use crate::expand::Macro;
+use crate::loops::{LoopAnalyzer, LoopKind};
+use crate::parser::Instruction;
+
+// a rewrite of a program into one that behaves the same, every pass can run on its own
+// and on the output of any other
+pub trait Optimizer {
+    fn optimize(&self, instructions: Vec<Instruction>) -> Vec<Instruction>;
+}
+
+// folds runs of increments and pointer moves into single adds and moves, and an add into
+// the set before it
+pub struct CollapseRuns;
+
+// turns `[-]` / `[+]` into a cell clear and runs of cleared neighbours into a bulk clear
+pub struct ClearCellOpt;
+
+// turns `[>]` / `[<<]` into a zero search
+pub struct ScanOpt;
+
+// turns `[->+<]` / `[->+++<]` into a multiply-add
+pub struct CopyOpt;
+
+// drops the writes `DataFlowAnalyzer` finds a later write replaces before anything looks at
+// the cell
+pub struct DeadStoreElim;
+
+// drops zero adds and moves, and clears, searches and loops on a cell that's already zero
+pub struct NoopElim;
+
+// replaces calls to built-in macros with what they stand for, calls that don't name one or
+// have the wrong arguments are left to fail when they run
+pub struct MacroExpander;
+
+// passes applied one after the other
+pub struct OptimizerPipeline(pub Vec<Box<dyn Optimizer>>);
+
+impl OptimizerPipeline {
+    pub const MAX_LEVEL: u8 = 3;
+
+    // 0 leaves the program alone, 1 only expands macros and folds runs, 2 is what
+    // `optimize` does and 3 adds multiply-adds and dead store elimination
+    pub fn level(level: u8) -> Option<OptimizerPipeline> {
+        let passes: Vec<Box<dyn Optimizer>> = match level {
+            0 => vec![],
+            1 => vec![
+                Box::new(MacroExpander),
+                Box::new(CollapseRuns),
+                Box::new(NoopElim),
+            ],
+            2 => vec![
+                Box::new(MacroExpander),
+                Box::new(CollapseRuns),
+                Box::new(ClearCellOpt),
+                Box::new(ScanOpt),
+                Box::new(NoopElim),
+            ],
+            3 => vec![
+                Box::new(MacroExpander),
+                Box::new(CollapseRuns),
+                Box::new(ClearCellOpt),
+                Box::new(ScanOpt),
+                Box::new(CopyOpt),
+                Box::new(DeadStoreElim),
+                Box::new(NoopElim),
+            ],
+            _ => return None,
+        };
+        Some(OptimizerPipeline(passes))
+    }
+}
+
+impl Optimizer for OptimizerPipeline {
+    fn optimize(&self, instructions: Vec<Instruction>) -> Vec<Instruction> {
+        self.0.iter().fold(instructions, |instructions, pass| {
+            pass.optimize(instructions)
+        })
+    }
+}
+
+// the default pipeline, `OptimizerPipeline::level(2)`
+pub fn optimize(instructions: Vec<Instruction>) -> Vec<Instruction> {
+    OptimizerPipeline::level(2)
+        .expect("level 2 exists")
+        .optimize(instructions)
+}
+
+impl Optimizer for MacroExpander {
+    fn optimize(&self, instructions: Vec<Instruction>) -> Vec<Instruction> {
+        let mut expanded = Vec::with_capacity(instructions.len());
+        for instr in instructions {
+            match instr {
+                Instruction::MacroCall { name, args } => match Macro::from_call(&name, &args) {
+                    Ok(m) => {
+                        debug!(name, ?args, "macro expanded");
+                        expanded.extend(m.expand());
+                    }
+                    Err(_) => expanded.push(Instruction::MacroCall { name, args }),
+                },
+                Instruction::Loop(body) => expanded.push(Instruction::Loop(self.optimize(body))),
+                other => expanded.push(other),
+            }
+        }
+        expanded
+    }
+}
+
+impl Optimizer for CollapseRuns {
+    fn optimize(&self, instructions: Vec<Instruction>) -> Vec<Instruction> {
+        let mut collapsed = Vec::with_capacity(instructions.len());
+        for instr in instructions {
+            let instr = match instr {
+                Instruction::Increment => Instruction::Add(1),
+                Instruction::Decrement => Instruction::Add(u8::MAX),
+                Instruction::IncrementPointer => Instruction::Move(1),
+                Instruction::DecrementPointer => Instruction::Move(-1),
+                Instruction::Loop(body) => Instruction::Loop(self.optimize(body)),
+                other => other,
+            };
+            push_folded(&mut collapsed, instr);
+        }
+        collapsed
+    }
+}
+
+impl Optimizer for ClearCellOpt {
+    fn optimize(&self, instructions: Vec<Instruction>) -> Vec<Instruction> {
+        let mut cleared = Vec::with_capacity(instructions.len());
+        for instr in instructions {
+            let instr = match instr {
+                Instruction::Loop(body) => {
+                    let body = self.optimize(body);
+                    match LoopAnalyzer::classify(&body) {
+                        LoopKind::ClearCell => {
+                            debug!(?body, "loop replaced by a cell clear");
+                            Instruction::SetCell(0)
+                        }
+                        _ => Instruction::Loop(body),
+                    }
+                }
+                other => other,
+            };
+            push_folded(&mut cleared, instr);
+        }
+        // every body gets its clears fused, not just the top level
+        fuse_clears(cleared)
+    }
+}
+
+impl Optimizer for ScanOpt {
+    fn optimize(&self, instructions: Vec<Instruction>) -> Vec<Instruction> {
+        replace_loops(instructions, &|body| match LoopAnalyzer::classify(&body) {
+            LoopKind::FindZero { direction, stride } => {
+                debug!(?body, "loop replaced by a zero search");
+                Instruction::FindZero {
+                    direction: direction * stride as isize,
+                }
+            }
+            _ => Instruction::Loop(body),
+        })
+    }
+}
+
+impl Optimizer for CopyOpt {
+    fn optimize(&self, instructions: Vec<Instruction>) -> Vec<Instruction> {
+        replace_loops(instructions, &|body| match LoopAnalyzer::classify(&body) {
+            LoopKind::CopyCell { offset } => {
+                debug!(?body, "loop replaced by a copy");
+                Instruction::MultiplyAdd { offset, factor: 1 }
+            }
+            LoopKind::MultiplyAdd { offset, factor } => {
+                debug!(?body, "loop replaced by a multiply-add");
+                Instruction::MultiplyAdd { offset, factor }
+            }
+            _ => Instruction::Loop(body),
+        })
+    }
+}
+
+impl Optimizer for DeadStoreElim {
+    fn optimize(&self, instructions: Vec<Instruction>) -> Vec<Instruction> {
+        let dead = DataFlowAnalyzer::analyze(&instructions)
+            .dead_stores()
+            .into_iter()
+            .collect();
+        drop_stores(instructions, 0, &dead)
+    }
+}
+
+// removes the instructions at the given opcode positions, a read that's overwritten still
+// takes a byte of input so it stays
+fn drop_stores(
+    instructions: Vec<Instruction>,
+    mut position: usize,
+    dead: &HashSet<usize>,
+) -> Vec<Instruction> {
+    let mut kept = Vec::with_capacity(instructions.len());
+    for instr in instructions {
+        let len = instr.opcode_len();
+        match instr {
+            Instruction::Loop(body) => {
+                kept.push(Instruction::Loop(drop_stores(body, position + 1, dead)))
+            }
+            Instruction::Read => kept.push(instr),
+            _ if dead.contains(&position) => debug!(?instr, "dead store dropped"),
+            _ => kept.push(instr),
+        }
+        position += len;
+    }
+    kept
+}
+
+impl Optimizer for NoopElim {
+    fn optimize(&self, instructions: Vec<Instruction>) -> Vec<Instruction> {
+        let mut kept: Vec<Instruction> = Vec::with_capacity(instructions.len());
+        for instr in instructions {
+            let zeroed = matches!(
+                kept.last(),
+                Some(
+                    Instruction::Loop(_)
+                        | Instruction::SetCell(0)
+                        | Instruction::FindZero { .. }
+                        | Instruction::MultiplyAdd { .. }
+                )
+            );
+            match instr {
+                Instruction::Add(0)
+                | Instruction::Move(0)
+                | Instruction::BulkZero { len: 0, .. } => {}
+                Instruction::Loop(_) | Instruction::SetCell(0) | Instruction::FindZero { .. }
+                    if zeroed => {}
+                Instruction::Loop(body) => kept.push(Instruction::Loop(self.optimize(body))),
+                other => kept.push(other),
+            }
+        }
+        kept
+    }
+}
+
+// rewrites every loop innermost first, `replace` gets a loop's already rewritten body
+// and the result is folded into what came before it
+fn replace_loops(
+    instructions: Vec<Instruction>,
+    replace: &impl Fn(Vec<Instruction>) -> Instruction,
+) -> Vec<Instruction> {
+    let mut replaced = Vec::with_capacity(instructions.len());
+    for instr in instructions {
+        let instr = match instr {
+            Instruction::Loop(body) => replace(replace_loops(body, replace)),
+            other => other,
+        };
+        push_folded(&mut replaced, instr);
+    }
+    replaced
+}
+
+fn push_folded(instructions: &mut Vec<Instruction>, instr: Instruction) {
+    match (instructions.last_mut(), instr) {
+        (Some(Instruction::Add(a)), Instruction::Add(b)) => *a = a.wrapping_add(b),
+        (Some(Instruction::Move(a)), Instruction::Move(b)) => *a += b,
+        (Some(Instruction::SetCell(a)), Instruction::Add(b)) => *a = a.wrapping_add(b),
+        (_, instr) => instructions.push(instr),
+    }
+
+    if matches!(
+        instructions.last(),
+        Some(Instruction::Add(0) | Instruction::Move(0))
+    ) {
+        instructions.pop();
+    }
+}
+
+// shortest run of cleared neighbours worth turning into a `BulkZero`
+const MIN_BULK_ZERO: usize = 4;
+
+// `[-]>[-]>[-]>[-]` becomes one bulk clear followed by the move to the last cell
+fn fuse_clears(instructions: Vec<Instruction>) -> Vec<Instruction> {
+    let mut fused: Vec<Instruction> = Vec::with_capacity(instructions.len());
+    let mut i = 0;
+
+    while i < instructions.len() {
+        let step = match instructions.get(i..i + 3) {
+            Some(
+                [Instruction::SetCell(0), Instruction::Move(step @ (1 | -1)), Instruction::SetCell(0)],
+            ) => *step,
+            _ => {
+                push_merged(&mut fused, instructions[i].clone());
+                i += 1;
+                continue;
+            }
+        };
+
+        let mut len = 1;
+        while matches!(
+            instructions.get(i + 2 * len - 1..i + 2 * len + 1),
+            Some([Instruction::Move(s), Instruction::SetCell(0)]) if *s == step
+        ) {
+            len += 1;
+        }
+
+        if len < MIN_BULK_ZERO {
+            push_merged(&mut fused, instructions[i].clone());
+            i += 1;
+            continue;
+        }
+
+        debug!(len, step, "clears fused into a bulk clear");
+        let span = (len - 1) as isize;
+        fused.push(Instruction::BulkZero {
+            start_offset: if step > 0 { 0 } else { -span },
+            len,
+        });
+        push_merged(&mut fused, Instruction::Move(step * span));
+        i += 2 * len - 1;
+    }
+
+    fused
+}
+
+fn push_merged(instructions: &mut Vec<Instruction>, instr: Instruction) {
+    match (instructions.last_mut(), instr) {
+        (Some(Instruction::Move(a)), Instruction::Move(b)) => {
+            *a += b;
+            if *a == 0 {
+                instructions.pop();
+            }
+        }
+        (_, instr) => instructions.push(instr),
+    }
+}
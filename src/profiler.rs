@@ -0,0 +1,170 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::json::Value;
+use crate::parser::{Instruction, MNEMONICS};
+
+fn slot(instr: &Instruction) -> usize {
+    match instr {
+        Instruction::IncrementPointer => 0,
+        Instruction::DecrementPointer => 1,
+        Instruction::Increment => 2,
+        Instruction::Decrement => 3,
+        Instruction::Write => 4,
+        Instruction::Read => 5,
+        Instruction::Loop(_) => 6,
+        Instruction::Add(_) => 7,
+        Instruction::Move(_) => 8,
+        Instruction::SetCell(_) => 9,
+        Instruction::BulkZero { .. } => 10,
+        Instruction::FindZero { .. } => 11,
+        Instruction::DebugPrint { .. } => 12,
+        Instruction::MultiplyAdd { .. } => 13,
+        Instruction::MacroCall { .. } => 14,
+        Instruction::CopyRange { .. } => 15,
+    }
+}
+
+// per-instruction-type counters kept by an interpreter, far cheaper than a trace
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    counts: [u64; MNEMONICS.len()],
+    time: [Duration; MNEMONICS.len()],
+    loop_iterations: u64,
+    depth: usize,
+    max_depth: usize,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler::default()
+    }
+
+    pub(crate) fn record(&mut self, instr: &Instruction, elapsed: Duration) {
+        let slot = slot(instr);
+        self.counts[slot] += 1;
+        self.time[slot] += elapsed;
+    }
+
+    pub(crate) fn enter_loop(&mut self) {
+        self.depth += 1;
+        self.max_depth = self.max_depth.max(self.depth);
+    }
+
+    pub(crate) fn loop_iteration(&mut self) {
+        self.loop_iterations += 1;
+    }
+
+    pub(crate) fn exit_loop(&mut self) {
+        self.depth -= 1;
+    }
+
+    pub fn report(&self) -> ProfileReport {
+        ProfileReport {
+            ops: MNEMONICS
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| self.counts[*i] > 0)
+                .map(|(i, op)| OpProfile {
+                    op,
+                    count: self.counts[i],
+                    time: self.time[i],
+                })
+                .collect(),
+            total_instructions: self.counts.iter().sum(),
+            loop_iterations: self.loop_iterations,
+            max_loop_depth: self.max_depth,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpProfile {
+    pub op: &'static str,
+    pub count: u64,
+    // for LOOP this is the condition checks only, the body is counted separately
+    pub time: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileReport {
+    pub ops: Vec<OpProfile>,
+    pub total_instructions: u64,
+    pub loop_iterations: u64,
+    pub max_loop_depth: usize,
+}
+
+impl ProfileReport {
+    pub fn total_time(&self) -> Duration {
+        self.ops.iter().map(|op| op.time).sum()
+    }
+
+    // percentage of the time spent in all instructions, unlike the count share in the table
+    fn time_share(&self, op: &OpProfile) -> f64 {
+        op.time.as_secs_f64() * 100.0 / self.total_time().as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+
+    pub fn to_json(&self) -> Value {
+        let ops = self
+            .ops
+            .iter()
+            .map(|op| {
+                Value::object([
+                    ("op", op.op.into()),
+                    ("count", op.count.into()),
+                    ("time_ns", (op.time.as_nanos() as u64).into()),
+                    ("time_percent", self.time_share(op).into()),
+                ])
+            })
+            .collect();
+        Value::object([
+            ("ops", Value::Array(ops)),
+            ("total_instructions", self.total_instructions.into()),
+            (
+                "total_time_ns",
+                (self.total_time().as_nanos() as u64).into(),
+            ),
+            ("loop_iterations", self.loop_iterations.into()),
+            ("max_loop_depth", self.max_loop_depth.into()),
+        ])
+    }
+
+    // one row per instruction type under a header, loop totals are left to the json
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("op,count,time_ns,time_percent\n");
+        for op in &self.ops {
+            out.push_str(&format!(
+                "{},{},{},{:.4}\n",
+                op.op,
+                op.count,
+                op.time.as_nanos(),
+                self.time_share(op)
+            ));
+        }
+        out
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<8} {:>14} {:>8} {:>14}",
+            "op", "count", "share", "time"
+        )?;
+        for op in &self.ops {
+            let share = op.count as f64 * 100.0 / self.total_instructions.max(1) as f64;
+            writeln!(
+                f,
+                "{:<8} {:>14} {:>7.2}% {:>14}",
+                op.op,
+                op.count,
+                share,
+                format!("{:.3?}", op.time)
+            )?;
+        }
+        writeln!(f, "total instructions: {}", self.total_instructions)?;
+        writeln!(f, "loop iterations: {}", self.loop_iterations)?;
+        writeln!(f, "max loop depth: {}", self.max_loop_depth)
+    }
+}
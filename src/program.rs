@@ -0,0 +1,327 @@
+use std::collections::HashSet;
+use std::io::Cursor;
+
+use crate::interpreter::{run_with_input, CapturedOutput, Interpreter, InterpreterConfig};
+use crate::lexer::{debug_prints, lex, tokenize, Token};
+use crate::optimize::{Optimizer, OptimizerPipeline};
+use crate::parser::{parse_with_debug_prints, Instruction, ParseError};
+use crate::rng::Rng;
+use crate::sha256;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Program {
+    pub instructions: Vec<Instruction>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EquivConfig {
+    pub input_lengths: Vec<usize>,
+    pub inputs_per_length: usize,
+    pub seed: u64,
+    // both programs run with this, set `max_steps` so non-terminating pairs still finish
+    pub interpreter: InterpreterConfig,
+    // also require the same final tape, for an optimizer whose rewrites must not leave
+    // different scratch cells behind
+    pub compare_state: bool,
+}
+
+impl Default for EquivConfig {
+    fn default() -> Self {
+        EquivConfig {
+            input_lengths: vec![0, 1, 4, 16],
+            inputs_per_length: 8,
+            seed: 0,
+            interpreter: InterpreterConfig {
+                max_steps: Some(10_000_000),
+                ..InterpreterConfig::default()
+            },
+            compare_state: false,
+        }
+    }
+}
+
+// a run either produces output or fails with the rendered runtime error
+pub type Outcome = Result<Vec<u8>, String>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EquivResult {
+    LikelyEquivalent,
+    Diverges {
+        input: Vec<u8>,
+        self_output: Outcome,
+        other_output: Outcome,
+    },
+    // same outcome, different final tapes, only with `compare_state`
+    StateDiverges {
+        input: Vec<u8>,
+        self_checksum: u64,
+        other_checksum: u64,
+    },
+}
+
+impl Program {
+    pub fn new(instructions: Vec<Instruction>) -> Self {
+        Program { instructions }
+    }
+
+    pub fn from_source(source: &str) -> Result<Program, ParseError> {
+        Ok(Program::new(parse_with_debug_prints(
+            &lex(source),
+            debug_prints(source),
+        )?))
+    }
+
+    // SHA-256 of the instruction tree, so any two sources that parse the same hash the same
+    // no matter how they're spaced or commented
+    pub fn semantic_hash(&self) -> [u8; 32] {
+        let mut canonical = String::new();
+        canonicalize(&self.instructions, &mut canonical);
+        sha256::digest(canonical.as_bytes())
+    }
+
+    // instructions weighted by how deeply they're nested, plus 10 per distinct loop body and
+    // 5 per i/o instruction. top-level code counts as depth 1 so loop-free programs score
+    // their length
+    pub fn complexity_score(&self) -> f64 {
+        let mut walk = ComplexityWalk::default();
+        walk.visit(&self.instructions, 1);
+        if walk.instructions == 0 {
+            return 0.0;
+        }
+
+        let avg_loop_depth = walk.depth_sum as f64 / walk.instructions as f64;
+        walk.instructions as f64 * avg_loop_depth
+            + walk.loops.len() as f64 * 10.0
+            + walk.io as f64 * 5.0
+    }
+
+    pub fn optimize_with(self, pipeline: &OptimizerPipeline) -> Program {
+        Program::new(pipeline.optimize(self.instructions))
+    }
+
+    pub fn run_with_input(&self, config: &InterpreterConfig, input: &[u8]) -> Outcome {
+        run_with_input(&self.instructions, config, input).map_err(|err| err.to_string())
+    }
+
+    // like `run_with_input`, along with `Interpreter::state_checksum` of where it stopped
+    pub fn run_for_state(&self, config: &InterpreterConfig, input: &[u8]) -> (Outcome, u64) {
+        let output = CapturedOutput::new();
+        let mut interpreter = Interpreter::new(config.clone());
+        interpreter.set_input(Cursor::new(input.to_vec()));
+        interpreter.set_output(output.clone());
+        let outcome = interpreter
+            .run(&self.instructions)
+            .map(|()| output.contents())
+            .map_err(|err| err.to_string());
+        (outcome, interpreter.state_checksum())
+    }
+
+    pub fn equivalence_check(&self, other: &Program, config: &EquivConfig) -> EquivResult {
+        if self == other {
+            return EquivResult::LikelyEquivalent;
+        }
+
+        let mut rng = Rng::new(config.seed);
+
+        for &length in &config.input_lengths {
+            // only one distinct empty input exists
+            let samples = if length == 0 {
+                1
+            } else {
+                config.inputs_per_length
+            };
+
+            for _ in 0..samples {
+                let mut input = vec![0; length];
+                rng.fill(&mut input);
+
+                let (self_output, self_checksum) = self.run_for_state(&config.interpreter, &input);
+                let (other_output, other_checksum) =
+                    other.run_for_state(&config.interpreter, &input);
+
+                if self_output != other_output {
+                    return EquivResult::Diverges {
+                        input,
+                        self_output,
+                        other_output,
+                    };
+                }
+                if config.compare_state && self_checksum != other_checksum {
+                    return EquivResult::StateDiverges {
+                        input,
+                        self_checksum,
+                        other_checksum,
+                    };
+                }
+            }
+        }
+
+        EquivResult::LikelyEquivalent
+    }
+}
+
+// a program along with where its instructions came from in the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedProgram {
+    pub program: Program,
+    // in source order, so a loop comes before everything in its body
+    spans: Vec<InstructionSpan>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct InstructionSpan {
+    // bytes from the first keyword's start to the last one's end
+    start: usize,
+    end: usize,
+    // indices from the top level down to the instruction
+    path: Vec<usize>,
+    // the enclosing loop's span
+    parent: Option<usize>,
+}
+
+impl ParsedProgram {
+    pub fn from_source(source: &str) -> Result<ParsedProgram, ParseError> {
+        let program = Program::from_source(source)?;
+        let tokens: Vec<Token> = tokenize(source)
+            .into_iter()
+            .filter(|token| token.op.is_some())
+            .collect();
+
+        let mut spans = Vec::new();
+        collect_spans(
+            &program.instructions,
+            &tokens,
+            &mut 0,
+            &mut Vec::new(),
+            None,
+            &mut spans,
+        );
+        Ok(ParsedProgram { program, spans })
+    }
+
+    // the innermost instruction whose keywords span `offset`, the space between two
+    // instructions in a loop belongs to the loop and between top-level ones to nothing.
+    // `#debug_print` comments have no keywords so they're never found
+    pub fn instruction_at(&self, offset: usize) -> Option<&Instruction> {
+        // the last one starting at or before `offset`, if it doesn't reach that far one of
+        // the loops around it might
+        let mut index = self
+            .spans
+            .partition_point(|span| span.start <= offset)
+            .checked_sub(1);
+        while let Some(span) = index.map(|i| &self.spans[i]) {
+            if offset < span.end {
+                return Some(self.resolve(&span.path));
+            }
+            index = span.parent;
+        }
+        None
+    }
+
+    fn resolve(&self, path: &[usize]) -> &Instruction {
+        let mut instr = &self.program.instructions[path[0]];
+        for &i in &path[1..] {
+            let Instruction::Loop(body) = instr else {
+                unreachable!("only loops have instructions under them");
+            };
+            instr = &body[i];
+        }
+        instr
+    }
+}
+
+// `position` counts opcodes like everywhere else, which with the comment-free tokens is
+// also the index of an instruction's first keyword
+fn collect_spans(
+    instructions: &[Instruction],
+    tokens: &[Token],
+    position: &mut usize,
+    path: &mut Vec<usize>,
+    parent: Option<usize>,
+    spans: &mut Vec<InstructionSpan>,
+) {
+    for (i, instr) in instructions.iter().enumerate() {
+        let len = instr.opcode_len();
+        if len == 0 {
+            continue;
+        }
+        path.push(i);
+        let index = spans.len();
+        spans.push(InstructionSpan {
+            start: tokens[*position].start,
+            end: tokens[*position + len - 1].end,
+            path: path.clone(),
+            parent,
+        });
+        if let Instruction::Loop(body) = instr {
+            collect_spans(body, tokens, &mut (*position + 1), path, Some(index), spans);
+        }
+        path.pop();
+        *position += len;
+    }
+}
+
+// parsed instructions as their brainfuck characters, optimizer output as tagged operands
+fn canonicalize(instructions: &[Instruction], out: &mut String) {
+    for instr in instructions {
+        match instr {
+            Instruction::IncrementPointer => out.push('>'),
+            Instruction::DecrementPointer => out.push('<'),
+            Instruction::Increment => out.push('+'),
+            Instruction::Decrement => out.push('-'),
+            Instruction::Write => out.push('.'),
+            Instruction::Read => out.push(','),
+            Instruction::Loop(body) => {
+                out.push('[');
+                canonicalize(body, out);
+                out.push(']');
+            }
+            Instruction::Add(n) => out.push_str(&format!("A{};", n)),
+            Instruction::Move(offset) => out.push_str(&format!("M{};", offset)),
+            Instruction::SetCell(value) => out.push_str(&format!("S{};", value)),
+            Instruction::BulkZero { start_offset, len } => {
+                out.push_str(&format!("Z{},{};", start_offset, len))
+            }
+            Instruction::FindZero { direction } => out.push_str(&format!("F{};", direction)),
+            Instruction::MultiplyAdd { offset, factor } => {
+                out.push_str(&format!("X{},{};", offset, factor))
+            }
+            Instruction::CopyRange {
+                src_offset,
+                dst_offset,
+                len,
+            } => out.push_str(&format!("C{},{},{};", src_offset, dst_offset, len)),
+            Instruction::MacroCall { name, args } => {
+                let args: Vec<String> = args.iter().map(u8::to_string).collect();
+                out.push_str(&format!("C{}({});", name, args.join(",")))
+            }
+            // comments as far as the program's behaviour goes
+            Instruction::DebugPrint { .. } => (),
+        }
+    }
+}
+
+#[derive(Default)]
+struct ComplexityWalk<'a> {
+    instructions: usize,
+    depth_sum: usize,
+    loops: HashSet<&'a [Instruction]>,
+    io: usize,
+}
+
+impl<'a> ComplexityWalk<'a> {
+    fn visit(&mut self, instructions: &'a [Instruction], depth: usize) {
+        for instr in instructions {
+            self.instructions += 1;
+            self.depth_sum += depth;
+            match instr {
+                Instruction::Loop(body) => {
+                    self.loops.insert(body);
+                    self.visit(body, depth + 1);
+                }
+                Instruction::Write | Instruction::Read => self.io += 1,
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+// a seccomp filter that limits the process to the syscalls a run needs, installed right
+// before execution so a bug the program manages to exploit can't open files, spawn
+// processes or talk to the network. anything off the list fails with EPERM
+use std::io;
+
+use crate::interpreter::IoMode;
+
+// `AUDIT_ARCH_*` from linux/audit.h, a filter written for one syscall table must not be
+// used to judge calls made through another
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xc000_003e;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+// offsets into `struct seccomp_data`
+const SYSCALL_NR: u32 = 0;
+const ARCH: u32 = 4;
+
+// the syscalls every run makes: writes for stdout and stderr, memory for tape growth and
+// the allocator, closing files opened before the filter went in, and exiting
+const BASE: [libc::c_long; 16] = [
+    libc::SYS_write,
+    libc::SYS_writev,
+    libc::SYS_close,
+    libc::SYS_brk,
+    libc::SYS_mmap,
+    libc::SYS_munmap,
+    libc::SYS_mremap,
+    libc::SYS_madvise,
+    libc::SYS_msync,
+    libc::SYS_futex,
+    libc::SYS_clock_gettime,
+    libc::SYS_rt_sigreturn,
+    libc::SYS_rt_sigprocmask,
+    libc::SYS_sigaltstack,
+    libc::SYS_exit,
+    libc::SYS_exit_group,
+];
+
+// the syscalls allowed under `io_mode`, only the standard mode reads stdin
+pub fn allowed_syscalls(io_mode: IoMode) -> Vec<libc::c_long> {
+    let mut syscalls = BASE.to_vec();
+    if io_mode == IoMode::Standard {
+        syscalls.push(libc::SYS_read);
+    }
+    syscalls
+}
+
+// the filter as classic BPF: kill the process on a foreign architecture, allow the listed
+// syscalls and fail everything else
+fn filter(syscalls: &[libc::c_long]) -> Vec<libc::sock_filter> {
+    let statement = |code: u32, k: u32| libc::sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    };
+    let jump = |k: u32, jt: u8, jf: u8| libc::sock_filter {
+        code: (libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K) as u16,
+        jt,
+        jf,
+        k,
+    };
+
+    let mut program = vec![
+        statement(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, ARCH),
+        jump(AUDIT_ARCH, 1, 0),
+        statement(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_KILL_PROCESS),
+        statement(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, SYSCALL_NR),
+    ];
+    for syscall in syscalls {
+        program.push(jump(*syscall as u32, 0, 1));
+        program.push(statement(
+            libc::BPF_RET | libc::BPF_K,
+            libc::SECCOMP_RET_ALLOW,
+        ));
+    }
+    program.push(statement(
+        libc::BPF_RET | libc::BPF_K,
+        libc::SECCOMP_RET_ERRNO | (libc::EPERM as u32 & libc::SECCOMP_RET_DATA),
+    ));
+    program
+}
+
+// restricts this thread, and any it would start, for the rest of the process. there's no
+// way back, so everything that needs a file or a thread has to be set up before
+pub fn install(io_mode: IoMode) -> io::Result<()> {
+    let mut program = filter(&allowed_syscalls(io_mode));
+    let prog = libc::sock_fprog {
+        len: program.len() as libc::c_ushort,
+        filter: program.as_mut_ptr(),
+    };
+
+    // SAFETY: both calls only read their arguments, `prog` outlives the second one and the
+    // kernel copies the filter before it returns
+    unsafe {
+        // without it an unprivileged process isn't allowed to install a filter
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if libc::prctl(
+            libc::PR_SET_SECCOMP,
+            libc::SECCOMP_MODE_FILTER as libc::c_ulong,
+            &prog as *const libc::sock_fprog,
+        ) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
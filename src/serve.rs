@@ -0,0 +1,147 @@
+use std::io::{self, BufRead, BufReader, Cursor, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use crate::base64;
+use crate::interpreter::{CapturedOutput, Interpreter, InterpreterConfig};
+use crate::json::Value;
+use crate::program::Program;
+
+// largest request body accepted, sources beyond this are refused with 413
+const MAX_BODY: usize = 1 << 20;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+// answers `POST /run` with `{"source": "...", "input": "<base64>"}`, one thread and one
+// request per connection, every run gets a fresh interpreter built from `config`
+pub fn serve(listener: TcpListener, config: InterpreterConfig) -> io::Result<()> {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                warn!("failed to accept a connection: {}", err);
+                continue;
+            }
+        };
+        let config = config.clone();
+        thread::spawn(move || {
+            if let Err(err) = handle(stream, &config) {
+                warn!("connection failed: {}", err);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn handle(stream: TcpStream, config: &InterpreterConfig) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut stream = stream;
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            break;
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    let (status, body) = match (method, path) {
+        ("POST", "/run") if length > MAX_BODY => (
+            "413 Payload Too Large",
+            failure(format!("request body is over {} bytes", MAX_BODY)),
+        ),
+        ("POST", "/run") => {
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body)?;
+            info!(peer = ?stream.peer_addr().ok(), bytes = length, "run requested");
+            match String::from_utf8(body)
+                .map_err(|_| "request body is not UTF-8".to_string())
+                .and_then(|body| Value::parse(&body))
+            {
+                Ok(request) => run(&request, config),
+                Err(err) => ("400 Bad Request", failure(err)),
+            }
+        }
+        (_, "/run") => (
+            "405 Method Not Allowed",
+            failure("/run only accepts POST".to_string()),
+        ),
+        _ => ("404 Not Found", failure(format!("no route for {}", path))),
+    };
+
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+// output is whatever the program wrote, also when it stopped with an error
+fn run(request: &Value, config: &InterpreterConfig) -> (&'static str, Value) {
+    let Some(source) = request.get("source").and_then(Value::as_str) else {
+        return (
+            "400 Bad Request",
+            failure("`source` must be a string".to_string()),
+        );
+    };
+    let input = match request.get("input").map(|input| input.as_str()) {
+        None => Vec::new(),
+        Some(Some(input)) => match base64::decode(input) {
+            Ok(input) => input,
+            Err(err) => return ("400 Bad Request", failure(format!("`input`: {}", err))),
+        },
+        Some(None) => {
+            return (
+                "400 Bad Request",
+                failure("`input` must be a base64 string".to_string()),
+            )
+        }
+    };
+
+    let program = match Program::from_source(source) {
+        Ok(program) => program,
+        Err(err) => return ("200 OK", response(&[], Some(err.to_string()))),
+    };
+
+    let output = CapturedOutput::new();
+    let mut interpreter = Interpreter::new(config.clone());
+    interpreter.set_input(Cursor::new(input));
+    interpreter.set_output(output.clone());
+    let error = interpreter.run(&program.instructions).err();
+    (
+        "200 OK",
+        response(&output.contents(), error.map(|err| err.to_string())),
+    )
+}
+
+fn response(output: &[u8], error: Option<String>) -> Value {
+    Value::object([
+        ("output", base64::encode(output).into()),
+        ("error", error.into()),
+    ])
+}
+
+fn failure(error: String) -> Value {
+    Value::object([("output", Value::Null), ("error", error.into())])
+}
@@ -0,0 +1,114 @@
+use std::cell::RefCell;
+use std::io::{self, Read};
+use std::rc::Rc;
+use std::time::Instant;
+
+use crate::hex;
+use crate::toml::{self, Value};
+
+// one successful read from stdin, `elapsed_us` is counted from the start of the recording
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub elapsed_us: u64,
+    pub bytes: Vec<u8>,
+}
+
+// everything a run read from stdin, written by `--record` and fed back by `--replay`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Session {
+    pub program: Option<String>,
+    pub chunks: Vec<Chunk>,
+}
+
+impl Session {
+    pub fn parse(source: &str) -> Result<Session, String> {
+        let table = toml::parse(source).map_err(|err| err.to_string())?;
+        let mut session = Session::default();
+
+        for (key, value) in &table {
+            match key.as_str() {
+                "program" => {
+                    let program = value.as_str().ok_or("`program` must be a string")?;
+                    session.program = Some(program.to_string());
+                }
+                "read" => {
+                    let reads = value
+                        .as_array()
+                        .ok_or("`read` must be an array of tables")?;
+                    for (i, read) in reads.iter().enumerate() {
+                        let read = read.as_table().ok_or("`read` must be an array of tables")?;
+                        let invalid = |field: &str| format!("read {}: invalid `{}`", i + 1, field);
+                        let elapsed_us = read
+                            .get("elapsed_us")
+                            .and_then(Value::as_integer)
+                            .and_then(|n| u64::try_from(n).ok())
+                            .ok_or_else(|| invalid("elapsed_us"))?;
+                        let bytes = read
+                            .get("bytes")
+                            .and_then(Value::as_str)
+                            .ok_or_else(|| invalid("bytes"))?;
+                        let bytes =
+                            hex::decode(bytes).map_err(|err| format!("read {}: {}", i + 1, err))?;
+                        session.chunks.push(Chunk { elapsed_us, bytes });
+                    }
+                }
+                _ => return Err(format!("unknown key `{}`", key)),
+            }
+        }
+
+        Ok(session)
+    }
+
+    pub fn to_toml(&self) -> String {
+        let mut out = String::from("# svolang session\n");
+        if let Some(program) = &self.program {
+            out.push_str(&format!("program = {}\n", Value::String(program.clone())));
+        }
+        for chunk in &self.chunks {
+            out.push_str(&format!(
+                "\n[[read]]\nelapsed_us = {}\nbytes = \"{}\"\n",
+                chunk.elapsed_us,
+                hex::encode(&chunk.bytes)
+            ));
+        }
+        out
+    }
+
+    // every recorded byte, in the order the program read them
+    pub fn input(&self) -> Vec<u8> {
+        self.chunks
+            .iter()
+            .flat_map(|chunk| chunk.bytes.iter().copied())
+            .collect()
+    }
+}
+
+// input source that passes reads through while adding them to a shared session
+pub struct Recorder<R: Read> {
+    inner: R,
+    started: Instant,
+    session: Rc<RefCell<Session>>,
+}
+
+impl<R: Read> Recorder<R> {
+    pub fn new(inner: R, session: Rc<RefCell<Session>>) -> Self {
+        Recorder {
+            inner,
+            started: Instant::now(),
+            session,
+        }
+    }
+}
+
+impl<R: Read> Read for Recorder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n > 0 {
+            self.session.borrow_mut().chunks.push(Chunk {
+                elapsed_us: self.started.elapsed().as_micros() as u64,
+                bytes: buf[..n].to_vec(),
+            });
+        }
+        Ok(n)
+    }
+}
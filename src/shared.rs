@@ -0,0 +1,103 @@
+use std::fs::OpenOptions;
+use std::hint;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::slice;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+use memmap2::{MmapOptions, MmapRaw};
+
+use crate::interpreter::RuntimeError;
+
+// tape cells memory-mapped from a file so several processes see each other's writes,
+// the first byte of the file is a spinlock and the cells follow it. not `Clone`, the cells
+// are handed out as `&mut [u8]` so one mapping must have one owner in this process
+#[derive(Debug)]
+pub struct SharedTape {
+    map: Arc<MmapRaw>,
+}
+
+impl SharedTape {
+    // creates the file when it's missing or empty, an existing file has to hold exactly
+    // `cells` cells plus the lock byte
+    pub fn open(path: &Path, cells: usize) -> Result<Self, RuntimeError> {
+        let expected = cells as u64 + 1;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let found = file.metadata()?.len();
+        if found == 0 {
+            file.set_len(expected)?;
+        } else if found != expected {
+            return Err(RuntimeError::SharedTapeConflict {
+                path: path.to_path_buf(),
+                expected,
+                found,
+            });
+        }
+
+        let map = MmapOptions::new().map_raw(&file)?;
+        Ok(SharedTape { map: Arc::new(map) })
+    }
+
+    // spins until no other holder has the lock byte set, the lock is released on drop
+    pub fn lock(&self) -> SharedTapeLock {
+        let lock = SharedTapeLock {
+            map: self.map.clone(),
+        };
+        while lock
+            .cell()
+            .compare_exchange_weak(0, 1, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            hint::spin_loop();
+        }
+        lock
+    }
+}
+
+impl Deref for SharedTape {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: `open` sized the map to the lock byte plus the cells, so it's at least one
+        // byte long and the range is inside it. the map lives as long as `self`, and the
+        // only other handle in this process is a `SharedTapeLock`, which never touches
+        // anything past the lock byte
+        unsafe { slice::from_raw_parts(self.map.as_ptr().add(1), self.map.len() - 1) }
+    }
+}
+
+impl DerefMut for SharedTape {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: the same range as `deref`. `SharedTape` isn't `Clone`, so `&mut self` is
+        // the only way to these cells in this process and the slice can't alias another.
+        // other processes mapping the file may still write them, holding `lock` is what
+        // keeps those writes apart
+        unsafe { slice::from_raw_parts_mut(self.map.as_mut_ptr().add(1), self.map.len() - 1) }
+    }
+}
+
+pub struct SharedTapeLock {
+    map: Arc<MmapRaw>,
+}
+
+impl SharedTapeLock {
+    fn cell(&self) -> &AtomicU8 {
+        // SAFETY: the map is at least one byte long and `self` keeps it alive, the first
+        // byte is left out of the slices `SharedTape` hands out and is only ever touched
+        // through atomics, here and in every other process
+        unsafe { AtomicU8::from_ptr(self.map.as_mut_ptr()) }
+    }
+}
+
+impl Drop for SharedTapeLock {
+    fn drop(&mut self) {
+        self.cell().store(0, Ordering::Release);
+    }
+}
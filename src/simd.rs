@@ -0,0 +1,188 @@
+// bulk tape operations and zero searches, SSE2/AVX2 on x86_64 when the CPU has them and plain loops elsewhere
+
+// the widest instruction set the bulk operations use on this CPU
+pub fn instruction_set() -> &'static str {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            "avx2"
+        } else {
+            "sse2"
+        }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    "none"
+}
+
+// zeroes `tape[start..start + len]`, panics if the range is out of bounds like slice indexing
+pub fn bulk_zero(tape: &mut [u8], start: usize, len: usize) {
+    let cells = &mut tape[start..start + len];
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: the feature was detected at runtime, and `zero_avx2` only stores to
+            // 32-byte chunks of `cells` with unaligned stores
+            unsafe { x86::zero_avx2(cells) };
+            return;
+        }
+        // SAFETY: SSE2 is part of the x86_64 baseline, and `zero_sse2` only stores to
+        // 16-byte chunks of `cells` with unaligned stores
+        unsafe { x86::zero_sse2(cells) };
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    cells.fill(0);
+}
+
+// copies `len` cells from `src` to `dst`, the ranges may overlap like `memmove`, panics if
+// either is out of bounds
+pub fn bulk_copy(tape: &mut [u8], src: usize, dst: usize, len: usize) {
+    assert!(
+        src.max(dst) + len <= tape.len(),
+        "bulk copy out of range of a tape of {} cells",
+        tape.len()
+    );
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        // overlapping ranges go through `copy_within`, which already copies like `memmove`
+        if src.abs_diff(dst) >= len {
+            let (from, to) = if src < dst {
+                let (low, high) = tape.split_at_mut(dst);
+                (&low[src..src + len], &mut high[..len])
+            } else {
+                let (low, high) = tape.split_at_mut(src);
+                (&high[..len], &mut low[dst..dst + len])
+            };
+            if is_x86_feature_detected!("avx2") {
+                // SAFETY: the feature was detected at runtime, and `copy_avx2` only loads
+                // 32-byte chunks of `from` and stores to the matching chunks of `to`, two
+                // slices of the same length that can't overlap
+                unsafe { x86::copy_avx2(from, to) };
+            } else {
+                // SAFETY: SSE2 is part of the x86_64 baseline, the rest as for `copy_avx2`
+                unsafe { x86::copy_sse2(from, to) };
+            }
+            return;
+        }
+    }
+
+    tape.copy_within(src..src + len, dst);
+}
+
+// index of the first zero cell at or after `start`
+pub fn find_zero(tape: &[u8], start: usize) -> Option<usize> {
+    let cells = &tape[start..];
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        // SAFETY: SSE2 is part of the x86_64 baseline, and `find_zero_sse2` only loads
+        // 16-byte chunks of `cells` with unaligned loads
+        unsafe { x86::find_zero_sse2(cells) }.map(|i| start + i)
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    cells.iter().position(|&cell| cell == 0).map(|i| start + i)
+}
+
+// index of the last zero cell at or before `start`
+pub fn rfind_zero(tape: &[u8], start: usize) -> Option<usize> {
+    let cells = &tape[..=start];
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        // SAFETY: as in `find_zero`, from the end of `cells`
+        unsafe { x86::rfind_zero_sse2(cells) }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    cells.iter().rposition(|&cell| cell == 0)
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    // every helper takes a slice and touches it only through `chunks_exact` /
+    // `rchunks_exact`, so each vector access covers exactly one whole chunk inside the
+    // slice and the tail is handled byte by byte. the loads and stores are the unaligned
+    // `loadu` / `storeu` forms, so the slice needs no particular alignment. the caller has
+    // to make sure the CPU has the feature the helper is compiled for
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn zero_avx2(cells: &mut [u8]) {
+        let mut chunks = cells.chunks_exact_mut(32);
+        for chunk in &mut chunks {
+            _mm256_storeu_si256(chunk.as_mut_ptr() as *mut __m256i, _mm256_setzero_si256());
+        }
+        chunks.into_remainder().fill(0);
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn zero_sse2(cells: &mut [u8]) {
+        let mut chunks = cells.chunks_exact_mut(16);
+        for chunk in &mut chunks {
+            _mm_storeu_si128(chunk.as_mut_ptr() as *mut __m128i, _mm_setzero_si128());
+        }
+        chunks.into_remainder().fill(0);
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn copy_avx2(from: &[u8], to: &mut [u8]) {
+        let mut chunks = to.chunks_exact_mut(32);
+        let mut sources = from.chunks_exact(32);
+        for (chunk, source) in (&mut chunks).zip(&mut sources) {
+            let value = _mm256_loadu_si256(source.as_ptr() as *const __m256i);
+            _mm256_storeu_si256(chunk.as_mut_ptr() as *mut __m256i, value);
+        }
+        chunks.into_remainder().copy_from_slice(sources.remainder());
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn copy_sse2(from: &[u8], to: &mut [u8]) {
+        let mut chunks = to.chunks_exact_mut(16);
+        let mut sources = from.chunks_exact(16);
+        for (chunk, source) in (&mut chunks).zip(&mut sources) {
+            let value = _mm_loadu_si128(source.as_ptr() as *const __m128i);
+            _mm_storeu_si128(chunk.as_mut_ptr() as *mut __m128i, value);
+        }
+        chunks.into_remainder().copy_from_slice(sources.remainder());
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn find_zero_sse2(cells: &[u8]) -> Option<usize> {
+        let zero = _mm_setzero_si128();
+        let mut chunks = cells.chunks_exact(16);
+        for (i, chunk) in (&mut chunks).enumerate() {
+            let value = _mm_loadu_si128(chunk.as_ptr() as *const __m128i);
+            let mask = _mm_movemask_epi8(_mm_cmpeq_epi8(value, zero));
+            if mask != 0 {
+                return Some(i * 16 + mask.trailing_zeros() as usize);
+            }
+        }
+        let done = cells.len() - chunks.remainder().len();
+        chunks
+            .remainder()
+            .iter()
+            .position(|&cell| cell == 0)
+            .map(|i| done + i)
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn rfind_zero_sse2(cells: &[u8]) -> Option<usize> {
+        let zero = _mm_setzero_si128();
+        let mut chunks = cells.rchunks_exact(16);
+        for (i, chunk) in (&mut chunks).enumerate() {
+            let value = _mm_loadu_si128(chunk.as_ptr() as *const __m128i);
+            let mask = _mm_movemask_epi8(_mm_cmpeq_epi8(value, zero));
+            if mask != 0 {
+                let chunk_start = cells.len() - (i + 1) * 16;
+                // the mask only fills the low 16 bits of the `i32`
+                return Some(chunk_start + 31 - mask.leading_zeros() as usize);
+            }
+        }
+        chunks.remainder().iter().rposition(|&cell| cell == 0)
+    }
+}
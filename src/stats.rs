@@ -0,0 +1,162 @@
+use crate::json::Value;
+use crate::lexer::{lex, OpCode};
+use crate::optimize::optimize;
+use crate::parser::{parse, Instruction, ParseError, MNEMONICS};
+use crate::program::Program;
+
+// loop bodies are assumed to run this many times when estimating cycles
+const ASSUMED_LOOP_ITERATIONS: u64 = 8;
+const IO_CYCLES: u64 = 10;
+
+#[derive(Debug, Clone)]
+pub struct ProgramStats {
+    pub source_bytes: usize,
+    pub opcodes: usize,
+    pub instructions: usize,
+    pub optimized_instructions: usize,
+    pub estimated_cycles: u64,
+    pub optimized_estimated_cycles: u64,
+    pub loops: usize,
+    pub complexity_score: f64,
+}
+
+impl ProgramStats {
+    pub fn collect(source: &str) -> Result<ProgramStats, ParseError> {
+        let opcodes = lex(source);
+        let program = parse(&opcodes)?;
+        let optimized = optimize(program.clone());
+
+        Ok(ProgramStats {
+            source_bytes: source.len(),
+            opcodes: opcodes.len(),
+            instructions: count_instructions(&program),
+            optimized_instructions: count_instructions(&optimized),
+            estimated_cycles: estimate_cycles(&program),
+            optimized_estimated_cycles: estimate_cycles(&optimized),
+            loops: count_loops(&program),
+            complexity_score: Program::new(program).complexity_score(),
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        Value::object([
+            ("source_bytes", self.source_bytes.into()),
+            ("opcodes", self.opcodes.into()),
+            ("instructions", self.instructions.into()),
+            ("optimized_instructions", self.optimized_instructions.into()),
+            ("estimated_cycles", self.estimated_cycles.into()),
+            (
+                "optimized_estimated_cycles",
+                self.optimized_estimated_cycles.into(),
+            ),
+            ("loops", self.loops.into()),
+            ("complexity_score", self.complexity_score.into()),
+        ])
+    }
+}
+
+const OPCODES: [(OpCode, &str); 8] = [
+    (OpCode::Increment, "Increment"),
+    (OpCode::Decrement, "Decrement"),
+    (OpCode::IncrementPointer, "IncrementPointer"),
+    (OpCode::DecrementPointer, "DecrementPointer"),
+    (OpCode::Write, "Write"),
+    (OpCode::Read, "Read"),
+    (OpCode::LoopBegin, "LoopBegin"),
+    (OpCode::LoopEnd, "LoopEnd"),
+];
+
+// how often each opcode appears in the source and each instruction type in the tree,
+// before and after optimization, loop bodies are counted recursively
+#[derive(Debug, Clone)]
+pub struct OpcodeStats {
+    pub opcodes: Vec<(&'static str, usize)>,
+    pub instructions: Vec<(&'static str, usize)>,
+    pub optimized_instructions: Vec<(&'static str, usize)>,
+}
+
+impl OpcodeStats {
+    pub fn collect(source: &str) -> Result<OpcodeStats, ParseError> {
+        let opcodes = lex(source);
+        let program = parse(&opcodes)?;
+        let optimized = optimize(program.clone());
+
+        Ok(OpcodeStats {
+            opcodes: OPCODES
+                .iter()
+                .map(|(op, name)| (*name, opcodes.iter().filter(|o| *o == op).count()))
+                .collect(),
+            instructions: instruction_frequencies(&program),
+            optimized_instructions: instruction_frequencies(&optimized),
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        let counts = |counts: &[(&str, usize)]| {
+            Value::object(counts.iter().map(|(name, count)| (*name, (*count).into())))
+        };
+        Value::object([
+            ("opcodes", counts(&self.opcodes)),
+            ("instructions", counts(&self.instructions)),
+            (
+                "optimized_instructions",
+                counts(&self.optimized_instructions),
+            ),
+        ])
+    }
+}
+
+// one entry per mnemonic, in `MNEMONICS` order
+pub fn instruction_frequencies(instructions: &[Instruction]) -> Vec<(&'static str, usize)> {
+    let mut counts: Vec<(&'static str, usize)> = MNEMONICS.iter().map(|name| (*name, 0)).collect();
+    count_mnemonics(instructions, &mut counts);
+    counts
+}
+
+fn count_mnemonics(instructions: &[Instruction], counts: &mut [(&'static str, usize)]) {
+    for instr in instructions {
+        if let Some(entry) = counts
+            .iter_mut()
+            .find(|(name, _)| *name == instr.mnemonic())
+        {
+            entry.1 += 1;
+        }
+        if let Instruction::Loop(body) = instr {
+            count_mnemonics(body, counts);
+        }
+    }
+}
+
+// every node of the tree, a loop counts itself plus its body
+pub fn count_instructions(instructions: &[Instruction]) -> usize {
+    instructions
+        .iter()
+        .map(|instr| match instr {
+            Instruction::Loop(body) => 1 + count_instructions(body),
+            _ => 1,
+        })
+        .sum()
+}
+
+pub fn count_loops(instructions: &[Instruction]) -> usize {
+    instructions
+        .iter()
+        .map(|instr| match instr {
+            Instruction::Loop(body) => 1 + count_loops(body),
+            _ => 0,
+        })
+        .sum()
+}
+
+pub fn estimate_cycles(instructions: &[Instruction]) -> u64 {
+    instructions
+        .iter()
+        .map(|instr| match instr {
+            Instruction::Loop(body) => {
+                ASSUMED_LOOP_ITERATIONS.saturating_mul(estimate_cycles(body).saturating_add(1))
+            }
+            Instruction::Write | Instruction::Read => IO_CYCLES,
+            _ => 1,
+        })
+        .fold(0, u64::saturating_add)
+}
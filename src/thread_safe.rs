@@ -0,0 +1,127 @@
+use std::io::{self, Read, Write};
+use std::mem;
+use std::sync::{Arc, Mutex};
+
+use crate::interpreter::{Interpreter, InterpreterConfig, RuntimeError, Tape};
+use crate::parser::Instruction;
+
+type SharedInput = Arc<Mutex<Box<dyn Read + Send + Sync>>>;
+type SharedOutput = Arc<Mutex<Box<dyn Write + Send + Sync>>>;
+
+// hands a shared reader or writer to an `Interpreter` for the length of one run
+struct Locked<T>(T);
+
+impl Read for Locked<SharedInput> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.lock().expect("input lock poisoned").read(buf)
+    }
+}
+
+impl Write for Locked<SharedOutput> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().expect("output lock poisoned").write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.lock().expect("output lock poisoned").flush()
+    }
+}
+
+// the `Send + Sync` counterpart of `CapturedOutput`
+#[derive(Debug, Clone, Default)]
+pub struct SharedCapture {
+    buffer: Arc<Mutex<Vec<u8>>>,
+}
+
+impl SharedCapture {
+    pub fn new() -> Self {
+        SharedCapture::default()
+    }
+
+    pub fn contents(&self) -> Vec<u8> {
+        self.buffer.lock().expect("capture lock poisoned").clone()
+    }
+}
+
+impl Write for SharedCapture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer
+            .lock()
+            .expect("capture lock poisoned")
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// an interpreter that can move between threads, it only accepts `Send + Sync` I/O and keeps
+// the machine state itself, each `run` executes on a plain `Interpreter` on the calling thread.
+// tracing, coverage and profiling need `Interpreter` directly
+pub struct ThreadSafeInterpreter {
+    config: InterpreterConfig,
+    tape: Tape,
+    input: SharedInput,
+    output: SharedOutput,
+    steps: u64,
+}
+
+impl ThreadSafeInterpreter {
+    pub fn new(config: InterpreterConfig) -> Self {
+        let tape = Interpreter::new(config.clone()).tape().clone();
+        ThreadSafeInterpreter {
+            config,
+            tape,
+            input: Arc::new(Mutex::new(Box::new(io::stdin()))),
+            output: Arc::new(Mutex::new(Box::new(io::stdout()))),
+            steps: 0,
+        }
+    }
+
+    pub fn config(&self) -> &InterpreterConfig {
+        &self.config
+    }
+
+    pub fn tape(&self) -> &Tape {
+        &self.tape
+    }
+
+    pub fn tape_mut(&mut self) -> &mut Tape {
+        &mut self.tape
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn set_input(&mut self, input: impl Read + Send + Sync + 'static) {
+        self.input = Arc::new(Mutex::new(Box::new(input)));
+    }
+
+    pub fn set_output(&mut self, output: impl Write + Send + Sync + 'static) {
+        self.output = Arc::new(Mutex::new(Box::new(output)));
+    }
+
+    pub fn run(&mut self, instructions: &[Instruction]) -> Result<(), RuntimeError> {
+        let mut interpreter = Interpreter::new(self.config.clone());
+        // moved rather than cloned so a shared tape stays mapped
+        *interpreter.tape_mut() = mem::replace(&mut self.tape, Tape::new(0, 0));
+        interpreter.set_steps(self.steps);
+        interpreter.set_input(Locked(self.input.clone()));
+        interpreter.set_output(Locked(self.output.clone()));
+
+        let result = interpreter.run(instructions);
+
+        self.tape = mem::replace(interpreter.tape_mut(), Tape::new(0, 0));
+        self.steps = interpreter.steps();
+        result
+    }
+}
+
+// checked at compile time so a field change can't quietly drop the guarantee
+const _: () = {
+    fn assert_send_sync<T: Send + Sync>() {}
+    let _ = assert_send_sync::<ThreadSafeInterpreter>;
+};
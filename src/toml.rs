@@ -0,0 +1,339 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+// the subset of TOML the config and session files use: bare or quoted keys,
+// strings, integers, floats, booleans, single-line arrays, `[table]` and `[[array]]` headers
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<Value>),
+    Table(Table),
+}
+
+pub type Table = BTreeMap<String, Value>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TomlError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TomlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for TomlError {}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Value::Integer(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Float(n) => Some(*n),
+            Value::Integer(n) => Some(*n as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_table(&self) -> Option<&Table> {
+        match self {
+            Value::Table(table) => Some(table),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "{:?}", s),
+            Value::Integer(n) => write!(f, "{}", n),
+            Value::Float(n) if n.fract() == 0.0 => write!(f, "{:.1}", n),
+            Value::Float(n) => write!(f, "{}", n),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Value::Table(table) => {
+                write!(f, "{{ ")?;
+                for (i, (key, value)) in table.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{} = {}", key, value)?;
+                }
+                write!(f, " }}")
+            }
+        }
+    }
+}
+
+pub fn parse(source: &str) -> Result<Table, TomlError> {
+    let mut root = Table::new();
+    // path of the table that keys are currently added to, plus whether it is an array entry
+    let mut current: Vec<String> = Vec::new();
+    let mut in_array = false;
+
+    for (i, raw_line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let err = |message: String| TomlError {
+            line: line_number,
+            message,
+        };
+        let line = strip_comment(raw_line).trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix("[[") {
+            let name = header
+                .strip_suffix("]]")
+                .ok_or_else(|| err("unterminated table header".to_string()))?;
+            current = split_key(name).map_err(err)?;
+            in_array = true;
+            let (last, parents) = current.split_last().expect("split_key never returns empty");
+            let parent = table_at(&mut root, parents).map_err(err)?;
+            match parent
+                .entry(last.clone())
+                .or_insert_with(|| Value::Array(Vec::new()))
+            {
+                Value::Array(items) => items.push(Value::Table(Table::new())),
+                _ => return Err(err(format!("`{}` is not an array of tables", last))),
+            }
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[') {
+            let name = header
+                .strip_suffix(']')
+                .ok_or_else(|| err("unterminated table header".to_string()))?;
+            current = split_key(name).map_err(err)?;
+            in_array = false;
+            table_at(&mut root, &current).map_err(err)?;
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| err(format!("expected `key = value`, found `{}`", line)))?;
+        let key = split_key(key).map_err(err)?;
+        let (value, rest) = parse_value(value.trim()).map_err(err)?;
+        if !rest.trim().is_empty() {
+            return Err(err(format!("unexpected `{}` after value", rest.trim())));
+        }
+
+        let table = if in_array {
+            let (last, parents) = current.split_last().expect("array header has a name");
+            let parent = table_at(&mut root, parents).map_err(err)?;
+            match parent.get_mut(last) {
+                Some(Value::Array(items)) => match items.last_mut() {
+                    Some(Value::Table(table)) => table,
+                    _ => unreachable!("array headers always push a table"),
+                },
+                _ => unreachable!("array headers always create the array"),
+            }
+        } else {
+            table_at(&mut root, &current).map_err(err)?
+        };
+
+        let (last, parents) = key.split_last().expect("split_key never returns empty");
+        let table = table_at(table, parents).map_err(err)?;
+        if table.insert(last.clone(), value).is_some() {
+            return Err(err(format!("duplicate key `{}`", last)));
+        }
+    }
+
+    Ok(root)
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = None;
+    let mut escaped = false;
+
+    for (i, c) in line.char_indices() {
+        match in_string {
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(quote) if c == quote => in_string = None,
+            Some(_) => (),
+            None if c == '"' || c == '\'' => in_string = Some(c),
+            None if c == '#' => return &line[..i],
+            None => (),
+        }
+    }
+
+    line
+}
+
+fn split_key(key: &str) -> Result<Vec<String>, String> {
+    let mut parts = Vec::new();
+
+    for part in key.split('.') {
+        let part = part.trim();
+        let part = if let Some(quoted) = part.strip_prefix('"') {
+            quoted
+                .strip_suffix('"')
+                .ok_or_else(|| format!("unterminated quoted key `{}`", part))?
+        } else {
+            part
+        };
+
+        if part.is_empty() {
+            return Err(format!("empty key in `{}`", key.trim()));
+        }
+
+        parts.push(part.to_string());
+    }
+
+    Ok(parts)
+}
+
+fn table_at<'a>(mut table: &'a mut Table, path: &[String]) -> Result<&'a mut Table, String> {
+    for name in path {
+        let entry = table
+            .entry(name.clone())
+            .or_insert_with(|| Value::Table(Table::new()));
+        table = match entry {
+            Value::Table(inner) => inner,
+            Value::Array(items) => match items.last_mut() {
+                Some(Value::Table(inner)) => inner,
+                _ => return Err(format!("`{}` is not a table", name)),
+            },
+            _ => return Err(format!("`{}` is not a table", name)),
+        };
+    }
+
+    Ok(table)
+}
+
+// parses one value from the start of `input`, returning it with whatever follows
+fn parse_value(input: &str) -> Result<(Value, &str), String> {
+    if let Some(rest) = input.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.char_indices();
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => return Ok((Value::String(value), &rest[i + 1..])),
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 't')) => value.push('\t'),
+                    Some((_, 'r')) => value.push('\r'),
+                    Some((_, '"')) => value.push('"'),
+                    Some((_, '\\')) => value.push('\\'),
+                    Some((start, 'u')) => {
+                        let hex = rest
+                            .get(start + 1..start + 5)
+                            .ok_or("truncated \\u escape")?;
+                        let code = u32::from_str_radix(hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("invalid \\u escape `{}`", hex))?;
+                        value.push(code);
+                        for _ in 0..4 {
+                            chars.next();
+                        }
+                    }
+                    Some((_, other)) => return Err(format!("unknown escape `\\{}`", other)),
+                    None => break,
+                },
+                c => value.push(c),
+            }
+        }
+
+        return Err("unterminated string".to_string());
+    }
+
+    if let Some(rest) = input.strip_prefix('\'') {
+        let end = rest.find('\'').ok_or("unterminated string")?;
+        return Ok((Value::String(rest[..end].to_string()), &rest[end + 1..]));
+    }
+
+    if let Some(mut rest) = input.strip_prefix('[') {
+        let mut items = Vec::new();
+
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((Value::Array(items), after));
+            }
+
+            let (item, after) = parse_value(rest)?;
+            items.push(item);
+            rest = after.trim_start();
+
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after;
+            } else if !rest.starts_with(']') {
+                return Err("expected `,` or `]` in array".to_string());
+            }
+        }
+    }
+
+    let end = input
+        .find(|c: char| c == ',' || c == ']' || c.is_whitespace())
+        .unwrap_or(input.len());
+    let (token, rest) = input.split_at(end);
+
+    let value = match token {
+        "true" => Value::Boolean(true),
+        "false" => Value::Boolean(false),
+        _ => {
+            let digits = token.replace('_', "");
+            if let Some(hex) = digits.strip_prefix("0x") {
+                i64::from_str_radix(hex, 16)
+                    .map(Value::Integer)
+                    .map_err(|_| format!("invalid number `{}`", token))?
+            } else if let Ok(n) = digits.parse::<i64>() {
+                Value::Integer(n)
+            } else if let Ok(n) = digits.parse::<f64>() {
+                Value::Float(n)
+            } else if token.is_empty() {
+                return Err("missing value".to_string());
+            } else {
+                return Err(format!("invalid value `{}`", token));
+            }
+        }
+    };
+
+    Ok((value, rest))
+}
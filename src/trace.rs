@@ -0,0 +1,272 @@
+use std::fmt;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use crate::interpreter::RuntimeError;
+use crate::parser::MNEMONICS;
+
+// one line of a trace file: the machine state right after an instruction ran,
+// loops are recorded every time their condition is checked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEvent {
+    pub step: u64,
+    pub position: usize,
+    pub op: &'static str,
+    pub pointer: usize,
+    pub cell: u8,
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "step={} pos={} op={} ptr={} cell={}",
+            self.step, self.position, self.op, self.pointer, self.cell
+        )
+    }
+}
+
+impl TraceEvent {
+    pub fn parse(line: &str) -> Result<TraceEvent, String> {
+        let mut step = None;
+        let mut position = None;
+        let mut op = None;
+        let mut pointer = None;
+        let mut cell = None;
+
+        for field in line.split_whitespace() {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or_else(|| format!("malformed field `{}`", field))?;
+            let invalid = || format!("invalid value for `{}`: `{}`", key, value);
+
+            match key {
+                "step" => step = Some(value.parse().map_err(|_| invalid())?),
+                "pos" => position = Some(value.parse().map_err(|_| invalid())?),
+                "op" => {
+                    op = Some(
+                        *MNEMONICS
+                            .iter()
+                            .find(|m| **m == value)
+                            .ok_or_else(invalid)?,
+                    )
+                }
+                "ptr" => pointer = Some(value.parse().map_err(|_| invalid())?),
+                "cell" => cell = Some(value.parse().map_err(|_| invalid())?),
+                _ => (),
+            }
+        }
+
+        Ok(TraceEvent {
+            step: step.ok_or("missing `step`")?,
+            position: position.ok_or("missing `pos`")?,
+            op: op.ok_or("missing `op`")?,
+            pointer: pointer.ok_or("missing `ptr`")?,
+            cell: cell.ok_or("missing `cell`")?,
+        })
+    }
+}
+
+pub trait TraceSink {
+    fn record(&mut self, event: &TraceEvent) -> Result<(), RuntimeError>;
+
+    // `checksum` is `Interpreter::state_checksum` once the run is over
+    fn finish(&mut self, checksum: u64) -> Result<(), RuntimeError> {
+        let _ = checksum;
+        Ok(())
+    }
+}
+
+pub struct TraceWriter<W: Write> {
+    out: W,
+    timing: Option<Timing>,
+}
+
+// wall-clock time between events, an event's duration is the time since the one before it
+// so a read includes waiting for input. a loop's test after the first one in a run of the
+// loop gets the time since its previous test instead, one whole iteration
+struct Timing {
+    last: Instant,
+    // position of each loop currently iterating and when its test last ran
+    loops: Vec<(usize, Instant)>,
+}
+
+impl Timing {
+    fn duration(&mut self, event: &TraceEvent, now: Instant) -> Duration {
+        if event.op != "LOOP" {
+            return now - self.last;
+        }
+        match self.loops.last_mut() {
+            Some((position, tested)) if *position == event.position => {
+                let iteration = now - *tested;
+                *tested = now;
+                // a zero cell ends the loop
+                if event.cell == 0 {
+                    self.loops.pop();
+                }
+                iteration
+            }
+            _ => {
+                if event.cell != 0 {
+                    self.loops.push((event.position, now));
+                }
+                now - self.last
+            }
+        }
+    }
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(mut out: W, program: &str) -> Result<Self, RuntimeError> {
+        writeln!(out, "# svolang trace")?;
+        writeln!(out, "program={}", program)?;
+        Ok(TraceWriter { out, timing: None })
+    }
+
+    // adds a `duration_ns` field to every event, readers that don't know it skip it
+    pub fn with_timing(mut self) -> Self {
+        self.timing = Some(Timing {
+            last: Instant::now(),
+            loops: Vec::new(),
+        });
+        self
+    }
+}
+
+impl<W: Write> TraceSink for TraceWriter<W> {
+    fn record(&mut self, event: &TraceEvent) -> Result<(), RuntimeError> {
+        let Some(timing) = self.timing.as_mut() else {
+            writeln!(self.out, "{}", event)?;
+            return Ok(());
+        };
+        let duration = timing.duration(event, Instant::now());
+        writeln!(self.out, "{} duration_ns={}", event, duration.as_nanos())?;
+        // writing the line isn't part of the next instruction
+        timing.last = Instant::now();
+        Ok(())
+    }
+
+    fn finish(&mut self, checksum: u64) -> Result<(), RuntimeError> {
+        writeln!(self.out, "checksum={:016x}", checksum)?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Trace {
+    pub program: Option<String>,
+    pub events: Vec<TraceEvent>,
+    // final tape checksum, traces written before it was recorded don't have one
+    pub checksum: Option<u64>,
+}
+
+impl Trace {
+    pub fn parse(source: &str) -> Result<Trace, String> {
+        let mut program = None;
+        let mut events = Vec::new();
+        let mut checksum = None;
+
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(path) = line.strip_prefix("program=") {
+                program = Some(path.to_string());
+                continue;
+            }
+
+            if let Some(value) = line.strip_prefix("checksum=") {
+                checksum = Some(u64::from_str_radix(value, 16).map_err(|_| {
+                    format!("line {}: invalid value for `checksum`: `{}`", i + 1, value)
+                })?);
+                continue;
+            }
+
+            events.push(TraceEvent::parse(line).map_err(|err| format!("line {}: {}", i + 1, err))?);
+        }
+
+        Ok(Trace {
+            program,
+            events,
+            checksum,
+        })
+    }
+
+    // index of the first event that differs from `other`'s, including one trace ending
+    // before the other, `None` when both ran exactly the same events. every event holds
+    // the state right after it, so a different tape before an instruction shows up one
+    // event earlier
+    pub fn divergence(&self, other: &Trace) -> Option<usize> {
+        (0..self.events.len().max(other.events.len()))
+            .find(|&i| self.events.get(i) != other.events.get(i))
+    }
+
+    // bytes the traced run consumed from stdin, in order
+    pub fn input(&self) -> Vec<u8> {
+        self.events
+            .iter()
+            .filter(|event| event.op == "READ")
+            .map(|event| event.cell)
+            .collect()
+    }
+}
+
+// compares a live run against a recorded trace, stopping at the first divergence
+pub struct ReplayVerifier {
+    expected: Vec<TraceEvent>,
+    checksum: Option<u64>,
+    next: usize,
+}
+
+impl ReplayVerifier {
+    pub fn new(trace: &Trace) -> Self {
+        ReplayVerifier {
+            expected: trace.events.clone(),
+            checksum: trace.checksum,
+            next: 0,
+        }
+    }
+}
+
+impl TraceSink for ReplayVerifier {
+    fn record(&mut self, event: &TraceEvent) -> Result<(), RuntimeError> {
+        match self.expected.get(self.next) {
+            Some(expected) if expected == event => {
+                self.next += 1;
+                Ok(())
+            }
+            Some(expected) => Err(RuntimeError::TraceDivergence {
+                step: event.step,
+                expected: expected.to_string(),
+                actual: event.to_string(),
+            }),
+            None => Err(RuntimeError::TraceDivergence {
+                step: event.step,
+                expected: "end of trace".to_string(),
+                actual: event.to_string(),
+            }),
+        }
+    }
+
+    fn finish(&mut self, checksum: u64) -> Result<(), RuntimeError> {
+        match (self.expected.get(self.next), self.checksum) {
+            (Some(expected), _) => Err(RuntimeError::TraceDivergence {
+                step: expected.step,
+                expected: expected.to_string(),
+                actual: "end of program".to_string(),
+            }),
+            // every step matched but something the events don't show, like a cell the
+            // pointer left behind, came out different
+            (None, Some(expected)) if expected != checksum => Err(RuntimeError::TraceDivergence {
+                step: self.expected.last().map_or(0, |event| event.step),
+                expected: format!("final tape checksum {:016x}", expected),
+                actual: format!("final tape checksum {:016x}", checksum),
+            }),
+            (None, _) => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use crate::lexer::{lex, OpCode};
+use crate::parser::parse;
+
+fn bf_opcode(c: char) -> Option<OpCode> {
+    match c {
+        '+' => Some(OpCode::Increment),
+        '-' => Some(OpCode::Decrement),
+        '[' => Some(OpCode::LoopBegin),
+        ']' => Some(OpCode::LoopEnd),
+        '<' => Some(OpCode::DecrementPointer),
+        '>' => Some(OpCode::IncrementPointer),
+        '.' => Some(OpCode::Write),
+        ',' => Some(OpCode::Read),
+        _ => None,
+    }
+}
+
+fn bf_char(op: OpCode) -> char {
+    match op {
+        OpCode::Increment => '+',
+        OpCode::Decrement => '-',
+        OpCode::LoopBegin => '[',
+        OpCode::LoopEnd => ']',
+        OpCode::DecrementPointer => '<',
+        OpCode::IncrementPointer => '>',
+        OpCode::Write => '.',
+        OpCode::Read => ',',
+    }
+}
+
+pub fn svo_word(op: OpCode) -> &'static str {
+    match op {
+        OpCode::Increment => "svo",
+        OpCode::Decrement => "svoo",
+        OpCode::LoopBegin => "svooo",
+        OpCode::LoopEnd => "svoooo",
+        OpCode::DecrementPointer => "svooooo",
+        OpCode::IncrementPointer => "svoooooo",
+        OpCode::Write => "svooooooo",
+        OpCode::Read => "svoooooooo",
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dialect {
+    #[default]
+    Bf,
+    // `Ook. Ook?` pairs, see `ook_opcode`
+    Ook,
+    // bf spelled with groups of three spaces and tabs, see `whitespace_opcode`
+    Whitespace,
+}
+
+impl Dialect {
+    pub fn from_name(name: &str) -> Option<Dialect> {
+        match name {
+            "bf" => Some(Dialect::Bf),
+            "ook" => Some(Dialect::Ook),
+            "whitespace" => Some(Dialect::Whitespace),
+            _ => None,
+        }
+    }
+}
+
+pub fn translate(source: &str, dialect: Dialect) -> (String, Vec<String>) {
+    match dialect {
+        Dialect::Bf => translate_bf(source),
+        Dialect::Ook => translate_ook(source),
+        Dialect::Whitespace => translate_whitespace(source),
+    }
+}
+
+fn ook_opcode(first: u8, second: u8) -> Option<OpCode> {
+    match (first, second) {
+        (b'.', b'?') => Some(OpCode::IncrementPointer),
+        (b'?', b'.') => Some(OpCode::DecrementPointer),
+        (b'.', b'.') => Some(OpCode::Increment),
+        (b'!', b'!') => Some(OpCode::Decrement),
+        (b'!', b'.') => Some(OpCode::Write),
+        (b'.', b'!') => Some(OpCode::Read),
+        (b'!', b'?') => Some(OpCode::LoopBegin),
+        (b'?', b'!') => Some(OpCode::LoopEnd),
+        _ => None,
+    }
+}
+
+// every `Ook` followed by `.`, `?` or `!` is half an opcode, anything else is ignored
+pub fn translate_ook(source: &str) -> (String, Vec<String>) {
+    let bytes = source.as_bytes();
+    let mut words: Vec<(usize, u8)> = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i..].starts_with(b"Ook") && matches!(bytes.get(i + 3), Some(b'.' | b'?' | b'!')) {
+            words.push((i, bytes[i + 3]));
+            i += 4;
+        } else {
+            i += 1;
+        }
+    }
+
+    let mut out = String::new();
+    let mut warnings = Vec::new();
+    for pair in words.chunks(2) {
+        match pair {
+            [(_, first), (_, second)] => match ook_opcode(*first, *second) {
+                Some(op) => out.push_str(svo_word(op)),
+                None => warnings.push(format!(
+                    "`Ook{} Ook{}` at byte {} is not an Ook! opcode, skipped",
+                    *first as char, *second as char, pair[0].0
+                )),
+            },
+            [(offset, _)] => warnings.push(format!(
+                "unpaired `Ook` at byte {} at the end of the program, skipped",
+                offset
+            )),
+            _ => unreachable!("chunks of two"),
+        }
+    }
+    out.push('\n');
+
+    (out, warnings)
+}
+
+// three bits per opcode, space is 0 and tab is 1, in bf's `+-><.,[]` order
+fn whitespace_opcode(bits: usize) -> OpCode {
+    match bits {
+        0b000 => OpCode::Increment,
+        0b001 => OpCode::Decrement,
+        0b010 => OpCode::IncrementPointer,
+        0b011 => OpCode::DecrementPointer,
+        0b100 => OpCode::Write,
+        0b101 => OpCode::Read,
+        0b110 => OpCode::LoopBegin,
+        _ => OpCode::LoopEnd,
+    }
+}
+
+// only spaces and tabs carry meaning, newlines and everything else are ignored
+pub fn translate_whitespace(source: &str) -> (String, Vec<String>) {
+    let bits: Vec<usize> = source
+        .bytes()
+        .filter_map(|b| match b {
+            b' ' => Some(0),
+            b'\t' => Some(1),
+            _ => None,
+        })
+        .collect();
+
+    let mut out = String::new();
+    let mut groups = bits.chunks_exact(3);
+    for group in &mut groups {
+        out.push_str(svo_word(whitespace_opcode(
+            group[0] << 2 | group[1] << 1 | group[2],
+        )));
+    }
+    out.push('\n');
+
+    let mut warnings = Vec::new();
+    if !groups.remainder().is_empty() {
+        warnings.push(format!(
+            "{} trailing space or tab characters don't make up an opcode, skipped",
+            groups.remainder().len()
+        ));
+    }
+
+    (out, warnings)
+}
+
+pub fn bf_to_svo(source: &str) -> String {
+    translate_bf(source).0
+}
+
+// translates every bf opcode exactly once and keeps everything else as is, then lexes
+// the result and warns if it doesn't read back as the same opcodes, e.g. when a bf
+// comment happens to contain an `sv` word
+pub fn translate_bf(source: &str) -> (String, Vec<String>) {
+    let mut out = String::with_capacity(source.len() * 6);
+    let mut expected = Vec::new();
+    let mut offsets = Vec::new();
+
+    for (offset, c) in source.char_indices() {
+        match bf_opcode(c) {
+            Some(op) => {
+                out.push_str(svo_word(op));
+                expected.push(op);
+                offsets.push(offset);
+            }
+            None => out.push(c),
+        }
+    }
+
+    let mut warnings = Vec::new();
+    let actual = lex(&out);
+    if let Some(i) =
+        (0..expected.len().max(actual.len())).find(|&i| expected.get(i) != actual.get(i))
+    {
+        warnings.push(match offsets.get(i) {
+            Some(offset) => format!(
+                "translated output stops matching the source at bf opcode `{}` (byte {}), look for `sv` words in the text before it",
+                &source[*offset..*offset + 1],
+                offset
+            ),
+            None => "translated output ends with opcodes the source doesn't have, look for `sv` words in the trailing text".to_string(),
+        });
+    }
+
+    (out, warnings)
+}
+
+// one `sv` word per line with the text between opcodes kept as `#` comments where it
+// was, a comment can't turn into an opcode so unlike `translate_bf` nothing can go wrong
+pub fn translate_bf_with_comments(source: &str) -> String {
+    let mut out = String::with_capacity(source.len() * 6);
+    let mut comment = String::new();
+
+    for c in source.chars() {
+        let op = bf_opcode(c);
+        // a comment runs up to the next opcode or the end of its line
+        if op.is_none() && c != '\n' {
+            comment.push(c);
+            continue;
+        }
+        push_comment(&mut out, &comment);
+        comment.clear();
+        if let Some(op) = op {
+            out.push_str(svo_word(op));
+            out.push('\n');
+        }
+    }
+    push_comment(&mut out, &comment);
+
+    out
+}
+
+fn push_comment(out: &mut String, comment: &str) {
+    let text = comment.trim();
+    if !text.is_empty() {
+        out.push_str("# ");
+        out.push_str(text);
+        out.push('\n');
+    }
+}
+
+// the reverse of `bf_to_svo`, bare bf opcodes with comments and unknown words dropped
+pub fn svo_to_bf(source: &str) -> String {
+    lex(source).into_iter().map(bf_char).collect()
+}
+
+// checks that `svo` parses and reads back as exactly the opcodes of `bf`, returning how
+// many there are, or where the two first differ
+pub fn validate_bf(bf: &str, svo: &str) -> Result<usize, String> {
+    parse(&lex(svo)).map_err(|err| format!("translated program doesn't parse: {}", err))?;
+
+    let expected: Vec<char> = bf.chars().filter(|&c| bf_opcode(c).is_some()).collect();
+    let actual: Vec<char> = svo_to_bf(svo).chars().collect();
+    let shown = |c: Option<&char>| c.map_or("nothing".to_string(), |c| format!("`{}`", c));
+    match (0..expected.len().max(actual.len())).find(|&i| expected.get(i) != actual.get(i)) {
+        Some(i) => Err(format!(
+            "round trip differs at opcode #{}: the source has {}, the translation reads back as {}",
+            i,
+            shown(expected.get(i)),
+            shown(actual.get(i))
+        )),
+        None => Ok(expected.len()),
+    }
+}
@@ -0,0 +1,127 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OpCode {
+    IncrementPointer,
+    DecrementPointer,
+    Increment,
+    Decrement,
+    Write,
+    Read,
+    LoopBegin,
+    LoopEnd,
+}
+
+// the keyword the opcode is written as
+impl fmt::Display for OpCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let keyword = match self {
+            OpCode::Increment => "svo",
+            OpCode::Decrement => "svoo",
+            OpCode::LoopBegin => "svooo",
+            OpCode::LoopEnd => "svoooo",
+            OpCode::DecrementPointer => "svooooo",
+            OpCode::IncrementPointer => "svoooooo",
+            OpCode::Write => "svooooooo",
+            OpCode::Read => "svoooooooo",
+        };
+        f.write_str(keyword)
+    }
+}
+
+// an `sv` word with its byte range in the source, `op` is `None` when the
+// number of `o`s doesn't name an opcode and the word is skipped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token {
+    pub op: Option<OpCode>,
+    pub start: usize,
+    pub end: usize,
+}
+
+pub fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let bytes = source.as_bytes();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'#' {
+            // comments run to the end of the line
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+        } else if i + 1 < bytes.len() && bytes[i] == b's' && bytes[i + 1] == b'v' {
+            let mut o_count = 0;
+            let mut j = i + 2;
+
+            while j < bytes.len() && bytes[j] == b'o' {
+                o_count += 1;
+                j += 1;
+            }
+
+            let op = match o_count {
+                1 => Some(OpCode::Increment),        // svo
+                2 => Some(OpCode::Decrement),        // svoo
+                3 => Some(OpCode::LoopBegin),        // svooo
+                4 => Some(OpCode::LoopEnd),          // svoooo
+                5 => Some(OpCode::DecrementPointer), // svooooo
+                6 => Some(OpCode::IncrementPointer), // svoooooo
+                7 => Some(OpCode::Write),            // svooooooo
+                8 => Some(OpCode::Read),             // svoooooooo
+                _ => None,
+            };
+
+            tokens.push(Token {
+                op,
+                start: i,
+                end: j,
+            });
+
+            i = j;
+        } else {
+            i += 1;
+        }
+    }
+
+    tokens
+}
+
+// `#debug_print "label"` comments as (number of opcodes before the comment, label)
+pub fn debug_prints(source: &str) -> Vec<(usize, String)> {
+    let tokens = tokenize(source);
+    let mut prints = Vec::new();
+    let mut line_start = 0;
+
+    for line in source.split_inclusive('\n') {
+        // sv words never hold a `#`, so the first one on a line starts its comment
+        if let Some(hash) = line.find('#') {
+            if let Some(label) = debug_print_label(&line[hash..]) {
+                let start = line_start + hash;
+                let position = tokens
+                    .iter()
+                    .filter(|token| token.op.is_some() && token.start < start)
+                    .count();
+                prints.push((position, label));
+            }
+        }
+        line_start += line.len();
+    }
+
+    prints
+}
+
+// the label of a comment that is a `#debug_print` directive, quotes around it are optional
+pub fn debug_print_label(comment: &str) -> Option<String> {
+    let rest = comment.strip_prefix("#debug_print")?.trim();
+    let label = rest
+        .strip_prefix('"')
+        .and_then(|rest| rest.split_once('"'))
+        .map_or(rest, |(label, _)| label);
+    Some(label.to_string())
+}
+
+pub fn lex(source: &str) -> Vec<OpCode> {
+    tokenize(source)
+        .into_iter()
+        .filter_map(|token| token.op)
+        .collect()
+}
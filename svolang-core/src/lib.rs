@@ -0,0 +1,5 @@
+// the lexer and parser on their own, shared by `svolang` and the `svo!` macro so both
+// accept exactly the same programs
+
+pub mod lexer;
+pub mod parser;
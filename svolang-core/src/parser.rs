@@ -0,0 +1,334 @@
+use std::fmt;
+
+use crate::lexer::OpCode;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    IncrementPointer,
+    DecrementPointer,
+    Increment,
+    Decrement,
+    Write,
+    Read,
+    Loop(Vec<Instruction>),
+
+    // produced by the optimizer
+    Add(u8),
+    Move(isize),
+    SetCell(u8),
+    // clears `len` cells starting `start_offset` cells from the pointer, which stays put
+    BulkZero {
+        start_offset: isize,
+        len: usize,
+    },
+    // moves `direction` cells at a time until the pointer lands on a zero, `[>]` is 1, `[<<]` is -2
+    FindZero {
+        direction: isize,
+    },
+    // adds `factor` times the current cell to the one `offset` away and clears the current
+    // one, `[->+<]` is offset 1 and factor 1
+    MultiplyAdd {
+        offset: isize,
+        factor: u8,
+    },
+    // copies `len` cells starting `src_offset` cells from the pointer to the ones starting
+    // `dst_offset` away, overlapping ranges copy like `memmove`, the pointer stays put
+    CopyRange {
+        src_offset: isize,
+        dst_offset: isize,
+        len: usize,
+    },
+
+    // from a `#debug_print "label"` comment, covers no opcode
+    DebugPrint {
+        label: String,
+    },
+
+    // a built-in macro like `clear_range` inserted by the generator, covers no opcode and
+    // has to be expanded by `MacroExpander` before the program runs
+    MacroCall {
+        name: String,
+        args: Vec<u8>,
+    },
+}
+
+impl Instruction {
+    // number of opcodes the instruction was parsed from, loops count both brackets
+    pub fn opcode_len(&self) -> usize {
+        match self {
+            Instruction::Loop(body) => body.iter().map(Instruction::opcode_len).sum::<usize>() + 2,
+            Instruction::DebugPrint { .. } | Instruction::MacroCall { .. } => 0,
+            _ => 1,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instruction::IncrementPointer => "RIGHT",
+            Instruction::DecrementPointer => "LEFT",
+            Instruction::Increment => "INC",
+            Instruction::Decrement => "DEC",
+            Instruction::Write => "WRITE",
+            Instruction::Read => "READ",
+            Instruction::Loop(_) => "LOOP",
+            Instruction::Add(_) => "ADD",
+            Instruction::Move(_) => "MOVE",
+            Instruction::SetCell(_) => "SET",
+            Instruction::BulkZero { .. } => "ZERO",
+            Instruction::FindZero { .. } => "FIND",
+            Instruction::DebugPrint { .. } => "DEBUG",
+            Instruction::MultiplyAdd { .. } => "MULADD",
+            Instruction::CopyRange { .. } => "COPY",
+            Instruction::MacroCall { .. } => "MACRO",
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instruction::IncrementPointer => write!(f, "IncrementPointer"),
+            Instruction::DecrementPointer => write!(f, "DecrementPointer"),
+            Instruction::Increment => write!(f, "Increment"),
+            Instruction::Decrement => write!(f, "Decrement"),
+            Instruction::Write => write!(f, "Write"),
+            Instruction::Read => write!(f, "Read"),
+            Instruction::Loop(body) => write!(f, "Loop {{ body: {} instructions }}", body.len()),
+            Instruction::Add(n) => write!(f, "Add({})", n),
+            Instruction::Move(offset) => write!(f, "Move({})", offset),
+            Instruction::SetCell(value) => write!(f, "SetCell({})", value),
+            Instruction::BulkZero { start_offset, len } => write!(
+                f,
+                "BulkZero {{ start_offset: {}, len: {} }}",
+                start_offset, len
+            ),
+            Instruction::FindZero { direction } => {
+                write!(f, "FindZero {{ direction: {} }}", direction)
+            }
+            Instruction::DebugPrint { label } => write!(f, "DebugPrint {{ label: {:?} }}", label),
+            Instruction::MultiplyAdd { offset, factor } => write!(
+                f,
+                "MultiplyAdd {{ offset: {}, factor: {} }}",
+                offset, factor
+            ),
+            Instruction::CopyRange {
+                src_offset,
+                dst_offset,
+                len,
+            } => write!(
+                f,
+                "CopyRange {{ src_offset: {}, dst_offset: {}, len: {} }}",
+                src_offset, dst_offset, len
+            ),
+            Instruction::MacroCall { name, args } => {
+                write!(f, "MacroCall {{ name: {:?}, args: {:?} }}", name, args)
+            }
+        }
+    }
+}
+
+// instructions as indented pseudo-assembly, one per line with loop bodies in braces:
+// `INC`, `RIGHT`, `LOOP {`, operands after the mnemonic for the optimizer's instructions
+pub struct ProgramDisplay<'a>(pub &'a [Instruction]);
+
+impl fmt::Display for ProgramDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_block(f, self.0, 0)
+    }
+}
+
+fn write_block(f: &mut fmt::Formatter, instructions: &[Instruction], depth: usize) -> fmt::Result {
+    let indent = "    ".repeat(depth);
+    for instr in instructions {
+        write!(f, "{}{}", indent, instr.mnemonic())?;
+        match instr {
+            Instruction::Loop(body) if body.is_empty() => write!(f, " {{}}")?,
+            Instruction::Loop(body) => {
+                writeln!(f, " {{")?;
+                write_block(f, body, depth + 1)?;
+                write!(f, "{}}}", indent)?;
+            }
+            Instruction::Add(n) => write!(f, " {}", n)?,
+            Instruction::Move(offset) => write!(f, " {}", offset)?,
+            Instruction::SetCell(value) => write!(f, " {}", value)?,
+            Instruction::BulkZero { start_offset, len } => write!(f, " {} {}", start_offset, len)?,
+            Instruction::FindZero { direction } => write!(f, " {}", direction)?,
+            Instruction::MultiplyAdd { offset, factor } => write!(f, " {} {}", offset, factor)?,
+            Instruction::CopyRange {
+                src_offset,
+                dst_offset,
+                len,
+            } => write!(f, " {} {} {}", src_offset, dst_offset, len)?,
+            Instruction::DebugPrint { label } => write!(f, " {:?}", label)?,
+            Instruction::MacroCall { name, args } => {
+                write!(f, " {}", name)?;
+                for arg in args {
+                    write!(f, " {}", arg)?;
+                }
+            }
+            _ => (),
+        }
+        writeln!(f)?;
+    }
+    Ok(())
+}
+
+// brackets only make a loop together with their partner, every other opcode is an
+// instruction of its own
+impl From<OpCode> for Option<Instruction> {
+    fn from(op: OpCode) -> Self {
+        match op {
+            OpCode::IncrementPointer => Some(Instruction::IncrementPointer),
+            OpCode::DecrementPointer => Some(Instruction::DecrementPointer),
+            OpCode::Increment => Some(Instruction::Increment),
+            OpCode::Decrement => Some(Instruction::Decrement),
+            OpCode::Write => Some(Instruction::Write),
+            OpCode::Read => Some(Instruction::Read),
+            OpCode::LoopBegin | OpCode::LoopEnd => None,
+        }
+    }
+}
+
+impl TryFrom<OpCode> for Instruction {
+    type Error = BracketError;
+
+    fn try_from(op: OpCode) -> Result<Self, Self::Error> {
+        Option::from(op).ok_or(BracketError(op))
+    }
+}
+
+// the bracket an `Instruction` couldn't be made from on its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BracketError(pub OpCode);
+
+impl fmt::Display for BracketError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "`{}` is a bracket, only a matched pair makes a loop",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for BracketError {}
+
+pub const MNEMONICS: [&str; 16] = [
+    "RIGHT", "LEFT", "INC", "DEC", "WRITE", "READ", "LOOP", "ADD", "MOVE", "SET", "ZERO", "FIND",
+    "DEBUG", "MULADD", "MACRO", "COPY",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    UnmatchedLoopEnd { position: usize },
+    UnmatchedLoopBegin { position: usize },
+}
+
+impl ParseError {
+    // opcode index the error points at
+    pub fn position(&self) -> usize {
+        match self {
+            ParseError::UnmatchedLoopEnd { position }
+            | ParseError::UnmatchedLoopBegin { position } => *position,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::UnmatchedLoopEnd { position } => {
+                write!(f, "loop ending at #{} has no beginning", position)
+            }
+            ParseError::UnmatchedLoopBegin { position } => {
+                write!(
+                    f,
+                    "loop that starts at #{} has no matching ending!",
+                    position
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub fn parse(opcodes: &[OpCode]) -> Result<Vec<Instruction>, ParseError> {
+    parse_inner(opcodes, Vec::new(), None)
+}
+
+// `prints` from `lexer::debug_prints`, each becomes a `DebugPrint` in front of the opcode
+// it precedes
+pub fn parse_with_debug_prints(
+    opcodes: &[OpCode],
+    prints: Vec<(usize, String)>,
+) -> Result<Vec<Instruction>, ParseError> {
+    parse_inner(opcodes, prints, None)
+}
+
+const PROGRESS_INTERVAL: usize = 1000;
+
+// `parse_with_debug_prints` that also calls `progress` with (parsed, total) every
+// `PROGRESS_INTERVAL` opcodes and once at the end
+pub fn parse_with_progress(
+    opcodes: &[OpCode],
+    prints: Vec<(usize, String)>,
+    progress: Option<Box<dyn Fn(usize, usize)>>,
+) -> Result<Vec<Instruction>, ParseError> {
+    parse_inner(opcodes, prints, progress)
+}
+
+fn parse_inner(
+    opcodes: &[OpCode],
+    prints: Vec<(usize, String)>,
+    progress: Option<Box<dyn Fn(usize, usize)>>,
+) -> Result<Vec<Instruction>, ParseError> {
+    let mut program: Vec<Instruction> = Vec::new();
+    let mut loop_stack: Vec<(usize, Vec<Instruction>)> = Vec::new();
+    let mut prints = prints.into_iter().peekable();
+
+    for (i, op) in opcodes.iter().enumerate() {
+        while let Some((_, label)) = prints.next_if(|(position, _)| *position <= i) {
+            program.push(Instruction::DebugPrint { label });
+        }
+
+        if let Some(progress) = &progress {
+            if i > 0 && i % PROGRESS_INTERVAL == 0 {
+                progress(i, opcodes.len());
+            }
+        }
+
+        let instr = match (*op).into() {
+            Some(instr) => instr,
+
+            None if *op == OpCode::LoopBegin => {
+                loop_stack.push((i, std::mem::take(&mut program)));
+                continue;
+            }
+
+            None => match loop_stack.pop() {
+                Some((_, outer)) => {
+                    let body = std::mem::replace(&mut program, outer);
+                    Instruction::Loop(body)
+                }
+                None => return Err(ParseError::UnmatchedLoopEnd { position: i }),
+            },
+        };
+
+        program.push(instr);
+    }
+
+    program.extend(prints.map(|(_, label)| Instruction::DebugPrint { label }));
+
+    if let Some((loop_start, _)) = loop_stack.first() {
+        return Err(ParseError::UnmatchedLoopBegin {
+            position: *loop_start,
+        });
+    }
+
+    if let Some(progress) = &progress {
+        progress(opcodes.len(), opcodes.len());
+    }
+
+    Ok(program)
+}
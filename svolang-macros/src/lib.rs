@@ -0,0 +1,141 @@
+// `svo!("...")` checks a program while the calling crate compiles and expands to the
+// `svolang::program::Program` it parses to, so nothing is lexed or parsed at runtime
+
+use proc_macro::{Literal, TokenStream, TokenTree};
+
+// the interpreter's own lexer and parser, so the macro accepts exactly what `run` does
+use svolang_core::lexer;
+use svolang_core::parser::{self, Instruction};
+
+#[proc_macro]
+pub fn svo(input: TokenStream) -> TokenStream {
+    match expand(input) {
+        Ok(tokens) => tokens,
+        Err(message) => format!("::core::compile_error!({:?})", message)
+            .parse()
+            .expect("compile_error! invocation is valid Rust"),
+    }
+}
+
+fn expand(input: TokenStream) -> Result<TokenStream, String> {
+    let mut tokens = input.into_iter();
+    let literal = match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Literal(literal)), None) => literal,
+        _ => return Err("svo! expects a single string literal".to_string()),
+    };
+
+    let source = string_value(&literal)?;
+    let prints = lexer::debug_prints(&source);
+    let instructions = parser::parse_with_debug_prints(&lexer::lex(&source), prints)
+        .map_err(|err| format!("invalid svolang program: {}", err))?;
+
+    let mut code = String::from("::svolang::program::Program::new(");
+    emit(&instructions, &mut code);
+    code.push(')');
+    code.parse()
+        .map_err(|err| format!("svo! generated invalid code: {}", err))
+}
+
+fn emit(instructions: &[Instruction], code: &mut String) {
+    code.push_str("::std::vec![");
+    for instr in instructions {
+        code.push_str("::svolang::parser::Instruction::");
+        match instr {
+            Instruction::IncrementPointer => code.push_str("IncrementPointer"),
+            Instruction::DecrementPointer => code.push_str("DecrementPointer"),
+            Instruction::Increment => code.push_str("Increment"),
+            Instruction::Decrement => code.push_str("Decrement"),
+            Instruction::Write => code.push_str("Write"),
+            Instruction::Read => code.push_str("Read"),
+            Instruction::Loop(body) => {
+                code.push_str("Loop(");
+                emit(body, code);
+                code.push(')');
+            }
+            Instruction::Add(n) => code.push_str(&format!("Add({})", n)),
+            Instruction::Move(offset) => code.push_str(&format!("Move({})", offset)),
+            Instruction::SetCell(value) => code.push_str(&format!("SetCell({})", value)),
+            Instruction::BulkZero { start_offset, len } => code.push_str(&format!(
+                "BulkZero {{ start_offset: {}, len: {} }}",
+                start_offset, len
+            )),
+            Instruction::FindZero { direction } => {
+                code.push_str(&format!("FindZero {{ direction: {} }}", direction))
+            }
+            Instruction::DebugPrint { label } => code.push_str(&format!(
+                "DebugPrint {{ label: ::std::string::String::from({:?}) }}",
+                label
+            )),
+            Instruction::MultiplyAdd { offset, factor } => code.push_str(&format!(
+                "MultiplyAdd {{ offset: {}, factor: {} }}",
+                offset, factor
+            )),
+            Instruction::CopyRange {
+                src_offset,
+                dst_offset,
+                len,
+            } => code.push_str(&format!(
+                "CopyRange {{ src_offset: {}, dst_offset: {}, len: {} }}",
+                src_offset, dst_offset, len
+            )),
+            Instruction::MacroCall { name, args } => code.push_str(&format!(
+                "MacroCall {{ name: ::std::string::String::from({:?}), args: ::std::vec!{:?} }}",
+                name, args
+            )),
+        }
+        code.push(',');
+    }
+    code.push(']');
+}
+
+// the value of a plain or raw string literal, from its source form
+fn string_value(literal: &Literal) -> Result<String, String> {
+    let repr = literal.to_string();
+
+    if let Some(raw) = repr.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        return raw
+            .get(hashes + 1..raw.len() - hashes - 1)
+            .map(str::to_string)
+            .ok_or_else(|| "svo! expects a string literal".to_string());
+    }
+
+    let inner = repr
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or("svo! expects a string literal")?;
+
+    let mut value = String::new();
+    let mut chars = inner.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some('r') => value.push('\r'),
+            Some('t') => value.push('\t'),
+            Some('0') => value.push('\0'),
+            Some(c @ ('\\' | '"' | '\'')) => value.push(c),
+            // a line continuation swallows the newline and the next line's indentation
+            Some('\n') => while chars.next_if(|c| c.is_whitespace()).is_some() {},
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                let byte = u8::from_str_radix(&hex, 16).map_err(|_| "invalid \\x escape")?;
+                value.push(byte as char);
+            }
+            Some('u') => {
+                let code: String = chars.by_ref().skip(1).take_while(|c| *c != '}').collect();
+                let c = u32::from_str_radix(&code, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or("invalid \\u escape")?;
+                value.push(c);
+            }
+            _ => return Err("unsupported escape in svo! literal".to_string()),
+        }
+    }
+
+    Ok(value)
+}
@@ -0,0 +1,117 @@
+use std::fs;
+
+use svolang::bytecode::{ByteCodeProgram, ByteCodeVM};
+use svolang::interpreter::{CapturedOutput, Interpreter, InterpreterConfig, TapeInit};
+use svolang::lexer::lex;
+use svolang::optimize::{Optimizer, OptimizerPipeline};
+use svolang::parser::{parse, Instruction};
+
+// enough for the small examples to finish, mandelbrot stops at the limit in both backends
+const MAX_STEPS: u64 = 1_000_000;
+
+struct Outcome {
+    output: Vec<u8>,
+    steps: u64,
+    result: Result<(), String>,
+}
+
+fn config(parallel_loops: bool) -> InterpreterConfig {
+    InterpreterConfig {
+        max_steps: Some(MAX_STEPS),
+        parallel_loops,
+        ..InterpreterConfig::default()
+    }
+}
+
+fn tree_walking(program: &[Instruction], parallel_loops: bool) -> Outcome {
+    let output = CapturedOutput::new();
+    let mut interpreter = Interpreter::new(config(parallel_loops));
+    interpreter.set_output(output.clone());
+    let result = interpreter.run(program).map_err(|err| err.to_string());
+    Outcome {
+        output: output.contents(),
+        steps: interpreter.steps(),
+        result,
+    }
+}
+
+fn bytecode(program: &[Instruction]) -> Outcome {
+    let output = CapturedOutput::new();
+    let mut interpreter = Interpreter::new(config(false));
+    interpreter.set_output(output.clone());
+    let mut vm = ByteCodeVM::new(ByteCodeProgram::compile(program), interpreter);
+    let result = vm.run().map_err(|err| err.to_string());
+    Outcome {
+        output: output.contents(),
+        steps: vm.interpreter.steps(),
+        result,
+    }
+}
+
+// every example at every optimization level, and with loops spread over threads, runs the
+// same in the tree-walking interpreter as in the bytecode VM
+#[test]
+fn backends_agree_on_the_examples() {
+    let mut examples: Vec<_> = fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/examples"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "svo"))
+        .collect();
+    examples.sort();
+    assert!(!examples.is_empty());
+
+    for path in examples {
+        let parsed = parse(&lex(&fs::read_to_string(&path).unwrap())).unwrap();
+        for level in 0..=OptimizerPipeline::MAX_LEVEL {
+            let program = OptimizerPipeline::level(level)
+                .unwrap()
+                .optimize(parsed.clone());
+            let expected = bytecode(&program);
+            for parallel_loops in [false, true] {
+                let actual = tree_walking(&program, parallel_loops);
+                let case = format!(
+                    "{} at level {}, parallel loops {}",
+                    path.display(),
+                    level,
+                    parallel_loops
+                );
+                assert_eq!(actual.output, expected.output, "{}", case);
+                assert_eq!(actual.steps, expected.steps, "{}", case);
+                assert_eq!(actual.result, expected.result, "{}", case);
+            }
+        }
+    }
+}
+
+// 40000 iterations of `+>>` over a tape of 0xff cells, enough to be split across threads
+#[test]
+fn parallel_loop_matches_bytecode() {
+    let config = |parallel_loops| InterpreterConfig {
+        tape_size: 100_000,
+        start_pointer: 0,
+        tape_init: TapeInit::Ones,
+        parallel_loops,
+        ..InterpreterConfig::default()
+    };
+    let program = [
+        Instruction::Move(80_000),
+        Instruction::SetCell(0),
+        Instruction::Move(-80_000),
+        Instruction::Loop(vec![Instruction::Add(1), Instruction::Move(2)]),
+    ];
+
+    let mut interpreter = Interpreter::new(config(true));
+    interpreter.run(&program).unwrap();
+    let mut vm = ByteCodeVM::new(
+        ByteCodeProgram::compile(&program),
+        Interpreter::new(config(false)),
+    );
+    vm.run().unwrap();
+
+    assert_eq!(interpreter.tape().cells(), vm.interpreter.tape().cells());
+    assert_eq!(
+        interpreter.tape().pointer(),
+        vm.interpreter.tape().pointer()
+    );
+    assert_eq!(interpreter.steps(), vm.interpreter.steps());
+}
@@ -0,0 +1,170 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+// runs the built binary, stdin is empty
+fn svolang(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_svolang"))
+        .args(args)
+        .output()
+        .expect("svolang runs")
+}
+
+// a file in a directory of its own under the system temp dir
+fn scratch_file(test: &str, name: &str, contents: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("svolang-cli-{}-{}", test, std::process::id()));
+    fs::create_dir_all(&dir).expect("scratch dir is created");
+    let path = dir.join(name);
+    fs::write(&path, contents).expect("scratch file is written");
+    path
+}
+
+#[test]
+fn expand_writes_header_and_is_idempotent() {
+    let source = scratch_file(
+        "expand",
+        "program.svo",
+        "# a comment\nsvo svo svooo svoo svoooooo svo svooooo svoooo\nsvoooooo svooooooo\n",
+    );
+    let once = scratch_file("expand", "once.svo", "");
+    let twice = scratch_file("expand", "twice.svo", "");
+
+    let output = svolang(&[
+        "expand",
+        source.to_str().unwrap(),
+        "-o",
+        once.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    let expanded = fs::read_to_string(&once).unwrap();
+    assert!(expanded.starts_with("# generated by svolang expand\n"));
+    assert!(!expanded.contains("a comment"));
+
+    let output = svolang(&[
+        "expand",
+        once.to_str().unwrap(),
+        "-o",
+        twice.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(fs::read_to_string(&twice).unwrap(), expanded);
+
+    let output = svolang(&["run", once.to_str().unwrap()]);
+    assert_eq!(output.stdout, b"\x02");
+}
+
+#[test]
+fn verbose_parse_reports_parse_errors_as_parse_errors() {
+    let source = scratch_file("verbose-parse-error", "program.svo", "svooo svo\n");
+    let output = svolang(&[
+        "--json-errors",
+        "run",
+        "--verbose-parse",
+        source.to_str().unwrap(),
+    ]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(r#""type":"parse""#), "{}", stderr);
+    assert!(stderr.contains(r#""source_position":0"#), "{}", stderr);
+}
+
+#[test]
+fn verbose_parse_keeps_debug_prints() {
+    let source = scratch_file(
+        "verbose-parse-debug-print",
+        "program.svo",
+        "svo svo\n#debug_print \"two\"\nsvo\n",
+    );
+    for args in [
+        vec!["run", "--debug-print"],
+        vec!["run", "--debug-print", "--verbose-parse"],
+    ] {
+        let output = svolang(&[args.as_slice(), &[source.to_str().unwrap()]].concat());
+        assert!(output.status.success(), "{:?}", output);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("[DEBUG two]"), "{:?}: {}", args, stderr);
+    }
+}
+
+#[test]
+fn dialect_file_keeps_debug_prints() {
+    let dialect = scratch_file(
+        "dialect-debug-print",
+        "dialect.toml",
+        "increment = \"inc\"\ndecrement = \"dec\"\nincrement_pointer = \"right\"\n\
+         decrement_pointer = \"left\"\nwrite = \"out\"\nread = \"in\"\n\
+         loop_begin = \"while\"\nloop_end = \"end\"\n",
+    );
+    let source = scratch_file(
+        "dialect-debug-print",
+        "program.txt",
+        "inc inc\n#debug_print \"two\"\ninc\n",
+    );
+    for args in [
+        vec!["run", "--debug-print"],
+        vec!["run", "--debug-print", "--verbose-parse"],
+    ] {
+        let output = svolang(
+            &[
+                args.as_slice(),
+                &["--dialect-file", dialect.to_str().unwrap()],
+                &[source.to_str().unwrap()],
+            ]
+            .concat(),
+        );
+        assert!(output.status.success(), "{:?}", output);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("[DEBUG two]"), "{:?}: {}", args, stderr);
+    }
+}
+
+#[test]
+fn coverage_numbers_source_lines_and_survives_runtime_errors() {
+    // `+`, a comment line, `[<+]` walking off the left of the tape, and a `+` never reached
+    let source = scratch_file(
+        "coverage-runtime-error",
+        "program.svo",
+        "svo\n  # note\nsvooo svooooo svo svoooo\nsvo\n",
+    );
+    let tracefile = source.with_extension("info");
+    let output = svolang(&["coverage", source.to_str().unwrap()]);
+    assert!(!output.status.success(), "{:?}", output);
+    let lcov = fs::read_to_string(&tracefile).unwrap();
+    assert!(lcov.contains("DA:1,1\n"), "{}", lcov);
+    assert!(!lcov.contains("DA:2,"), "{}", lcov);
+    assert!(lcov.contains("DA:4,0\n"), "{}", lcov);
+}
+
+#[test]
+fn pack_refuses_settings_the_c_would_ignore() {
+    let source = scratch_file("pack-write-mode", "program.svo", "svo\n");
+    let dir = source.parent().unwrap();
+    fs::write(dir.join("svolang.toml"), "write_mode = \"codepoint\"\n").unwrap();
+    for command in ["pack", "compile"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_svolang"))
+            .args([command, source.to_str().unwrap()])
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "{}: {:?}", command, output);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("`write_mode`"), "{}: {}", command, stderr);
+    }
+}
+
+#[test]
+fn profile_output_takes_a_format_and_a_file() {
+    let source = scratch_file("profile-output", "program.svo", "svo svo\n");
+    let profile = source.with_file_name("profile.csv");
+    let output = svolang(&[
+        "run",
+        "--profile-output",
+        "csv",
+        profile.to_str().unwrap(),
+        source.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    let csv = fs::read_to_string(&profile).unwrap();
+    assert!(csv.starts_with("op,count,"), "{}", csv);
+    assert!(csv.contains("\nINC,2,"), "{}", csv);
+}
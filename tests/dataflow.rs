@@ -0,0 +1,44 @@
+use svolang::dataflow::DataFlowAnalyzer;
+use svolang::optimize::{DeadStoreElim, Optimizer};
+use svolang::parser::Instruction;
+
+// with the input used up a read leaves the cell alone, so the store before it stays live
+#[test]
+fn store_before_a_read_is_live() {
+    let graph = DataFlowAnalyzer::analyze(&[Instruction::SetCell(7), Instruction::Read]);
+    assert_eq!(graph.uses[&0], vec![1]);
+    assert!(!graph.dead_stores().contains(&0));
+}
+
+#[test]
+fn store_overwritten_by_a_set_is_dead() {
+    let graph = DataFlowAnalyzer::analyze(&[
+        Instruction::SetCell(7),
+        Instruction::SetCell(1),
+        Instruction::Write,
+    ]);
+    assert_eq!(graph.dead_stores(), vec![0]);
+}
+
+// `DeadStoreElim` drops exactly what the analyzer calls dead, inside loops too, and keeps
+// what's left on the tape at the end
+#[test]
+fn dead_store_elim_follows_the_analyzer() {
+    let program = vec![
+        Instruction::SetCell(7),
+        Instruction::Loop(vec![Instruction::Add(3), Instruction::SetCell(0)]),
+        Instruction::Read,
+        Instruction::SetCell(2),
+        Instruction::Add(1),
+    ];
+    assert_eq!(
+        DeadStoreElim.optimize(program),
+        vec![
+            Instruction::SetCell(7),
+            Instruction::Loop(vec![Instruction::SetCell(0)]),
+            Instruction::Read,
+            Instruction::SetCell(2),
+            Instruction::Add(1),
+        ]
+    );
+}
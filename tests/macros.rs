@@ -0,0 +1,29 @@
+use svolang::interpreter::RuntimeError;
+use svolang::{svo_test, svo_test_err};
+
+svo_test!(
+    hello_world,
+    path = "../examples/helloworld.svo",
+    expected_output = b"Hello World!\n"
+);
+
+// `,[.,]` echoes its input up to a zero byte
+svo_test!(
+    echo,
+    source = "svoooooooo svooo svooooooo svoooooooo svoooo",
+    input = b"svo\0",
+    expected_output = b"svo"
+);
+
+svo_test_err!(
+    read_past_eof,
+    source = "svoooooooo",
+    error = RuntimeError::Io(_)
+);
+
+// `+[<+]` walks left until it falls off the tape
+svo_test_err!(
+    pointer_off_the_left,
+    source = "svo svooo svooooo svo svoooo",
+    error = RuntimeError::PointerUnderflow
+);
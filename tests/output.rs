@@ -0,0 +1,28 @@
+use svolang::interpreter::{
+    CapturedOutput, Interpreter, InterpreterConfig, RuntimeError, WriteMode,
+};
+use svolang::parser::Instruction;
+
+// `max_output_bytes` is a limit on bytes, a code point past 127 takes two of them
+#[test]
+fn output_limit_counts_encoded_bytes() {
+    let mut interpreter = Interpreter::new(InterpreterConfig {
+        write_mode: WriteMode::UnicodeCodepoint,
+        max_output_bytes: Some(3),
+        output_buffer_size: 0,
+        ..InterpreterConfig::default()
+    });
+    let output = CapturedOutput::new();
+    interpreter.set_output(output.clone());
+
+    let result = interpreter.run(&[
+        Instruction::SetCell(200),
+        Instruction::Write,
+        Instruction::Write,
+    ]);
+    assert!(matches!(
+        result,
+        Err(RuntimeError::OutputLimitExceeded { limit: 3 })
+    ));
+    assert_eq!(output.contents(), [0xc3, 0x88]);
+}
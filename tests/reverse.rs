@@ -0,0 +1,62 @@
+use svolang::interpreter::{CapturedOutput, Interpreter, InterpreterConfig, RuntimeError};
+use svolang::parser::Instruction;
+
+#[test]
+fn lossy_instructions_are_irreversible() {
+    let lossy = [
+        Instruction::SetCell(3),
+        Instruction::BulkZero {
+            start_offset: 0,
+            len: 2,
+        },
+        Instruction::MultiplyAdd {
+            offset: 1,
+            factor: 2,
+        },
+        Instruction::FindZero { direction: 1 },
+        Instruction::CopyRange {
+            src_offset: 0,
+            dst_offset: 1,
+            len: 1,
+        },
+    ];
+    for instr in lossy {
+        let mut interpreter = Interpreter::new(InterpreterConfig::default());
+        let program = [Instruction::Increment, instr.clone()];
+        let err = interpreter.run_reverse(&program).unwrap_err();
+        assert!(
+            matches!(err, RuntimeError::Irreversible { pos: 1, .. }),
+            "{:?}: {}",
+            instr,
+            err
+        );
+    }
+}
+
+#[test]
+fn io_is_skipped() {
+    let mut interpreter = Interpreter::new(InterpreterConfig::default());
+    let output = CapturedOutput::new();
+    interpreter.set_output(output.clone());
+    interpreter
+        .run_reverse(&[Instruction::Add(5), Instruction::Write])
+        .unwrap();
+    assert!(output.contents().is_empty());
+    assert_eq!(interpreter.tape().get(), 251);
+}
+
+#[test]
+fn watched_cells_see_the_undone_value() {
+    let mut interpreter = Interpreter::new(InterpreterConfig::default());
+    let cell = interpreter.tape().pointer();
+    interpreter.watch_cell(cell);
+    interpreter
+        .run_reverse(&[Instruction::Add(5), Instruction::Decrement])
+        .unwrap();
+    let values: Vec<u8> = interpreter.cell_writes()[0]
+        .1
+        .iter()
+        .map(|write| write.value)
+        .collect();
+    assert_eq!(values, [1, 252]);
+}
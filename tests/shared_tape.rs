@@ -0,0 +1,23 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use svolang::interpreter::Tape;
+
+// a clone can't hand out a second `&mut [u8]` over the mapping, it gets its own cells
+#[test]
+fn clone_of_a_shared_tape_is_private() {
+    let dir = env::temp_dir().join(format!("svolang-shared-tape-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("tape");
+    let _ = fs::remove_file(&path);
+
+    let mut tape = Tape::shared(&path, 16, 0).unwrap();
+    tape.cells_mut()[3] = 7;
+    let mut copy = tape.clone();
+    copy.cells_mut()[3] = 9;
+
+    assert_eq!(tape.cells()[3], 7);
+    assert_eq!(copy.cells()[3], 9);
+    assert_eq!(fs::read(&path).unwrap()[1 + 3], 7);
+}
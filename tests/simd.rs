@@ -0,0 +1,55 @@
+use svolang::expand::{self, Macro};
+use svolang::interpreter::{Interpreter, InterpreterConfig};
+use svolang::lexer::lex;
+use svolang::parser::{parse, Instruction};
+use svolang::simd;
+
+// long enough for whole AVX2 chunks and a tail, overlapping either way copies like `memmove`
+#[test]
+fn bulk_copy_matches_copy_within() {
+    let tape: Vec<u8> = (0..200).map(|i| i as u8).collect();
+    for (src, dst, len) in [
+        (0, 100, 70),
+        (100, 0, 70),
+        (10, 30, 90),
+        (30, 10, 90),
+        (5, 5, 9),
+    ] {
+        let mut expected = tape.clone();
+        expected.copy_within(src..src + len, dst);
+        let mut copied = tape.clone();
+        simd::bulk_copy(&mut copied, src, dst, len);
+        assert_eq!(copied, expected, "{} -> {} x {}", src, dst, len);
+    }
+}
+
+// the range copy `memcpy` lowers to leaves the same tape as the loops `expand` spells it as
+#[test]
+fn memcpy_copy_range_matches_its_loops() {
+    let setup: Vec<Instruction> = (1..=6)
+        .flat_map(|i| {
+            [
+                Instruction::Move(i),
+                Instruction::SetCell(i as u8 * 10),
+                Instruction::Move(-i),
+            ]
+        })
+        .collect();
+    let memcpy = Macro::Memcpy {
+        src_offset: 1,
+        dst_offset: 3,
+        len: 4,
+    };
+
+    let mut lowered = setup.clone();
+    lowered.extend(memcpy.expand());
+    let mut spelled = setup;
+    spelled.extend(parse(&lex(&expand::to_source(&[memcpy.into()]).unwrap())).unwrap());
+
+    let tape = |program: &[Instruction]| {
+        let mut interpreter = Interpreter::new(InterpreterConfig::default());
+        interpreter.run(program).unwrap();
+        interpreter.tape().cells().to_vec()
+    };
+    assert_eq!(tape(&lowered), tape(&spelled));
+}
@@ -0,0 +1,49 @@
+use svolang::interpreter::{
+    CapturedOutput, Interpreter, InterpreterConfig, RunStatistics, WriteMode,
+};
+use svolang::parser::Instruction;
+
+fn statistics(program: &[Instruction]) -> RunStatistics {
+    let mut interpreter = Interpreter::new(InterpreterConfig::default());
+    interpreter.set_output(CapturedOutput::new());
+    interpreter.enable_statistics();
+    interpreter.run(program).unwrap();
+    interpreter.statistics().unwrap().clone()
+}
+
+#[test]
+fn printing_a_cell_does_not_write_it() {
+    let stats = statistics(&[Instruction::Add(65), Instruction::Write, Instruction::Write]);
+    assert_eq!(stats.cells_written, 1);
+    assert_eq!(stats.bytes_written, 2);
+}
+
+#[test]
+fn bulk_zero_and_multiply_add_write_every_cell_they_touch() {
+    let stats = statistics(&[
+        Instruction::BulkZero {
+            start_offset: -1,
+            len: 3,
+        },
+        Instruction::SetCell(2),
+        Instruction::MultiplyAdd {
+            offset: 4,
+            factor: 3,
+        },
+    ]);
+    assert_eq!(stats.cells_written, 4);
+}
+
+#[test]
+fn bytes_written_counts_encoded_bytes() {
+    let mut interpreter = Interpreter::new(InterpreterConfig {
+        write_mode: WriteMode::UnicodeCodepoint,
+        ..InterpreterConfig::default()
+    });
+    interpreter.set_output(CapturedOutput::new());
+    interpreter.enable_statistics();
+    interpreter
+        .run(&[Instruction::SetCell(200), Instruction::Write])
+        .unwrap();
+    assert_eq!(interpreter.statistics().unwrap().bytes_written, 2);
+}
@@ -0,0 +1,39 @@
+// svolang: `sv` followed by 1-8 `o`s is an opcode, `#` starts a line comment,
+// every other character is ignored
+module.exports = grammar({
+  name: 'svolang',
+
+  extras: $ => [$.comment, $.ignored_word, $._text],
+
+  rules: {
+    source_file: $ => repeat($._instruction),
+
+    _instruction: $ => choice(
+      $.increment,
+      $.decrement,
+      $.move_left,
+      $.move_right,
+      $.write,
+      $.read,
+      $.loop_expression,
+    ),
+
+    loop_expression: $ => seq($.loop_begin, repeat($._instruction), $.loop_end),
+
+    increment: _ => 'svo',
+    decrement: _ => 'svoo',
+    loop_begin: _ => 'svooo',
+    loop_end: _ => 'svoooo',
+    move_left: _ => 'svooooo',
+    move_right: _ => 'svoooooo',
+    write: _ => 'svooooooo',
+    read: _ => 'svoooooooo',
+
+    // `sv` with no `o`s or more than eight, the interpreter skips these
+    ignored_word: _ => /sv(o{9,})?/,
+
+    comment: _ => token(seq('#', /[^\n]*/)),
+
+    _text: _ => token(prec(-2, choice(/[^s#]+/, 's'))),
+  },
+});
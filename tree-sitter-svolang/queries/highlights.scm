@@ -0,0 +1,7 @@
+(comment) @comment
+(ignored_word) @error
+
+[(increment) (decrement)] @operator
+[(move_left) (move_right)] @keyword
+[(write) (read)] @function.builtin
+[(loop_begin) (loop_end)] @punctuation.bracket